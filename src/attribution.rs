@@ -0,0 +1,299 @@
+use forensic_rs::{
+    err::ForensicResult,
+    traits::registry::{RegHiveKey, RegValue, RegistryReader},
+};
+
+use crate::{
+    category::{profile_user, PathCategory},
+    common::PrefetchFile,
+    volume_path::relative_path,
+};
+
+/// Key of the SOFTWARE hive with the profile folder of each SID
+pub const PROFILE_LIST_KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\ProfileList";
+
+/// Profiles shared by every user, they do not point to a single user
+const SHARED_PROFILES: [&str; 4] = ["PUBLIC", "DEFAULT", "DEFAULT USER", "ALL USERS"];
+
+/// Profile folders of the service accounts inside the Windows folder, with their well-known SIDs
+const SERVICE_PROFILES: [(&str, &str); 3] = [
+    (r"\WINDOWS\SYSTEM32\CONFIG\SYSTEMPROFILE", "S-1-5-18"),
+    (r"\WINDOWS\SERVICEPROFILES\LOCALSERVICE", "S-1-5-19"),
+    (r"\WINDOWS\SERVICEPROFILES\NETWORKSERVICE", "S-1-5-20"),
+];
+
+/// Kind of account of a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileKind {
+    /// `\USERS\<name>` or `\DOCUMENTS AND SETTINGS\<name>`
+    User,
+    /// SYSTEM, LocalService or NetworkService
+    Service,
+}
+
+/// Profile of the ProfileList key
+#[derive(Debug, Clone, Default)]
+pub struct ProfileEntry {
+    pub sid: String,
+    /// Ex: `C:\Users\gorn` or `%systemroot%\system32\config\systemprofile`
+    pub profile_path: String,
+}
+
+impl ProfileEntry {
+    /// Last folder of the profile path. Ex: `gorn`
+    pub fn name(&self) -> &str {
+        self.profile_path
+            .trim_end_matches('\\')
+            .rsplit('\\')
+            .next()
+            .unwrap_or_default()
+    }
+}
+
+/// Profiles of the machine from the SOFTWARE hive
+#[derive(Debug, Clone, Default)]
+pub struct ProfileList {
+    pub profiles: Vec<ProfileEntry>,
+}
+
+impl ProfileList {
+    /// Reads the ProfileList key. A missing key returns an empty list.
+    pub fn from_registry(registry: &dyn RegistryReader) -> ForensicResult<Self> {
+        let key = match registry.open_key(RegHiveKey::HkeyLocalMachine, PROFILE_LIST_KEY) {
+            Ok(v) => v,
+            Err(_) => return Ok(Self::default()),
+        };
+        let mut profiles = Vec::new();
+        for sid in registry.enumerate_keys(key)? {
+            let profile_key = match registry.open_key(key, &sid) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let profile_path = match registry.read_value(profile_key, "ProfileImagePath") {
+                Ok(RegValue::ExpandSZ(v)) | Ok(RegValue::SZ(v)) => Some(v),
+                _ => None,
+            };
+            registry.close_key(profile_key);
+            if let Some(profile_path) = profile_path {
+                profiles.push(ProfileEntry { sid, profile_path });
+            }
+        }
+        registry.close_key(key);
+        Ok(Self { profiles })
+    }
+
+    /// SID of the profile with the folder name (ignoring case)
+    pub fn sid_of(&self, name: &str) -> Option<&str> {
+        self.profiles
+            .iter()
+            .find(|v| v.name().eq_ignore_ascii_case(name))
+            .map(|v| v.sid.as_str())
+    }
+}
+
+/// Profile with loaded files or directories of a prefetch
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserCandidate<'a> {
+    /// Folder name of the profile as stored in the prefetch. Ex: `GORN` or `SYSTEMPROFILE`
+    pub name: &'a str,
+    pub kind: ProfileKind,
+    /// From ProfileList, or the well-known SID of a service account
+    pub sid: Option<String>,
+    /// Loaded files inside the profile
+    pub files: usize,
+    /// Directories inside the profile
+    pub directories: usize,
+    /// Loaded files and directories in AppData or Temp, which are only written by the user of the profile
+    pub private_entries: usize,
+    /// The executable is inside the profile
+    pub executable: bool,
+    pub score: usize,
+    /// Share of the score of every candidate, from 0 to 1
+    pub confidence: f32,
+}
+
+/// Profiles that may have run the executable, best candidate first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserAttribution<'a> {
+    pub candidates: Vec<UserCandidate<'a>>,
+}
+
+impl<'a> UserAttribution<'a> {
+    pub fn best(&self) -> Option<&UserCandidate<'a>> {
+        self.candidates.first()
+    }
+
+    /// Candidates other than the best one
+    pub fn others(&self) -> &[UserCandidate<'a>] {
+        self.candidates.get(1..).unwrap_or_default()
+    }
+}
+
+/// Scores the profiles referenced by the loaded files and directories of a prefetch.
+/// Profile names are mapped to SIDs when a `ProfileList` is supplied.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{attribution::attribute_users, prelude::*};
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/30/C/Windows/Prefetch/RUST_OUT.EXE-5D2C8541.pf")).unwrap();
+/// let prefetch = read_prefetch_file("RUST_OUT.EXE-5D2C8541.pf", file).unwrap();
+/// let attribution = attribute_users(&prefetch, None);
+/// let best = attribution.best().unwrap();
+/// assert_eq!("GORN", best.name);
+/// assert!(best.executable);
+/// ```
+pub fn attribute_users<'a>(
+    prefetch: &'a PrefetchFile,
+    profiles: Option<&ProfileList>,
+) -> UserAttribution<'a> {
+    let executable = prefetch.executable_path();
+    let mut candidates: Vec<UserCandidate<'a>> = Vec::new();
+    let metrics = prefetch.metrics.iter().map(|v| (v.file.as_str(), true));
+    let directories = prefetch
+        .volume
+        .iter()
+        .flat_map(|v| v.directory_strings.iter())
+        .map(|v| (v.as_str(), false));
+    for (path, is_file) in metrics.chain(directories) {
+        let (name, kind, sid) = match profile_of(path) {
+            Some(v) => v,
+            None => continue,
+        };
+        let position = match candidates
+            .iter()
+            .position(|v| v.name.eq_ignore_ascii_case(name))
+        {
+            Some(v) => v,
+            None => {
+                candidates.push(UserCandidate {
+                    name,
+                    kind,
+                    sid: sid
+                        .map(|v| v.to_string())
+                        .or_else(|| profiles.and_then(|p| p.sid_of(name)).map(|v| v.to_string())),
+                    files: 0,
+                    directories: 0,
+                    private_entries: 0,
+                    executable: false,
+                    score: 0,
+                    confidence: 0.0,
+                });
+                candidates.len() - 1
+            }
+        };
+        let candidate = &mut candidates[position];
+        if is_file {
+            candidate.files += 1;
+        } else {
+            candidate.directories += 1;
+        }
+        if matches!(
+            PathCategory::of(path),
+            PathCategory::AppData | PathCategory::Temp
+        ) {
+            candidate.private_entries += 1;
+        }
+        if is_file && path == executable {
+            candidate.executable = true;
+        }
+    }
+    for candidate in candidates.iter_mut() {
+        candidate.score = candidate.files * 2
+            + candidate.directories
+            + candidate.private_entries * 3
+            + if candidate.executable { 10 } else { 0 };
+    }
+    let total: usize = candidates.iter().map(|v| v.score).sum();
+    for candidate in candidates.iter_mut() {
+        candidate.confidence = if total == 0 {
+            0.0
+        } else {
+            candidate.score as f32 / total as f32
+        };
+    }
+    // Stable sort: equal scores keep the order of appearance
+    candidates.sort_by_key(|v| std::cmp::Reverse(v.score));
+    UserAttribution { candidates }
+}
+
+/// Profile folder of a path: user name, kind and well-known SID
+fn profile_of(path: &str) -> Option<(&str, ProfileKind, Option<&'static str>)> {
+    if let Some(name) = profile_user(path) {
+        if SHARED_PROFILES.iter().any(|v| v.eq_ignore_ascii_case(name)) {
+            return None;
+        }
+        return Some((name, ProfileKind::User, None));
+    }
+    let relative = relative_path(path)?;
+    for (folder, sid) in SERVICE_PROFILES {
+        let inside = relative
+            .get(..folder.len())
+            .map(|v| v.eq_ignore_ascii_case(folder))
+            .unwrap_or(false);
+        let boundary = matches!(relative.as_bytes().get(folder.len()), None | Some(b'\\'));
+        if inside && boundary {
+            let name = &relative[folder.rfind('\\')? + 1..folder.len()];
+            return Some((name, ProfileKind::Service, Some(sid)));
+        }
+    }
+    None
+}
+
+#[test]
+fn should_attribute_several_users() {
+    use crate::common::{Metric, VolumeInformation};
+    use forensic_rs::utils::testing::TestingRegistry;
+    let volume = r"\VOLUME{01d861d36906cd20-22690e22}";
+    let metric = |file: &str| Metric {
+        file: format!("{}{}", volume, file),
+        ..Default::default()
+    };
+    let prefetch = PrefetchFile {
+        name: "TOOL.EXE".into(),
+        metrics: vec![
+            metric(r"\WINDOWS\SYSTEM32\NTDLL.DLL"),
+            metric(r"\USERS\ALICE\DOWNLOADS\TOOL.EXE"),
+            metric(r"\USERS\ALICE\APPDATA\LOCAL\TEMP\TOOL.TMP"),
+            metric(r"\USERS\PUBLIC\DESKTOP\DATA.TXT"),
+            metric(r"\USERS\BOB\APPDATA\ROAMING\CONFIG.INI"),
+            metric(r"\WINDOWS\SYSTEM32\CONFIG\SYSTEMPROFILE\APPDATA\X.DAT"),
+        ],
+        volume: vec![VolumeInformation {
+            device_path: volume.into(),
+            directory_strings: vec![
+                format!(r"{}\USERS\ALICE", volume),
+                format!(r"{}\USERS\ALICE\DOWNLOADS", volume),
+                format!(r"{}\USERS\BOB", volume),
+            ],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let mut registry = TestingRegistry::new();
+    registry.add_value(
+        &format!(r"HKLM\{}\S-1-5-21-1111-2222-3333-1001", PROFILE_LIST_KEY),
+        "ProfileImagePath",
+        RegValue::ExpandSZ(r"C:\Users\alice".into()),
+    );
+    let profiles = ProfileList::from_registry(&registry).unwrap();
+    let attribution = attribute_users(&prefetch, Some(&profiles));
+    assert_eq!(3, attribution.candidates.len());
+    let best = attribution.best().unwrap();
+    assert_eq!("ALICE", best.name);
+    assert!(best.executable);
+    assert_eq!(2, best.files);
+    assert_eq!(2, best.directories);
+    assert_eq!(Some("S-1-5-21-1111-2222-3333-1001"), best.sid.as_deref());
+    let service = attribution
+        .others()
+        .iter()
+        .find(|v| v.kind == ProfileKind::Service)
+        .unwrap();
+    assert_eq!("SYSTEMPROFILE", service.name);
+    assert_eq!(Some("S-1-5-18"), service.sid.as_deref());
+    let total: f32 = attribution.candidates.iter().map(|v| v.confidence).sum();
+    assert!((total - 1.0).abs() < 0.001);
+    assert!(attribution.candidates.iter().all(|v| v.name != "PUBLIC"));
+}
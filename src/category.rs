@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    common::PrefetchFile,
+    inventory::VolumeDeviceType,
+    volume_path::{dos_relative_path, VolumeId, VolumePath},
+};
+
+/// Kind of location of a loaded file or directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PathCategory {
+    /// `\WINDOWS\SYSTEM32`
+    System32,
+    /// `\WINDOWS\SYSWOW64`
+    SysWow64,
+    /// `\WINDOWS\WINSXS`
+    WinSxS,
+    /// Other folders of `\WINDOWS`
+    Windows,
+    /// `\PROGRAM FILES` and `\PROGRAM FILES (X86)`
+    ProgramFiles,
+    /// `\PROGRAMDATA`
+    ProgramData,
+    /// `\USERS\<user>` or `\DOCUMENTS AND SETTINGS\<user>` outside of the other user folders
+    UserProfile,
+    /// `\USERS\<user>\APPDATA` or `\DOCUMENTS AND SETTINGS\<user>\APPLICATION DATA`
+    AppData,
+    /// Temporary folders of the users and of Windows
+    Temp,
+    /// `\USERS\<user>\DOWNLOADS`
+    Downloads,
+    /// `\$RECYCLE.BIN`, `\RECYCLER` or `\RECYCLED`
+    RecycleBin,
+    /// `\PERFLOGS`
+    PerfLogs,
+    /// UNC path or network redirector device
+    Network,
+    /// CD-ROM or floppy device. USB disks are hard disk volumes, see `RemovableMediaDetector`
+    Removable,
+    #[default]
+    Unknown,
+}
+
+impl PathCategory {
+    /// Classifies a path of a prefetch (`\VOLUME{...}\...`, `\DEVICE\...\...`), a DOS path (`C:\...`) or a UNC path (`\\server\share\...`)
+    pub fn of(path: &str) -> Self {
+        let relative = match split_path(path) {
+            SplitPath::Relative(v) => v,
+            SplitPath::Network => return Self::Network,
+            SplitPath::Removable => return Self::Removable,
+            SplitPath::Unknown => return Self::Unknown,
+        };
+        let upper = relative.to_ascii_uppercase();
+        let mut components = upper.split('\\').filter(|v| !v.is_empty());
+        match components.next() {
+            Some("WINDOWS") => match components.next() {
+                Some("SYSTEM32") => Self::System32,
+                Some("SYSWOW64") => Self::SysWow64,
+                Some("WINSXS") => Self::WinSxS,
+                Some("TEMP") => Self::Temp,
+                _ => Self::Windows,
+            },
+            Some("PROGRAM FILES") | Some("PROGRAM FILES (X86)") => Self::ProgramFiles,
+            Some("PROGRAMDATA") => Self::ProgramData,
+            Some("PERFLOGS") => Self::PerfLogs,
+            Some("$RECYCLE.BIN") | Some("RECYCLER") | Some("RECYCLED") => Self::RecycleBin,
+            Some("USERS") | Some("DOCUMENTS AND SETTINGS") => {
+                if components.next().is_none() {
+                    return Self::Unknown;
+                }
+                match (components.next(), components.next(), components.next()) {
+                    (Some("APPDATA"), Some("LOCAL"), Some("TEMP")) => Self::Temp,
+                    (Some("LOCAL SETTINGS"), Some("TEMP"), _) => Self::Temp,
+                    (Some("APPDATA"), _, _) | (Some("APPLICATION DATA"), _, _) => Self::AppData,
+                    (Some("LOCAL SETTINGS"), Some("APPLICATION DATA"), _) => Self::AppData,
+                    (Some("DOWNLOADS"), _, _) => Self::Downloads,
+                    _ => Self::UserProfile,
+                }
+            }
+            _ => Self::Unknown,
+        }
+    }
+
+    /// A standard user can write in the folder without elevation
+    pub fn is_user_writable(&self) -> bool {
+        matches!(
+            self,
+            Self::UserProfile
+                | Self::AppData
+                | Self::Temp
+                | Self::Downloads
+                | Self::RecycleBin
+                | Self::ProgramData
+                | Self::PerfLogs
+        )
+    }
+}
+
+/// User folder of a path inside a profile. Ex: `GORN` for `\VOLUME{...}\USERS\GORN\APPDATA`
+pub fn profile_user(path: &str) -> Option<&str> {
+    let relative = match split_path(path) {
+        SplitPath::Relative(v) => v,
+        _ => return None,
+    };
+    let mut components = relative.split('\\').filter(|v| !v.is_empty());
+    let root = components.next()?;
+    if !root.eq_ignore_ascii_case("USERS") && !root.eq_ignore_ascii_case("DOCUMENTS AND SETTINGS") {
+        return None;
+    }
+    components.next()
+}
+
+/// Number of loaded files and directories of a prefetch in each category
+#[derive(Debug, Clone, Default)]
+pub struct PathCategorySummary {
+    /// Category of the executable, Unknown when its path is not in the metrics
+    pub executable: PathCategory,
+    pub files: BTreeMap<PathCategory, usize>,
+    pub directories: BTreeMap<PathCategory, usize>,
+}
+
+impl PathCategorySummary {
+    pub fn new(prefetch: &PrefetchFile) -> Self {
+        let mut summary = Self {
+            executable: PathCategory::of(prefetch.executable_path()),
+            ..Default::default()
+        };
+        for metric in &prefetch.metrics {
+            *summary.files.entry(metric.category()).or_default() += 1;
+        }
+        for volume in &prefetch.volume {
+            for directory in &volume.directory_strings {
+                *summary
+                    .directories
+                    .entry(PathCategory::of(directory))
+                    .or_default() += 1;
+            }
+        }
+        summary
+    }
+
+    /// Number of loaded files in the category
+    pub fn files_in(&self, category: PathCategory) -> usize {
+        self.files.get(&category).copied().unwrap_or_default()
+    }
+
+    /// Number of directories in the category
+    pub fn directories_in(&self, category: PathCategory) -> usize {
+        self.directories.get(&category).copied().unwrap_or_default()
+    }
+
+    /// Some loaded file or directory is in the category
+    pub fn contains(&self, category: PathCategory) -> bool {
+        self.files_in(category) > 0 || self.directories_in(category) > 0
+    }
+}
+
+enum SplitPath<'a> {
+    Relative(&'a str),
+    Network,
+    Removable,
+    Unknown,
+}
+
+fn split_path(path: &str) -> SplitPath<'_> {
+    if let Some(volume_path) = VolumePath::parse(path) {
+        return match volume_path.volume {
+            VolumeId::Device(device) => match VolumeDeviceType::from_device_path(device) {
+                VolumeDeviceType::Network => SplitPath::Network,
+                VolumeDeviceType::CdRom | VolumeDeviceType::Floppy => SplitPath::Removable,
+                _ => SplitPath::Relative(volume_path.relative),
+            },
+            VolumeId::Identifier { .. } => SplitPath::Relative(volume_path.relative),
+        };
+    }
+    if path.starts_with(r"\\") {
+        return SplitPath::Network;
+    }
+    match dos_relative_path(path) {
+        Some(relative) => SplitPath::Relative(relative),
+        None => SplitPath::Unknown,
+    }
+}
+
+#[test]
+fn should_classify_paths() {
+    let cases = [
+        (
+            r"\VOLUME{01d861d36906cd20-22690e22}\WINDOWS\SYSTEM32\NTDLL.DLL",
+            PathCategory::System32,
+        ),
+        (
+            r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSWOW64\KERNEL32.DLL",
+            PathCategory::SysWow64,
+        ),
+        (
+            r"C:\Windows\WinSxS\amd64_microsoft.windows.common-controls\COMCTL32.DLL",
+            PathCategory::WinSxS,
+        ),
+        (r"C:\WINDOWS\FONTS\ARIAL.TTF", PathCategory::Windows),
+        (r"C:\WINDOWS\TEMP\A.EXE", PathCategory::Temp),
+        (
+            r"\DEVICE\HARDDISKVOLUME2\PROGRAM FILES (X86)\APP\APP.EXE",
+            PathCategory::ProgramFiles,
+        ),
+        (r"C:\PROGRAMDATA\X.DLL", PathCategory::ProgramData),
+        (r"C:\USERS\GORN\DESKTOP\A.EXE", PathCategory::UserProfile),
+        (
+            r"C:\USERS\GORN\APPDATA\ROAMING\A.EXE",
+            PathCategory::AppData,
+        ),
+        (
+            r"C:\USERS\GORN\APPDATA\LOCAL\TEMP\RUST_OUT.EXE",
+            PathCategory::Temp,
+        ),
+        (
+            r"C:\DOCUMENTS AND SETTINGS\ADMIN\LOCAL SETTINGS\TEMP\A.EXE",
+            PathCategory::Temp,
+        ),
+        (
+            r"C:\DOCUMENTS AND SETTINGS\ADMIN\APPLICATION DATA\A.EXE",
+            PathCategory::AppData,
+        ),
+        (
+            r"C:\USERS\GORN\DOWNLOADS\SETUP.EXE",
+            PathCategory::Downloads,
+        ),
+        (
+            r"C:\$RECYCLE.BIN\S-1-5-21\$R1.EXE",
+            PathCategory::RecycleBin,
+        ),
+        (r"C:\PERFLOGS\A.EXE", PathCategory::PerfLogs),
+        (r"\\SERVER\SHARE\A.EXE", PathCategory::Network),
+        (
+            r"\DEVICE\MUP\;LANMANREDIRECTOR\SERVER\SHARE\A.EXE",
+            PathCategory::Network,
+        ),
+        (r"\DEVICE\CDROM0\SETUP.EXE", PathCategory::Removable),
+        (r"C:\USERS", PathCategory::Unknown),
+        (r"C:\TOOLS\A.EXE", PathCategory::Unknown),
+        ("NOTEPAD.EXE", PathCategory::Unknown),
+    ];
+    for (path, category) in cases {
+        assert_eq!(category, PathCategory::of(path), "{}", path);
+    }
+    assert_eq!(
+        Some("GORN"),
+        profile_user(r"\VOLUME{01d861d36906cd20-22690e22}\USERS\GORN\APPDATA")
+    );
+    assert_eq!(None, profile_user(r"C:\WINDOWS\SYSTEM32"));
+}
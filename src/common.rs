@@ -10,6 +10,24 @@ use forensic_rs::{
     utils::time::Filetime,
 };
 
+use crate::{
+    attribution::{attribute_users, ProfileList, UserAttribution},
+    category::{PathCategory, PathCategorySummary},
+    consistency::{TimestampAnalyzer, TimestampFinding},
+    executable::{resolve_executable_path, ExecutablePath},
+    first_run::{estimate_first_run, FirstRunEstimate, FirstRunSource, PrefetchFileTimes},
+    hash::{verify_prefetch_hash, PrefetchHashCheck},
+    hosting::is_hosting_application,
+    provenance::PrefetchProvenance,
+    references::{correlate_file_references, VolumeReferences},
+    runs::{run_dependencies, single_run_dependencies, RunDependencies},
+    trace::TraceChain,
+    volume_path::VolumePath,
+};
+
+/// Time context of the timeline event with the estimated first execution
+pub const FIRST_RUN_TIME_CONTEXT: &str = "FirstExecution";
+
 /// By default blocks will be loaded into executable memory sections
 pub const FLAG_PROGRAM_BLOCK_EXECUTABLE: u32 = 0x0200;
 
@@ -33,8 +51,12 @@ pub const FLAG_BLOCK_DONT_PREFETCH: u8 = 0x01;
 pub struct PrefetchFile {
     /// Prefetch file version
     pub version: u32,
+    /// Name of the `.pf` file as supplied to the parser, without folders. Ex: `CMD.EXE-087B4001.pf`
+    pub file_name: String,
     /// Executable name
     pub name: String,
+    /// Hash of the executable path stored in the prefetch header
+    pub hash: u32,
     /// List of DLLs/EXEs loaded by the executable
     pub metrics: Vec<Metric>,
     /// Last execution times (max 8)
@@ -43,9 +65,52 @@ pub struct PrefetchFile {
     pub run_count: u32,
     /// Information about the disks and other volumes
     pub volume: Vec<VolumeInformation>,
+    /// File header as stored in the prefetch
+    pub header: PrefetchHeader,
+    /// Version dependent file information as stored in the prefetch
+    pub file_information: PrefetchFileInformation,
+    /// Position of each value in the decompressed file. Only filled in provenance mode.
+    pub provenance: Option<PrefetchProvenance>,
+    /// Timestamps of the `.pf` file when it was read from a filesystem
+    pub file_times: Option<PrefetchFileTimes>,
+    /// Position in `metrics` of the executable chosen by `resolve_executable` when the file was parsed.
+    /// Set it again after adding or removing metrics
+    pub executable_index: Option<usize>,
+    /// Executable path re-hashed and checked against the stored hash when the file was parsed
+    pub hash_check: Option<PrefetchHashCheck>,
+}
+
+/// File header (84 bytes) shared by all the prefetch versions
+#[derive(Clone, Debug, Default)]
+pub struct PrefetchHeader {
+    /// Format version
+    pub version: u32,
+    /// Signature, must be "SCCA"
+    pub signature: [u8; 4],
+    /// Unknown value at offset 8. Seen 15 in version 17 and 17 in newer versions
+    pub unknown_8: u32,
+    /// File size declared in the header
+    pub file_size: u32,
+    /// Executable name (max 29 characters)
+    pub executable_name: String,
+    /// Prefetch hash
+    pub hash: u32,
+    /// Flags at offset 80
+    pub flags: u32,
+}
+
+/// Raw bytes of a field with unknown meaning
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnknownField {
+    /// Offset from the start of the (decompressed) prefetch file
+    pub offset: usize,
+    pub data: Vec<u8>,
 }
+
 #[derive(Clone, Debug, Default)]
 pub struct PrefetchFileInformation {
+    /// Size of the file information structure: 68 (v17), 156 (v23), 224 (v26 and v30 variant 1) or 212 (v30 variant 2)
+    pub size: u32,
     pub metrics_offsets: u32,
     pub metrics_count: u32,
     pub trace_chain_offset: u32,
@@ -55,8 +120,19 @@ pub struct PrefetchFileInformation {
     pub volume_information_offset: u32,
     pub volume_count: u32,
     pub volume_information_size: u32,
+    /// Last execution times, empty slots are skipped
     pub last_run_times: Vec<Filetime>,
+    /// Raw value of each last execution time slot, including the empty ones
+    pub last_run_time_slots: Vec<u64>,
     pub run_count: u32,
+    /// Offset of the string used to compute the prefetch hash (version 30 variant 2)
+    pub hash_string_offset: u32,
+    /// Size in bytes of the string used to compute the prefetch hash (version 30 variant 2)
+    pub hash_string_size: u32,
+    /// Device path used to compute the prefetch hash (version 30 variant 2). Ex: `\DEVICE\HARDDISKVOLUME3\WINDOWS\SYSWOW64\CMD.EXE`
+    pub hash_string: Option<String>,
+    /// Fields of the structure with unknown meaning
+    pub unknown: Vec<UnknownField>,
 }
 
 /// Files loaded by the executable
@@ -68,14 +144,16 @@ pub struct Metric {
     pub flags: PrefetchFlag,
     /// Number of blocks to be prefetched
     pub blocks_to_prefetch: u32,
-    /// Traces for this dependency
-    pub traces: Vec<Trace>,
+    /// Trace chain entries with every stored field and the anomalies found while following the chain
+    pub trace_chain: TraceChain,
+    /// NTFS reference of the file stored in the metric entry (version 23 and later)
+    pub mft_reference: Option<NtfsFile>,
 }
 #[derive(Debug, Clone, Default)]
 pub struct Trace {
     /// Flags for loading blocks: executable, resource, non-prefetchable or force prefetch.
     pub flags: BlockFlags,
-    /// Memory block offset
+    /// Memory block offset. libscca names this field the total block load count
     pub block_offset: u32,
     /// Stores whether the block was used in each of the last eight runs (1 bit each)
     pub used_bitfield: u8,
@@ -86,6 +164,9 @@ pub struct Trace {
 pub struct PrefetchFlag(u32);
 
 impl PrefetchFlag {
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
     pub fn is_executable(&self) -> bool {
         self.0 & FLAG_PROGRAM_BLOCK_EXECUTABLE > 0
     }
@@ -148,6 +229,9 @@ impl core::fmt::Display for PrefetchFlag {
 pub struct BlockFlags(u8);
 
 impl BlockFlags {
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
     pub fn is_executable(&self) -> bool {
         self.0 & FLAG_BLOCK_EXECUTABLE > 0
     }
@@ -229,12 +313,30 @@ pub struct VolumeInformation {
     pub serial_number: u32,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct NtfsFile {
     pub mft_entry: u64,
     pub seq_number: u16,
 }
 
+impl NtfsFile {
+    /// Splits a 64 bit file reference into the MFT entry (48 bits) and the sequence number (16 bits).
+    /// Empty references return None.
+    pub fn from_reference(reference: u64) -> Option<Self> {
+        let mft_entry = reference & 0xffffffffffff;
+        if mft_entry == 0 {
+            return None;
+        }
+        Some(Self {
+            mft_entry,
+            seq_number: (reference >> 48) as u16,
+        })
+    }
+    pub fn to_reference(&self) -> u64 {
+        (self.mft_entry & 0xffffffffffff) | ((self.seq_number as u64) << 48)
+    }
+}
+
 pub fn utf16_at_offset(file_buffer: &[u8], offset: usize, size: usize) -> ForensicResult<String> {
     let end_pos = offset + size;
     if end_pos > file_buffer.len() {
@@ -242,14 +344,17 @@ pub fn utf16_at_offset(file_buffer: &[u8], offset: usize, size: usize) -> Forens
             "The utf16 string position is greater than the file buffer",
         ));
     }
-    let txt = &file_buffer[offset..end_pos];
-    let txt_u16: &[u16] = unsafe { std::mem::transmute(txt) };
-    let end = txt_u16
-        .iter()
-        .position(|&v| v == 0)
-        .unwrap_or(txt_u16.len());
-    let txt = String::from_utf16_lossy(&txt_u16[0..end]);
-    Ok(txt)
+    Ok(utf16_from_bytes(&file_buffer[offset..end_pos]))
+}
+
+/// Decodes a NUL terminated UTF-16LE string from a byte buffer
+pub fn utf16_from_bytes(buffer: &[u8]) -> String {
+    let characters: Vec<u16> = buffer
+        .chunks_exact(2)
+        .map(|v| u16::from_le_bytes([v[0], v[1]]))
+        .take_while(|&v| v != 0)
+        .collect();
+    String::from_utf16_lossy(&characters)
 }
 
 pub fn u16_at_pos(buffer: &[u8], pos: usize) -> u16 {
@@ -263,8 +368,28 @@ pub fn u64_at_pos(buffer: &[u8], pos: usize) -> u64 {
 }
 
 impl Metric {
+    /// MFT entry and sequence number of the loaded file, to be checked against the $MFT of the volume
+    pub fn file_reference(&self) -> Option<&NtfsFile> {
+        self.mft_reference.as_ref()
+    }
+    /// Volume and path inside the volume of the loaded file
+    pub fn volume_path(&self) -> Option<VolumePath<'_>> {
+        VolumePath::parse(&self.file)
+    }
+    /// Path of the loaded file inside its volume. Ex: `\WINDOWS\SYSTEM32\NTDLL.DLL`
+    pub fn relative_path(&self) -> Option<&str> {
+        self.volume_path().map(|v| v.relative)
+    }
+    /// Category of the location of the loaded file
+    pub fn category(&self) -> PathCategory {
+        PathCategory::of(&self.file)
+    }
+    /// Traces for this dependency, derived from the trace chain
+    pub fn traces(&self) -> impl Iterator<Item = Trace> + '_ {
+        self.trace_chain.traces()
+    }
     pub fn has_executable_block(&self) -> bool {
-        for trace in self.traces.iter() {
+        for trace in self.trace_chain.entries.iter() {
             if trace.flags.is_executable() {
                 return true;
             }
@@ -278,62 +403,104 @@ impl PrefetchFile {
         PrefetchFile::default()
     }
 
+    /// Re-hashes the executable path and checks it against the hash stored in the header.
+    /// The parser stores the result in `hash_check`.
+    pub fn verify_hash(&self) -> PrefetchHashCheck {
+        verify_prefetch_hash(self)
+    }
+
+    /// The executable is a hosting application (svchost, rundll32, dllhost or mmc) and its hash includes the command line.
+    pub fn is_hosting_application(&self) -> bool {
+        is_hosting_application(&self.name)
+    }
+
+    /// Files with used blocks in each of the last runs (up to 8). None when the run bits of the version are unknown (17).
+    /// The run bits are not matched with `last_run_times`, see [`normalize_run_bitfield`](crate::runs::normalize_run_bitfield).
+    pub fn run_dependencies(&self) -> Option<Vec<RunDependencies<'_>>> {
+        run_dependencies(self)
+    }
+
+    /// Files with used blocks in a single one of the last runs, with the position of that run bit.
+    pub fn single_run_dependencies(&self) -> Option<Vec<(&Metric, usize)>> {
+        single_run_dependencies(self)
+    }
+
+    /// Volume of a metric or directory path
+    pub fn volume_of(&self, path: &str) -> Option<&VolumeInformation> {
+        let position = VolumePath::parse(path)?.position_in(&self.volume)?;
+        self.volume.get(position)
+    }
+
+    /// File references of each volume linked to the metrics and directories they belong to
+    pub fn volume_references(&self) -> Vec<VolumeReferences<'_>> {
+        correlate_file_references(self)
+    }
+
+    /// Number of loaded files and directories in each path category
+    pub fn path_categories(&self) -> PathCategorySummary {
+        PathCategorySummary::new(self)
+    }
+
+    /// Full path of the executable, or the name of the header when no metric matches it. See `resolve_executable`.
+    /// Uses the metric resolved when parsing, and only ranks the metrics again when `executable_index` is not set
     pub fn executable_path(&self) -> &str {
-        for loaded in &self.metrics {
-            if loaded.file.ends_with(&self.name) {
-                return &loaded.file;
-            }
+        match self.executable_index.and_then(|i| self.metrics.get(i)) {
+            Some(metric) => &metric.file,
+            None => resolve_executable_path(self).path,
         }
-        &self.name
     }
-    /// Gets for which user was the program executed. Its not precise.
+
+    /// Metrics that match the (possibly truncated) executable name, ranked with the path hash, and the confidence of the choice
+    pub fn resolve_executable(&self) -> ExecutablePath<'_> {
+        resolve_executable_path(self)
+    }
+    /// Estimated first execution: the oldest run time when the run count fits in them, else the creation time of the `.pf` file
+    pub fn first_run_estimate(&self) -> Option<FirstRunEstimate> {
+        estimate_first_run(self)
+    }
+
+    /// Timestamp inconsistencies left by wiping or timestomping, checked against the current time
+    pub fn timestamp_findings(&self) -> Vec<TimestampFinding> {
+        TimestampAnalyzer::new().analyze(self)
+    }
+
+    /// Gets for which user was the program executed. Its not precise: it is the best candidate of `attribute_users`.
     pub fn user(&self) -> Option<&str> {
-        for volume in &self.volume {
-            for file in &volume.directory_strings {
-                if !file.starts_with(r"\") {
-                    continue;
-                }
-                let filename = &file[1..];
-                let mut splited = filename.split(r"\");
-                if splited.next().is_none() {
-                    continue;
-                };
-                match splited.next() {
-                    Some("USERS") => {}
-                    _ => continue,
-                };
-                let user = match splited.next() {
-                    Some(v) => v,
-                    None => continue,
-                };
-                match splited.next() {
-                    Some("APPDATA") => {}
-                    _ => continue,
-                };
-                return Some(user);
-            }
-        }
-        None
+        self.attribute_users(None).best().map(|v| v.name)
+    }
+
+    /// Every profile with loaded files or directories in the prefetch, scored by the evidence found. SIDs are resolved with the ProfileList when supplied.
+    pub fn attribute_users(&self, profiles: Option<&ProfileList>) -> UserAttribution<'_> {
+        attribute_users(self, profiles)
     }
 }
 
 pub struct PrefetchTimelineIterator<'a> {
     prefetch: &'a PrefetchFile,
     time_pos: usize,
+    /// First execution older than the last run times, estimated from the creation of the `.pf` file
+    first_run: Option<FirstRunEstimate>,
 }
-impl<'a> Iterator for PrefetchTimelineIterator<'a> {
-    type Item = TimelineData;
-    fn next(&mut self) -> Option<Self::Item> {
-        let actual_pos = self.time_pos;
-        if actual_pos >= self.prefetch.last_run_times.len() {
-            return None;
+impl<'a> PrefetchTimelineIterator<'a> {
+    pub fn new(prefetch: &'a PrefetchFile) -> Self {
+        Self {
+            prefetch,
+            time_pos: 0,
+            first_run: prefetch
+                .first_run_estimate()
+                .filter(|v| v.source == FirstRunSource::FileCreation),
         }
-        self.time_pos += 1;
+    }
+
+    fn remaining(&self) -> usize {
+        let runs = self.prefetch.last_run_times.len();
+        runs.saturating_sub(self.time_pos)
+            + (self.first_run.is_some() && self.time_pos <= runs) as usize
+    }
+
+    fn execution_data(&self, time: Filetime) -> ForensicData {
         let mut data = ForensicData::default();
-        data.add_field(
-            FILE_ACCESSED,
-            Field::Date(self.prefetch.last_run_times[actual_pos]),
-        );
+        data.add_field(FILE_ACCESSED, Field::Date(time));
         data.add_field(FILE_PATH, Field::Path(PathBuf::from(&self.prefetch.name)));
         let dependencies: Vec<Text> = self
             .prefetch
@@ -351,20 +518,64 @@ impl<'a> Iterator for PrefetchTimelineIterator<'a> {
             }
         }
         data.add_field("prefetch.volume_files", Field::Array(volume_files));
+        data
+    }
+}
+impl<'a> Iterator for PrefetchTimelineIterator<'a> {
+    type Item = TimelineData;
+    fn next(&mut self) -> Option<Self::Item> {
+        let actual_pos = self.time_pos;
+        if actual_pos > self.prefetch.last_run_times.len() {
+            return None;
+        }
+        self.time_pos += 1;
+        if actual_pos == self.prefetch.last_run_times.len() {
+            // Extra event with the first execution after the last run times. When the oldest run time is the
+            // first execution it already has its own event.
+            let estimate = self.first_run?;
+            return Some(TimelineData {
+                time: estimate.time,
+                data: self.execution_data(estimate.time),
+                time_context: TimeContext::Other(Cow::Borrowed(FIRST_RUN_TIME_CONTEXT)),
+            });
+        }
+        let time = self.prefetch.last_run_times[actual_pos];
         Some(TimelineData {
-            time: self.prefetch.last_run_times[actual_pos],
-            data,
+            time,
+            data: self.execution_data(time),
             time_context: TimeContext::Accessed,
         })
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.time_pos, Some(self.prefetch.last_run_times.len()))
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
     }
 }
 
 pub struct PrefetchActivityIterator<'a> {
     prefetch: &'a PrefetchFile,
     time_pos: usize,
+    attribution: UserAttribution<'a>,
+}
+
+impl<'a> PrefetchActivityIterator<'a> {
+    pub fn new(prefetch: &'a PrefetchFile) -> Self {
+        Self::with_profiles(prefetch, None)
+    }
+
+    /// The user of each activity is the SID of the best candidate when the ProfileList knows it, or its profile name
+    pub fn with_profiles(prefetch: &'a PrefetchFile, profiles: Option<&ProfileList>) -> Self {
+        Self {
+            prefetch,
+            time_pos: 0,
+            attribution: attribute_users(prefetch, profiles),
+        }
+    }
+
+    /// Candidate users of the activities. The best one is used, the others are kept here
+    pub fn attribution(&self) -> &UserAttribution<'a> {
+        &self.attribution
+    }
 }
 impl<'a> Iterator for PrefetchActivityIterator<'a> {
     type Item = ForensicActivity;
@@ -378,58 +589,63 @@ impl<'a> Iterator for PrefetchActivityIterator<'a> {
             timestamp: self.prefetch.last_run_times[actual_pos],
             activity: ProgramExecution::new(self.prefetch.executable_path().to_string()).into(),
             user: self
-                .prefetch
-                .user()
-                .map(|v| v.to_string())
+                .attribution
+                .best()
+                .map(|v| v.sid.as_deref().unwrap_or(v.name).to_string())
                 .unwrap_or_default(),
             session_id: SessionId::Unknown,
         })
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.time_pos, Some(self.prefetch.last_run_times.len()))
+        let remaining = self
+            .prefetch
+            .last_run_times
+            .len()
+            .saturating_sub(self.time_pos);
+        (remaining, Some(remaining))
     }
 }
 
 impl<'a> IntoActivity<'a> for &'a PrefetchFile {
     fn activity(&'a self) -> Self::IntoIter {
-        PrefetchActivityIterator {
-            prefetch: self,
-            time_pos: 0,
-        }
+        PrefetchActivityIterator::new(self)
     }
 
-    type IntoIter = PrefetchActivityIterator<'a> where Self: 'a;
+    type IntoIter
+        = PrefetchActivityIterator<'a>
+    where
+        Self: 'a;
 }
 
 impl<'a> IntoActivity<'a> for PrefetchFile {
     fn activity(&'a self) -> Self::IntoIter {
-        PrefetchActivityIterator {
-            prefetch: self,
-            time_pos: 0,
-        }
+        PrefetchActivityIterator::new(self)
     }
 
-    type IntoIter = PrefetchActivityIterator<'a> where Self: 'a;
+    type IntoIter
+        = PrefetchActivityIterator<'a>
+    where
+        Self: 'a;
 }
 
 impl<'a> IntoTimeline<'a> for &'a PrefetchFile {
     fn timeline(&'a self) -> Self::IntoIter {
-        PrefetchTimelineIterator {
-            prefetch: self,
-            time_pos: 0,
-        }
+        PrefetchTimelineIterator::new(self)
     }
 
-    type IntoIter = PrefetchTimelineIterator<'a> where Self: 'a;
+    type IntoIter
+        = PrefetchTimelineIterator<'a>
+    where
+        Self: 'a;
 }
 
 impl<'a> IntoTimeline<'a> for PrefetchFile {
     fn timeline(&'a self) -> Self::IntoIter {
-        PrefetchTimelineIterator {
-            prefetch: self,
-            time_pos: 0,
-        }
+        PrefetchTimelineIterator::new(self)
     }
 
-    type IntoIter = PrefetchTimelineIterator<'a> where Self: 'a;
+    type IntoIter
+        = PrefetchTimelineIterator<'a>
+    where
+        Self: 'a;
 }
@@ -0,0 +1,288 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use forensic_rs::utils::time::Filetime;
+
+use crate::{common::PrefetchFile, first_run::unix_to_filetime, volume_path::VolumePath};
+
+/// Inconsistency between the timestamps of a prefetch file
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimestampFindingKind {
+    /// The run time of the slot is newer than the one of the previous slot. Slot 0 is the most recent run.
+    RunTimesOutOfOrder { slot: usize },
+    /// The slot has the same run time as a previous slot
+    DuplicatedRunTime { slot: usize, duplicate_of: usize },
+    /// The run time is after the reference time (acquisition time or now)
+    RunTimeInFuture { slot: usize, time: Filetime },
+    /// The run time is before the creation of a volume that must have existed at that time
+    RunTimeBeforeVolumeCreation {
+        slot: usize,
+        time: Filetime,
+        serial_number: u32,
+        creation_time: Filetime,
+    },
+    /// The run count is lower than the number of stored run times
+    RunCountBelowRunTimes { run_count: u32, run_times: usize },
+    /// Slots that should have a run time for the run count are empty
+    ZeroedRunTimeSlots { run_count: u32, slots: Vec<usize> },
+    /// The `.pf` file was modified before the last run, but Windows writes it after each run
+    FileModifiedBeforeLastRun {
+        modified: Filetime,
+        last_run: Filetime,
+    },
+}
+
+/// How strongly the finding points to tampering rather than clock changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FindingSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestampFinding {
+    pub kind: TimestampFindingKind,
+    pub severity: FindingSeverity,
+}
+
+impl TimestampFinding {
+    fn new(kind: TimestampFindingKind) -> Self {
+        let severity = match &kind {
+            TimestampFindingKind::RunTimeInFuture { .. }
+            | TimestampFindingKind::RunTimesOutOfOrder { .. }
+            | TimestampFindingKind::DuplicatedRunTime { .. }
+            | TimestampFindingKind::ZeroedRunTimeSlots { .. } => FindingSeverity::Medium,
+            TimestampFindingKind::RunTimeBeforeVolumeCreation { .. }
+            | TimestampFindingKind::RunCountBelowRunTimes { .. }
+            | TimestampFindingKind::FileModifiedBeforeLastRun { .. } => FindingSeverity::High,
+        };
+        Self { kind, severity }
+    }
+}
+
+/// Checks the run times, run count and file timestamps of prefetch files for the traces left by wiping and timestomping tools.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{consistency::*, prelude::*};
+/// let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+/// let mut prefetch = scan_prefetch_report(&mut fs).unwrap().prefetches.remove(0);
+/// let analyzer = TimestampAnalyzer::new();
+/// assert!(analyzer.analyze(&prefetch).is_empty());
+/// prefetch.run_count = 0;
+/// let findings = analyzer.analyze(&prefetch);
+/// assert!(matches!(findings[0].kind, TimestampFindingKind::RunCountBelowRunTimes { run_count: 0, .. }));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampAnalyzer {
+    /// Run times after this time are in the future. Ex: the acquisition time of the image
+    pub reference_time: Filetime,
+    /// Margin for clock differences and filesystem timestamp resolution. Concurrent runs can be stored slightly out of order.
+    pub tolerance: Duration,
+    /// Margin for runs before the volume creation. Windows setup creates the volume before fixing the time zone.
+    pub volume_tolerance: Duration,
+}
+
+impl Default for TimestampAnalyzer {
+    fn default() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self {
+            reference_time: unix_to_filetime(now),
+            tolerance: Duration::from_secs(2),
+            volume_tolerance: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl TimestampAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the acquisition time of the evidence to find run times in the future
+    pub fn with_reference_time(reference_time: Filetime) -> Self {
+        Self {
+            reference_time,
+            ..Default::default()
+        }
+    }
+
+    pub fn analyze(&self, prefetch: &PrefetchFile) -> Vec<TimestampFinding> {
+        let mut findings = Vec::new();
+        let slots = run_time_slots(prefetch);
+        let tolerance = self.tolerance.as_nanos() as u64 / 100;
+        let run_times: Vec<(usize, u64)> = slots
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v != 0)
+            .map(|(i, v)| (i, *v))
+            .collect();
+        // Order and duplicates: each slot must be older than the previous ones
+        for (position, (slot, time)) in run_times.iter().enumerate() {
+            if let Some((duplicate_of, _)) = run_times[..position].iter().find(|(_, v)| v == time) {
+                findings.push(TimestampFinding::new(
+                    TimestampFindingKind::DuplicatedRunTime {
+                        slot: *slot,
+                        duplicate_of: *duplicate_of,
+                    },
+                ));
+            } else if position > 0 && *time > run_times[position - 1].1.saturating_add(tolerance) {
+                findings.push(TimestampFinding::new(
+                    TimestampFindingKind::RunTimesOutOfOrder { slot: *slot },
+                ));
+            }
+        }
+        let reference = self.reference_time.filetime().saturating_add(tolerance);
+        for (slot, time) in &run_times {
+            if *time > reference {
+                findings.push(TimestampFinding::new(
+                    TimestampFindingKind::RunTimeInFuture {
+                        slot: *slot,
+                        time: Filetime::new(*time),
+                    },
+                ));
+            }
+        }
+        self.check_volume_creation(prefetch, &run_times, &mut findings);
+        if (prefetch.run_count as usize) < run_times.len() {
+            findings.push(TimestampFinding::new(
+                TimestampFindingKind::RunCountBelowRunTimes {
+                    run_count: prefetch.run_count,
+                    run_times: run_times.len(),
+                },
+            ));
+        }
+        let expected = (prefetch.run_count as usize).min(slots.len());
+        let zeroed: Vec<usize> = (0..expected).filter(|i| slots[*i] == 0).collect();
+        if !zeroed.is_empty() {
+            findings.push(TimestampFinding::new(
+                TimestampFindingKind::ZeroedRunTimeSlots {
+                    run_count: prefetch.run_count,
+                    slots: zeroed,
+                },
+            ));
+        }
+        let modified = prefetch.file_times.and_then(|v| v.modified);
+        let last_run = run_times.iter().map(|(_, v)| *v).max();
+        if let (Some(modified), Some(last_run)) = (modified, last_run) {
+            if modified.filetime().saturating_add(tolerance) < last_run {
+                findings.push(TimestampFinding::new(
+                    TimestampFindingKind::FileModifiedBeforeLastRun {
+                        modified,
+                        last_run: Filetime::new(last_run),
+                    },
+                ));
+            }
+        }
+        findings
+    }
+
+    /// Every run must be after the creation of the volume of the executable, and the last run after the creation of every volume
+    fn check_volume_creation(
+        &self,
+        prefetch: &PrefetchFile,
+        run_times: &[(usize, u64)],
+        findings: &mut Vec<TimestampFinding>,
+    ) {
+        let executable_volume = VolumePath::parse(prefetch.executable_path())
+            .and_then(|v| v.position_in(&prefetch.volume));
+        let last_run = run_times.iter().max_by_key(|(_, v)| *v);
+        let tolerance = self.volume_tolerance.as_nanos() as u64 / 100;
+        for (position, volume) in prefetch.volume.iter().enumerate() {
+            if volume.creation_time == 0 {
+                continue;
+            }
+            let checked: Vec<&(usize, u64)> = if executable_volume == Some(position) {
+                run_times.iter().collect()
+            } else {
+                last_run.into_iter().collect()
+            };
+            for (slot, time) in checked {
+                if time.saturating_add(tolerance) < volume.creation_time {
+                    findings.push(TimestampFinding::new(
+                        TimestampFindingKind::RunTimeBeforeVolumeCreation {
+                            slot: *slot,
+                            time: Filetime::new(*time),
+                            serial_number: volume.serial_number,
+                            creation_time: Filetime::new(volume.creation_time),
+                        },
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Raw run time slots, including the empty ones. Prefetch files not parsed from disk only have the non empty run times.
+fn run_time_slots(prefetch: &PrefetchFile) -> Vec<u64> {
+    if prefetch.file_information.last_run_time_slots.is_empty() {
+        prefetch
+            .last_run_times
+            .iter()
+            .map(|v| v.filetime())
+            .collect()
+    } else {
+        prefetch.file_information.last_run_time_slots.clone()
+    }
+}
+
+#[test]
+fn should_find_run_time_inconsistencies() {
+    use crate::common::{Metric, VolumeInformation};
+    let day = 864_000_000_000u64;
+    let base = 133_500_000_000_000_000u64;
+    let mut prefetch = PrefetchFile {
+        name: "TOOL.EXE".into(),
+        run_count: 8,
+        metrics: vec![Metric {
+            file: r"\VOLUME{01d861d36906cd20-22690e22}\TOOLS\TOOL.EXE".into(),
+            ..Default::default()
+        }],
+        volume: vec![VolumeInformation {
+            device_path: r"\VOLUME{01d861d36906cd20-22690e22}".into(),
+            creation_time: base - 3 * day,
+            serial_number: 0x22690e22,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    prefetch.file_information.last_run_time_slots = vec![
+        base,
+        base - day,
+        base + day,
+        base - day,
+        0,
+        base - 5 * day,
+        0,
+        0,
+    ];
+    let analyzer = TimestampAnalyzer::with_reference_time(Filetime::new(base + day / 2));
+    let kinds: Vec<TimestampFindingKind> = analyzer
+        .analyze(&prefetch)
+        .into_iter()
+        .map(|v| v.kind)
+        .collect();
+    assert!(kinds.contains(&TimestampFindingKind::RunTimesOutOfOrder { slot: 2 }));
+    assert!(kinds.contains(&TimestampFindingKind::DuplicatedRunTime {
+        slot: 3,
+        duplicate_of: 1
+    }));
+    assert!(kinds.contains(&TimestampFindingKind::RunTimeInFuture {
+        slot: 2,
+        time: Filetime::new(base + day)
+    }));
+    assert!(kinds.iter().any(|v| matches!(
+        v,
+        TimestampFindingKind::RunTimeBeforeVolumeCreation { slot: 5, .. }
+    )));
+    assert!(kinds.contains(&TimestampFindingKind::ZeroedRunTimeSlots {
+        run_count: 8,
+        slots: vec![4, 6, 7]
+    }));
+    assert!(!kinds
+        .iter()
+        .any(|v| matches!(v, TimestampFindingKind::RunCountBelowRunTimes { .. })));
+}
@@ -4,10 +4,14 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
     let mut buffered_flags = 0;
     let mut buffered_flag_count = 0;
     let mut input_position = 0;
-    let mut output_position = 0;
+    let mut output_position = out_buf.len();
     let mut last_length_half_byte = 0;
     loop {
         if buffered_flag_count == 0 {
+            if input_position == in_buf.len() {
+                return Ok(());
+            }
+            check_input_size(in_buf, input_position, 4)?;
             buffered_flags = u32::from_le_bytes(
                 in_buf[input_position..input_position + 4]
                     .try_into()
@@ -18,6 +22,7 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
         }
         buffered_flag_count -= 1;
         if (buffered_flags & (1 << buffered_flag_count)) == 0 {
+            check_input_size(in_buf, input_position, 1)?;
             out_buf.push(in_buf[input_position]);
             input_position += 1;
             output_position += 1;
@@ -25,6 +30,7 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
             if input_position == in_buf.len() {
                 return Ok(());
             }
+            check_input_size(in_buf, input_position, 2)?;
             let match_bytes = u16::from_le_bytes(
                 in_buf[input_position..input_position + 2]
                     .try_into()
@@ -35,6 +41,7 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
             let match_offset = (match_bytes / 8) + 1;
             if match_length == 7 {
                 if last_length_half_byte == 0 {
+                    check_input_size(in_buf, input_position, 1)?;
                     match_length = (in_buf[input_position] as u32) % 16;
                     last_length_half_byte = input_position;
                     input_position += 1;
@@ -43,9 +50,11 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
                     last_length_half_byte = 0;
                 }
                 if match_length == 15 {
+                    check_input_size(in_buf, input_position, 1)?;
                     match_length = in_buf[input_position] as u32;
                     input_position += 1;
                     if match_length == 255 {
+                        check_input_size(in_buf, input_position, 2)?;
                         match_length = u16::from_le_bytes(
                             in_buf[input_position..input_position + 2]
                                 .try_into()
@@ -53,6 +62,7 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
                         ) as u32;
                         input_position += 2;
                         if match_length == 0 {
+                            check_input_size(in_buf, input_position, 4)?;
                             match_length = u32::from_le_bytes(
                                 in_buf[input_position..input_position + 4]
                                     .try_into()
@@ -72,6 +82,11 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
                 match_length += 7;
             }
             match_length += 3;
+            if match_offset as usize > output_position {
+                return Err(ForensicError::bad_format_str(
+                    "decompress_LZ77(): Invalid match offset, greater than the decompressed data",
+                ));
+            }
             for _ in 0..match_length {
                 out_buf.push(out_buf[output_position - match_offset as usize]);
                 output_position += 1;
@@ -80,6 +95,15 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
     }
 }
 
+fn check_input_size(in_buf: &[u8], position: usize, size: usize) -> ForensicResult<()> {
+    if position + size > in_buf.len() {
+        return Err(ForensicError::bad_format_str(
+            "decompress_LZ77(): Unexpected end of the compressed data",
+        ));
+    }
+    Ok(())
+}
+
 #[test]
 fn basic_lz77_decompression() {
     let uncompressed = b"abcdefghijklmnopqrstuvwxyz";
@@ -104,3 +128,11 @@ fn basic_lz77_decompression_2() {
     decompress(&encoded, &mut decoded_value).unwrap();
     assert_eq!(uncompressed, &decoded_value[..]);
 }
+
+#[test]
+fn lz77_invalid_offset() {
+    let encoded: [u8; 6] = [0x00, 0x00, 0x00, 0x80, 0x17, 0x00];
+
+    let mut decoded_value = Vec::with_capacity(1024);
+    decompress(&encoded, &mut decoded_value).expect_err("Offset outside the decompressed data");
+}
@@ -1,4 +1,4 @@
-use forensic_rs::err::ForensicResult;
+use forensic_rs::err::{ForensicError, ForensicResult};
 use std::mem;
 
 const LZNT1_COMPRESSED_FLAG: usize = 0x8000;
@@ -10,7 +10,7 @@ macro_rules! load16le {
 }
 
 pub fn decompress2_push(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
-    let mut out_idx: usize = 0;
+    let mut out_idx: usize = out_buf.len();
     let mut in_idx: usize = 0;
 
     let mut header: usize;
@@ -23,15 +23,21 @@ pub fn decompress2_push(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<
     // We don't want to compute those values at each round.
     let in_buf_max_size = in_buf.len();
 
-    while in_idx < in_buf_max_size {
+    while in_idx + mem::size_of::<u16>() <= in_buf_max_size {
         let in_chunk_base = in_idx;
         // compressed chunk header (2 bytes)
         load16le!(header, in_buf, in_idx);
         in_idx += mem::size_of::<u16>();
+        if header == 0 {
+            // End of the compressed buffer
+            break;
+        }
         chunk_len = (header & 0xfff) + 1;
 
         if chunk_len > (in_buf_max_size - in_idx) {
-            return Err(forensic_rs::err::ForensicError::BadFormat);
+            return Err(ForensicError::bad_format_str(
+                "decompress_lznt1(): The chunk size is greater than the input buffer",
+            ));
         }
 
         if header & LZNT1_COMPRESSED_FLAG != 0 {
@@ -62,9 +68,19 @@ pub fn decompress2_push(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<
                         break;
                     }
 
+                    if in_idx + 1 >= in_buf_max_size {
+                        return Err(ForensicError::bad_format_str(
+                            "decompress_lznt1(): Incomplete copy token",
+                        ));
+                    }
                     load16le!(copy_token, in_buf, in_idx);
                     in_idx += mem::size_of::<u16>();
 
+                    if out_idx == out_base_idx {
+                        return Err(ForensicError::bad_format_str(
+                            "decompress_lznt1(): The copy offset is outside the chunk",
+                        ));
+                    }
                     let mut pos = out_idx - out_base_idx - 1;
                     let mut l_mask = 0xFFF;
                     let mut o_shift = 12;
@@ -78,21 +94,13 @@ pub fn decompress2_push(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<
                     length = (copy_token & l_mask) + 3;
                     offset = (copy_token >> o_shift) + 1;
 
-                    if offset > out_idx {
-                        return Err(forensic_rs::err::ForensicError::BadFormat);
+                    if offset > out_idx - out_base_idx {
+                        return Err(ForensicError::bad_format_str(
+                            "decompress_lznt1(): The copy offset is outside the chunk",
+                        ));
                     }
 
-                    /*
-                    if (out_idx + length) >= out_buf_max_size {
-                        length = out_buf_max_size - out_idx;
-                    }
-                    */
-
                     for _i in 0..length {
-                        if offset > out_idx {
-                            return Err(forensic_rs::err::ForensicError::BadFormat);
-                        }
-
                         out_buf.push(out_buf[out_idx - offset]);
                         out_idx += mem::size_of::<u8>();
                     }
@@ -124,4 +132,32 @@ pub fn decompress2_push(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<
     Ok(())
 }
 
+#[test]
+fn basic_lznt1_uncompressed_chunk() {
+    let uncompressed = b"abc";
+    let encoded: [u8; 7] = [0x02, 0x30, 0x61, 0x62, 0x63, 0x00, 0x00];
 
+    let mut decoded_value = Vec::with_capacity(1024);
+    decompress2_push(&encoded, &mut decoded_value).unwrap();
+    assert_eq!(uncompressed, &decoded_value[..]);
+}
+
+#[test]
+fn basic_lznt1_decompression() {
+    let uncompressed = b"abcabcabcabcxyz";
+    let encoded: [u8; 13] = [
+        0x08, 0xb0, 0x08, 0x61, 0x62, 0x63, 0x06, 0x20, 0x78, 0x79, 0x7a, 0x00, 0x00,
+    ];
+
+    let mut decoded_value = Vec::with_capacity(1024);
+    decompress2_push(&encoded, &mut decoded_value).unwrap();
+    assert_eq!(uncompressed, &decoded_value[..]);
+}
+
+#[test]
+fn lznt1_invalid_offset() {
+    let encoded: [u8; 6] = [0x02, 0xb0, 0x01, 0x06, 0x20, 0x00];
+
+    let mut decoded_value = Vec::with_capacity(1024);
+    decompress2_push(&encoded, &mut decoded_value).expect_err("Offset outside the chunk");
+}
@@ -1,7 +1,9 @@
 use forensic_rs::prelude::ForensicResult;
 
 pub mod lz77;
+pub mod lznt1;
 pub mod xpress_huff;
+pub mod xpress_huff_compress;
 
 #[repr(u32)]
 #[derive(Debug, Clone)]
@@ -25,6 +27,15 @@ impl From<u32> for CompressionAlgorithm {
     }
 }
 
+/// Compressed bytes that produce a range of the decompressed output
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressedChunk {
+    pub compressed_offset: usize,
+    pub compressed_size: usize,
+    pub decompressed_offset: usize,
+    pub decompressed_size: usize,
+}
+
 pub fn decompress(
     in_buf: &[u8],
     out_buf: &mut Vec<u8>,
@@ -32,18 +43,85 @@ pub fn decompress(
 ) -> ForensicResult<()> {
     match algorithm {
         CompressionAlgorithm::CompressionFormatNone => {
-            out_buf.copy_from_slice(in_buf);
+            out_buf.extend_from_slice(in_buf);
         }
         CompressionAlgorithm::CompressionFormatDefault => {
             return Err(forensic_rs::err::ForensicError::Other(
                 "Default compression algorithm not supported".into(),
             ))
         }
-        CompressionAlgorithm::CompressionFormatLznt1 => lz77::decompress(in_buf, out_buf)?,
-        CompressionAlgorithm::CompressionFormatXpress => xpress_huff::decompress(in_buf, out_buf)?, // Can't happen
+        CompressionAlgorithm::CompressionFormatLznt1 => lznt1::decompress2_push(in_buf, out_buf)?,
+        CompressionAlgorithm::CompressionFormatXpress => lz77::decompress(in_buf, out_buf)?,
         CompressionAlgorithm::CompressionFormatXpressHuff => {
             xpress_huff::decompress(in_buf, out_buf)?
         }
     }
     Ok(())
 }
+
+/// Decompresses the buffer and returns which input bytes produce each part of the output.
+/// The list is empty when the algorithm does not allow the mapping.
+pub fn decompress_with_chunks(
+    in_buf: &[u8],
+    out_buf: &mut Vec<u8>,
+    algorithm: CompressionAlgorithm,
+) -> ForensicResult<Vec<CompressedChunk>> {
+    match algorithm {
+        CompressionAlgorithm::CompressionFormatNone => {
+            let decompressed_offset = out_buf.len();
+            out_buf.extend_from_slice(in_buf);
+            Ok(vec![CompressedChunk {
+                compressed_offset: 0,
+                compressed_size: in_buf.len(),
+                decompressed_offset,
+                decompressed_size: in_buf.len(),
+            }])
+        }
+        CompressionAlgorithm::CompressionFormatXpressHuff => {
+            xpress_huff::decompress_with_chunks(in_buf, out_buf)
+        }
+        _ => {
+            decompress(in_buf, out_buf, algorithm)?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+#[test]
+fn should_route_each_algorithm() {
+    let uncompressed = b"abcabcabcabcxyz";
+    let lznt1: [u8; 13] = [
+        0x08, 0xb0, 0x08, 0x61, 0x62, 0x63, 0x06, 0x20, 0x78, 0x79, 0x7a, 0x00, 0x00,
+    ];
+    let mut decoded_value = Vec::with_capacity(1024);
+    decompress(
+        &lznt1,
+        &mut decoded_value,
+        CompressionAlgorithm::CompressionFormatLznt1,
+    )
+    .unwrap();
+    assert_eq!(uncompressed, &decoded_value[..]);
+
+    let uncompressed = b"abcdefghijklmnopqrstuvwxyz";
+    let xpress: [u8; 30] = [
+        0x3f, 0x00, 0x00, 0x00, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b,
+        0x6c, 0x6d, 0x6e, 0x6f, 0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a,
+    ];
+    let mut decoded_value = Vec::with_capacity(1024);
+    decompress(
+        &xpress,
+        &mut decoded_value,
+        CompressionAlgorithm::CompressionFormatXpress,
+    )
+    .unwrap();
+    assert_eq!(uncompressed, &decoded_value[..]);
+
+    let mut decoded_value = Vec::with_capacity(1024);
+    decompress(
+        uncompressed,
+        &mut decoded_value,
+        CompressionAlgorithm::CompressionFormatNone,
+    )
+    .unwrap();
+    assert_eq!(uncompressed, &decoded_value[..]);
+}
@@ -1,14 +1,35 @@
 use forensic_rs::err::{ForensicError, ForensicResult};
 use std::{cell::RefCell, cmp::Ordering, rc::Rc};
 
+use super::CompressedChunk;
+
 /// Inspired by https://raw.githubusercontent.com/Velocidex/go-prefetch/master/lzxpress.go
 pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
+    decompress_with_chunks(in_buf, out_buf)?;
+    Ok(())
+}
+
+/// Decompresses the buffer and returns the input and output range of each chunk
+pub fn decompress_with_chunks(
+    in_buf: &[u8],
+    out_buf: &mut Vec<u8>,
+) -> ForensicResult<Vec<CompressedChunk>> {
     let mut in_index = 0;
     let mut out_index = 0;
     let output_size = out_buf.capacity();
+    let mut chunks = Vec::with_capacity(output_size / 65536 + 1);
     loop {
         let chunk_size = (output_size - out_index).min(65536);
+        let (chunk_in_index, chunk_out_index) = (in_index, out_index);
         (in_index, out_index) = decompress_chunk(in_index, in_buf, out_index, out_buf, chunk_size)?;
+        if out_index > chunk_out_index {
+            chunks.push(CompressedChunk {
+                compressed_offset: chunk_in_index,
+                compressed_size: in_index - chunk_in_index,
+                decompressed_offset: chunk_out_index,
+                decompressed_size: (out_index - chunk_out_index).min(output_size - chunk_out_index),
+            });
+        }
         if in_index >= in_buf.len() || out_index >= output_size {
             break;
         }
@@ -16,7 +37,7 @@ pub fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> ForensicResult<()> {
     if output_size < out_buf.len() {
         out_buf.resize(output_size, 0);
     }
-    Ok(())
+    Ok(chunks)
 }
 
 fn decompress_chunk(
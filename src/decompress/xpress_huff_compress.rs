@@ -0,0 +1,372 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+const CHUNK_SIZE: usize = 65536;
+const SYMBOL_COUNT: usize = 512;
+const EOF_SYMBOL: usize = 256;
+const MAX_CODE_LENGTH: u32 = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = u16::MAX as usize;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_SIZE: usize = 1 << 15;
+const MAX_CHAIN: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { length: usize, offset: usize },
+}
+
+/// Compresses a buffer with the Xpress Huffman algorithm ([MS-XCA] 2.1). The data is split in chunks of 64Kb, each one with its own Huffman table.
+///
+/// ```rust
+/// use frnsc_prefetch::decompress::{xpress_huff, xpress_huff_compress};
+/// let data = b"abcabcabcabcabcabcabcabcabc";
+/// let compressed = xpress_huff_compress::compress(data);
+/// let mut decompressed = Vec::with_capacity(data.len());
+/// xpress_huff::decompress(&compressed, &mut decompressed).unwrap();
+/// assert_eq!(&data[..], &decompressed[..]);
+/// ```
+pub fn compress(in_buf: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::with_capacity(in_buf.len() / 2 + 512);
+    let mut matcher = MatchFinder::new();
+    let mut chunk_start = 0;
+    loop {
+        let chunk_end = (chunk_start + CHUNK_SIZE).min(in_buf.len());
+        let is_last = chunk_end == in_buf.len();
+        let tokens = matcher.tokenize(in_buf, chunk_start, chunk_end);
+        compress_chunk(&mut writer, &tokens, is_last);
+        if is_last {
+            break;
+        }
+        chunk_start = chunk_end;
+    }
+    writer.output
+}
+
+fn compress_chunk(writer: &mut BitWriter, tokens: &[Token], is_last: bool) {
+    let mut frequencies = [0u32; SYMBOL_COUNT];
+    for token in tokens {
+        frequencies[token_symbol(token)] += 1;
+    }
+    if is_last {
+        frequencies[EOF_SYMBOL] += 1;
+    }
+    let lengths = huffman_code_lengths(&frequencies);
+    let codes = canonical_codes(&lengths);
+    let mut table = [0u8; SYMBOL_COUNT / 2];
+    for (i, entry) in table.iter_mut().enumerate() {
+        *entry = (lengths[2 * i] | (lengths[2 * i + 1] << 4)) as u8;
+    }
+    writer.start_chunk(&table);
+    for token in tokens {
+        let symbol = token_symbol(token);
+        writer.write_bits(codes[symbol], lengths[symbol]);
+        if let Token::Match { length, offset } = *token {
+            let length = length - MIN_MATCH;
+            if length >= 15 {
+                if length - 15 < 255 {
+                    writer.write_byte((length - 15) as u8);
+                } else {
+                    writer.write_byte(255);
+                    writer.write_byte((length & 0xff) as u8);
+                    writer.write_byte((length >> 8) as u8);
+                }
+            }
+            let offset_bits = offset_bits(offset);
+            writer.write_bits((offset - (1 << offset_bits)) as u32, offset_bits);
+        }
+    }
+    if is_last {
+        writer.write_bits(codes[EOF_SYMBOL], lengths[EOF_SYMBOL]);
+    }
+    writer.end_chunk();
+}
+
+fn offset_bits(offset: usize) -> u32 {
+    usize::BITS - 1 - offset.leading_zeros()
+}
+
+fn token_symbol(token: &Token) -> usize {
+    match *token {
+        Token::Literal(v) => v as usize,
+        Token::Match { length, offset } => {
+            256 + ((offset_bits(offset) as usize) << 4) + (length - MIN_MATCH).min(15)
+        }
+    }
+}
+
+/// Huffman code lengths limited to 15 bits. Frequencies are flattened until the tree fits.
+fn huffman_code_lengths(frequencies: &[u32; SYMBOL_COUNT]) -> [u32; SYMBOL_COUNT] {
+    let mut frequencies = *frequencies;
+    loop {
+        let lengths = unbounded_code_lengths(&frequencies);
+        if lengths.iter().all(|&v| v <= MAX_CODE_LENGTH) {
+            return lengths;
+        }
+        for frequency in frequencies.iter_mut().filter(|v| **v > 0) {
+            *frequency = (*frequency >> 1).max(1);
+        }
+    }
+}
+
+fn unbounded_code_lengths(frequencies: &[u32; SYMBOL_COUNT]) -> [u32; SYMBOL_COUNT] {
+    let mut lengths = [0u32; SYMBOL_COUNT];
+    // Nodes: (frequency, index). Leaves are 0..512, internal nodes follow.
+    let mut parents: Vec<usize> = vec![usize::MAX; SYMBOL_COUNT];
+    let mut heap = BinaryHeap::new();
+    for (symbol, &frequency) in frequencies.iter().enumerate() {
+        if frequency > 0 {
+            heap.push(Reverse((frequency as u64, symbol)));
+        }
+    }
+    if heap.len() == 1 {
+        let Reverse((_, symbol)) = heap.pop().unwrap_or_default();
+        lengths[symbol] = 1;
+        return lengths;
+    }
+    while heap.len() > 1 {
+        let Reverse((freq_a, node_a)) = heap.pop().unwrap_or_default();
+        let Reverse((freq_b, node_b)) = heap.pop().unwrap_or_default();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[node_a] = node;
+        parents[node_b] = node;
+        heap.push(Reverse((freq_a + freq_b, node)));
+    }
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        if frequencies[symbol] == 0 {
+            continue;
+        }
+        let mut node = symbol;
+        while parents[node] != usize::MAX {
+            node = parents[node];
+            *length += 1;
+        }
+    }
+    lengths
+}
+
+/// Assigns the codes in the same order used by the decoder: sorted by length and then by symbol.
+fn canonical_codes(lengths: &[u32; SYMBOL_COUNT]) -> [u32; SYMBOL_COUNT] {
+    let mut codes = [0u32; SYMBOL_COUNT];
+    let mut symbols: Vec<usize> = (0..SYMBOL_COUNT).filter(|&v| lengths[v] > 0).collect();
+    symbols.sort_by_key(|&v| (lengths[v], v));
+    let mut mask = 0;
+    let mut bits = 1;
+    for symbol in symbols {
+        mask <<= lengths[symbol] - bits;
+        bits = lengths[symbol];
+        codes[symbol] = mask;
+        mask += 1;
+    }
+    codes
+}
+
+/// Writes the bit stream as 16-bit little endian words. The decoder reads the extra length bytes
+/// from the position after the words it has already loaded, so the slots of those words are reserved
+/// before writing any byte.
+struct BitWriter {
+    output: Vec<u8>,
+    /// Positions of the reserved 16-bit words of the current chunk
+    slots: Vec<usize>,
+    /// Number of words already written into their slot
+    written_words: usize,
+    /// Bits consumed by the decoder in the current chunk
+    bit_count: usize,
+    accumulator: u32,
+    accumulated: u32,
+}
+
+impl BitWriter {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            output: Vec::with_capacity(capacity),
+            slots: Vec::with_capacity(CHUNK_SIZE / 8),
+            written_words: 0,
+            bit_count: 0,
+            accumulator: 0,
+            accumulated: 0,
+        }
+    }
+
+    fn start_chunk(&mut self, table: &[u8]) {
+        self.output.extend_from_slice(table);
+        self.slots.clear();
+        self.written_words = 0;
+        self.bit_count = 0;
+        self.accumulator = 0;
+        self.accumulated = 0;
+    }
+
+    /// Number of words loaded by the decoder after consuming `bit_count` bits
+    fn loaded_words(&self) -> usize {
+        (self.bit_count.div_ceil(16) + 1).max(2)
+    }
+
+    fn reserve_slots(&mut self, words: usize) {
+        while self.slots.len() < words {
+            self.slots.push(self.output.len());
+            self.output.extend_from_slice(&[0, 0]);
+        }
+    }
+
+    fn write_bits(&mut self, value: u32, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+        self.accumulator = (self.accumulator << bits) | (value & ((1 << bits) - 1));
+        self.accumulated += bits;
+        self.bit_count += bits as usize;
+        while self.accumulated >= 16 {
+            self.accumulated -= 16;
+            let word = (self.accumulator >> self.accumulated) as u16;
+            self.accumulator &= (1 << self.accumulated) - 1;
+            self.write_word(word);
+        }
+    }
+
+    fn write_word(&mut self, word: u16) {
+        self.reserve_slots(self.written_words + 1);
+        let pos = self.slots[self.written_words];
+        self.output[pos..pos + 2].copy_from_slice(&word.to_le_bytes());
+        self.written_words += 1;
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        self.reserve_slots(self.loaded_words());
+        self.output.push(value);
+    }
+
+    fn end_chunk(&mut self) {
+        if self.accumulated > 0 {
+            let word = (self.accumulator << (16 - self.accumulated)) as u16;
+            self.accumulated = 0;
+            self.accumulator = 0;
+            self.write_word(word);
+        }
+        self.reserve_slots(self.loaded_words());
+    }
+}
+
+/// Hash chain match finder over a 64Kb window
+struct MatchFinder {
+    head: Vec<usize>,
+    previous: Vec<usize>,
+    inserted: usize,
+}
+
+impl MatchFinder {
+    fn new() -> Self {
+        Self {
+            head: vec![usize::MAX; HASH_SIZE],
+            previous: Vec::new(),
+            inserted: 0,
+        }
+    }
+
+    fn hash(data: &[u8], pos: usize) -> usize {
+        let value =
+            (data[pos] as usize) << 16 | (data[pos + 1] as usize) << 8 | data[pos + 2] as usize;
+        (value.wrapping_mul(2654435761) >> 8) & (HASH_SIZE - 1)
+    }
+
+    fn insert_until(&mut self, data: &[u8], pos: usize) {
+        if self.previous.len() < data.len() {
+            self.previous.resize(data.len(), usize::MAX);
+        }
+        while self.inserted < pos {
+            let i = self.inserted;
+            if i + MIN_MATCH <= data.len() {
+                let hash = Self::hash(data, i);
+                self.previous[i] = self.head[hash];
+                self.head[hash] = i;
+            }
+            self.inserted += 1;
+        }
+    }
+
+    fn find(&self, data: &[u8], pos: usize, end: usize) -> Option<(usize, usize)> {
+        if pos + MIN_MATCH > end {
+            return None;
+        }
+        let max_length = (end - pos).min(MAX_MATCH);
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[Self::hash(data, pos)];
+        let mut chain = 0;
+        while candidate != usize::MAX && chain < MAX_CHAIN {
+            let offset = pos - candidate;
+            if offset > MAX_OFFSET {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[pos..pos + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length >= MIN_MATCH && best.map(|v| length > v.0).unwrap_or(true) {
+                best = Some((length, offset));
+                if length == max_length {
+                    break;
+                }
+            }
+            candidate = self.previous[candidate];
+            chain += 1;
+        }
+        best
+    }
+
+    fn tokenize(&mut self, data: &[u8], start: usize, end: usize) -> Vec<Token> {
+        let mut tokens = Vec::with_capacity(end - start);
+        let mut pos = start;
+        while pos < end {
+            self.insert_until(data, pos);
+            match self.find(data, pos, end) {
+                Some((length, offset)) => {
+                    tokens.push(Token::Match { length, offset });
+                    pos += length;
+                }
+                None => {
+                    tokens.push(Token::Literal(data[pos]));
+                    pos += 1;
+                }
+            }
+        }
+        self.insert_until(data, pos);
+        tokens
+    }
+}
+
+#[cfg(test)]
+fn round_trip(data: &[u8]) {
+    let compressed = compress(data);
+    let mut decompressed = Vec::with_capacity(data.len());
+    super::xpress_huff::decompress(&compressed, &mut decompressed).unwrap();
+    assert_eq!(data.len(), decompressed.len());
+    assert!(data == &decompressed[..]);
+}
+
+#[test]
+fn basic_huffman_encoding() {
+    round_trip(b"abcdefghijklmnopqrstuvwxyz");
+    round_trip(b"abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabc");
+    round_trip(b"a");
+}
+
+#[test]
+fn huffman_encoding_long_matches_and_chunks() {
+    let mut data = Vec::with_capacity(200_000);
+    data.extend(std::iter::repeat_n(b'x', 70_000));
+    let mut seed: u32 = 12345;
+    for _ in 0..100_000 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        data.push(b"SCCA\\WINDOWS\\SYSTEM32\\.DLL"[(seed >> 16) as usize % 26]);
+    }
+    data.extend(std::iter::repeat_n(0u8, 300));
+    round_trip(&data);
+}
+
+#[test]
+fn huffman_encoding_all_symbols() {
+    let data: Vec<u8> = (0..40_000u32).map(|v| (v * 7 % 251) as u8).collect();
+    round_trip(&data);
+}
@@ -0,0 +1,518 @@
+use forensic_rs::{
+    err::ForensicResult,
+    traits::registry::{RegHiveKey, RegValue, RegistryReader},
+};
+
+use crate::{
+    common::{utf16_from_bytes, PrefetchFile, VolumeInformation},
+    removable::{read_emd_volumes, EmdVolume},
+    volume_path::VolumePath,
+};
+
+/// Key of the SYSTEM hive with the drive letters and volume GUIDs of the mounted volumes
+pub const MOUNTED_DEVICES_KEY: &str = r"SYSTEM\MountedDevices";
+/// Key of the NTUSER hive with the volumes and network shares mounted by the user
+pub const MOUNT_POINTS_KEY: &str =
+    r"Software\Microsoft\Windows\CurrentVersion\Explorer\MountPoints2";
+
+/// Identity of the volume stored in a MountedDevices value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MountedDeviceData {
+    /// Partition of a MBR disk
+    Mbr {
+        disk_signature: u32,
+        partition_offset: u64,
+    },
+    /// Partition of a GPT disk. Ex: `{0d1c1a4b-6d47-4bd2-a7fa-8ac0ecd4f8a9}`
+    Gpt {
+        partition_guid: String,
+    },
+    /// Device interface of the volume, used by removable media. Ex: `\??\USBSTOR#Disk&Ven_Kingston&Prod_DataTraveler...`
+    Device(String),
+    Unknown(Vec<u8>),
+}
+
+impl MountedDeviceData {
+    pub fn from_bytes(data: &[u8]) -> Self {
+        if data.len() == 12 {
+            return Self::Mbr {
+                disk_signature: u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+                partition_offset: u64::from_le_bytes([
+                    data[4], data[5], data[6], data[7], data[8], data[9], data[10], data[11],
+                ]),
+            };
+        }
+        if data.len() == 24 && data.starts_with(b"DMIO:ID:") {
+            return Self::Gpt {
+                partition_guid: format_guid(&data[8..24]),
+            };
+        }
+        if data.len() % 2 == 0 {
+            let device = utf16_from_bytes(data);
+            if device.starts_with(r"\??\") || device.starts_with("_??_") {
+                return Self::Device(device);
+            }
+        }
+        Self::Unknown(data.to_vec())
+    }
+}
+
+/// Value of the MountedDevices key
+#[derive(Debug, Clone)]
+pub struct MountedDevice {
+    /// Name of the value. Ex: `\DosDevices\C:` or `\??\Volume{4c1b02c1-d990-11dc-99ae-806e6f6e6963}`
+    pub name: String,
+    pub data: MountedDeviceData,
+}
+
+impl MountedDevice {
+    /// Drive letter of a `\DosDevices\X:` value
+    pub fn letter(&self) -> Option<char> {
+        let letter = self.name.strip_prefix(r"\DosDevices\")?.strip_suffix(':')?;
+        let mut chars = letter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(v), None) if v.is_ascii_alphabetic() => Some(v.to_ascii_uppercase()),
+            _ => None,
+        }
+    }
+    /// Volume GUID of a `\??\Volume{GUID}` value
+    pub fn volume_guid(&self) -> Option<&str> {
+        let guid = self.name.strip_prefix(r"\??\Volume")?;
+        if guid.starts_with('{') && guid.ends_with('}') {
+            Some(guid)
+        } else {
+            None
+        }
+    }
+}
+
+/// Subkey of MountPoints2 in the NTUSER hive of a user
+#[derive(Debug, Clone)]
+pub struct UserMountPoint {
+    /// SID of the user
+    pub user: String,
+    /// Volume GUID (`{...}`) or network share (`##server#share`)
+    pub name: String,
+    /// Drive letter of the volume when MountedDevices assigns one
+    pub letter: Option<char>,
+}
+
+/// How the drive letter of a volume was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveLetterSource {
+    /// Assigned by the analyst with the serial number or the device path of the volume
+    Assigned,
+    /// The volume holds the Windows folder of `SystemRoot`
+    SystemRoot,
+    /// EMDMgmt links the serial number of the volume with a device interface that has a letter in MountedDevices.
+    /// It is the last letter given to the device, not necessarily the one it had when the prefetch was written.
+    MountedDevices,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DriveLetter {
+    pub letter: char,
+    pub source: DriveLetterSource,
+}
+
+/// Maps the volumes of a prefetch to drive letters.
+///
+/// The prefetch only stores the device path, serial number and creation time of each volume. MountedDevices identifies fixed
+/// disks by disk signature and partition offset (MBR) or partition GUID (GPT), which the prefetch does not have: the system volume
+/// is found with `SystemRoot` and the other fixed volumes must be assigned with their serial number or device path.
+/// Removable volumes are linked through EMDMgmt, whose keys hold the device interface stored in MountedDevices and the volume
+/// serial number.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{drive::DriveMapper, prelude::*};
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/30/C/Windows/Prefetch/CMD.EXE-D269B812.pf")).unwrap();
+/// let mut prefetch = read_prefetch_file("CMD.EXE-D269B812.pf", file).unwrap();
+/// let mut mapper = DriveMapper::new();
+/// mapper.system_root = Some(r"C:\Windows".into());
+/// mapper.assign_serial(0x66f451bc, 'E');
+/// let cmd = prefetch.metrics.iter().find(|v| v.file.ends_with(r"\SYSTEM32\CMD.EXE")).unwrap();
+/// assert_eq!(Some(r"C:\WINDOWS\SYSTEM32\CMD.EXE".to_string()), mapper.dos_path(&prefetch, &cmd.file));
+/// mapper.rewrite_paths(&mut prefetch);
+/// assert!(prefetch.metrics.iter().any(|v| v.file == r"E:\CMDER129\VENDOR\CLINK\CLINK_DLL_X64.DLL"));
+/// assert_eq!(r"C:\WINDOWS\SYSTEM32\CMD.EXE", prefetch.executable_path());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DriveMapper {
+    pub mounted_devices: Vec<MountedDevice>,
+    pub user_mount_points: Vec<UserMountPoint>,
+    /// Volumes of removable devices with their serial number, from the EMDMgmt key of the SOFTWARE hive
+    pub emd_volumes: Vec<EmdVolume>,
+    /// Windows folder. Ex: `C:\Windows`
+    pub system_root: Option<String>,
+    serial_letters: Vec<(u32, char)>,
+    device_letters: Vec<(String, char)>,
+}
+
+impl DriveMapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads MountedDevices, EMDMgmt and SystemRoot from the SYSTEM and SOFTWARE hives and MountPoints2 from the hive of each user.
+    /// Missing keys are skipped.
+    pub fn from_registry(registry: &dyn RegistryReader) -> ForensicResult<Self> {
+        let mut mapper = Self {
+            mounted_devices: read_mounted_devices(registry)?,
+            emd_volumes: read_emd_volumes(registry)?,
+            system_root: registry.get_system_root().ok(),
+            ..Default::default()
+        };
+        for user in registry.list_users().unwrap_or_default() {
+            for name in read_mount_points(registry, &user) {
+                let letter = mapper.volume_letter(&name);
+                mapper.user_mount_points.push(UserMountPoint {
+                    user: user.clone(),
+                    name,
+                    letter,
+                });
+            }
+        }
+        Ok(mapper)
+    }
+
+    /// Assigns a drive letter to the volume with the serial number. Ex: the output of `vol C:`
+    pub fn assign_serial(&mut self, serial_number: u32, letter: char) {
+        self.serial_letters
+            .push((serial_number, letter.to_ascii_uppercase()));
+    }
+
+    /// Assigns a drive letter to a device path. Ex: `\DEVICE\HARDDISKVOLUME2`
+    pub fn assign_device(&mut self, device_path: &str, letter: char) {
+        self.device_letters
+            .push((device_path.to_string(), letter.to_ascii_uppercase()));
+    }
+
+    /// Drive letter of a volume GUID (`{...}`) in MountedDevices
+    pub fn volume_letter(&self, volume_guid: &str) -> Option<char> {
+        let volume = self
+            .mounted_devices
+            .iter()
+            .find(|v| v.volume_guid().map(|g| g.eq_ignore_ascii_case(volume_guid)) == Some(true))?;
+        self.mounted_devices
+            .iter()
+            .filter(|v| v.data == volume.data)
+            .find_map(|v| v.letter())
+    }
+
+    /// Drive letter of each volume of the prefetch, in the same order as `PrefetchFile::volume`
+    pub fn drive_letters(&self, prefetch: &PrefetchFile) -> Vec<Option<DriveLetter>> {
+        let system_volume = self.system_volume(&prefetch.volume);
+        prefetch
+            .volume
+            .iter()
+            .enumerate()
+            .map(|(i, volume)| {
+                if let Some(letter) = self.assigned_letter(volume) {
+                    return Some(DriveLetter {
+                        letter,
+                        source: DriveLetterSource::Assigned,
+                    });
+                }
+                let system_letter = self.system_root.as_deref().and_then(system_root_letter);
+                if let (Some(letter), true) = (system_letter, system_volume == Some(i)) {
+                    return Some(DriveLetter {
+                        letter,
+                        source: DriveLetterSource::SystemRoot,
+                    });
+                }
+                let device = self.mounted_device_of(volume)?;
+                let letter = self
+                    .mounted_devices
+                    .iter()
+                    .filter(|v| &v.data == device)
+                    .find_map(|v| v.letter())?;
+                Some(DriveLetter {
+                    letter,
+                    source: DriveLetterSource::MountedDevices,
+                })
+            })
+            .collect()
+    }
+
+    /// MountPoints2 entries of the users that mounted a removable volume of the prefetch
+    pub fn mount_points_of(&self, volume: &VolumeInformation) -> Vec<&UserMountPoint> {
+        let device = match self.mounted_device_of(volume) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+        let guids: Vec<&str> = self
+            .mounted_devices
+            .iter()
+            .filter(|v| &v.data == device)
+            .filter_map(|v| v.volume_guid())
+            .collect();
+        self.user_mount_points
+            .iter()
+            .filter(|v| guids.iter().any(|guid| guid.eq_ignore_ascii_case(&v.name)))
+            .collect()
+    }
+
+    /// Path with the drive letter of its volume. Ex: `C:\WINDOWS\SYSTEM32\NTDLL.DLL`
+    pub fn dos_path(&self, prefetch: &PrefetchFile, path: &str) -> Option<String> {
+        let volume_path = VolumePath::parse(path)?;
+        let position = volume_path.position_in(&prefetch.volume)?;
+        let letter = self.drive_letters(prefetch).get(position).copied()??;
+        Some(dos_path(letter.letter, volume_path.relative))
+    }
+
+    /// Replaces the volume of `Metric::file` and the directory strings with the drive letter when it is known.
+    /// Paths of unmapped volumes are kept, the device path of `VolumeInformation` is not modified.
+    pub fn rewrite_paths(&self, prefetch: &mut PrefetchFile) {
+        let letters = self.drive_letters(prefetch);
+        let volumes = prefetch.volume.clone();
+        let rewrite = |path: &mut String| {
+            let new_path = VolumePath::parse(path).and_then(|volume_path| {
+                let letter = letters.get(volume_path.position_in(&volumes)?).copied()??;
+                Some(dos_path(letter.letter, volume_path.relative))
+            });
+            if let Some(new_path) = new_path {
+                *path = new_path;
+            }
+        };
+        for metric in prefetch.metrics.iter_mut() {
+            rewrite(&mut metric.file);
+        }
+        for volume in prefetch.volume.iter_mut() {
+            for directory in volume.directory_strings.iter_mut() {
+                rewrite(directory);
+            }
+        }
+    }
+
+    /// MountedDevices data of the device interface that EMDMgmt links with the serial number of the volume
+    fn mounted_device_of(&self, volume: &VolumeInformation) -> Option<&MountedDeviceData> {
+        self.emd_volumes
+            .iter()
+            .filter(|v| v.serial_number == volume.serial_number)
+            .filter_map(|v| emd_device_interface(&v.key))
+            .find_map(|interface| {
+                self.mounted_devices
+                    .iter()
+                    .map(|v| &v.data)
+                    .find(|data| match data {
+                        MountedDeviceData::Device(device) => device
+                            .get(4..)
+                            .map(|v| v.eq_ignore_ascii_case(interface))
+                            .unwrap_or(false),
+                        _ => false,
+                    })
+            })
+    }
+
+    fn assigned_letter(&self, volume: &VolumeInformation) -> Option<char> {
+        let by_serial = self
+            .serial_letters
+            .iter()
+            .find(|(serial, _)| *serial == volume.serial_number)
+            .map(|(_, letter)| *letter);
+        by_serial.or_else(|| {
+            self.device_letters
+                .iter()
+                .find(|(device, _)| device.eq_ignore_ascii_case(&volume.device_path))
+                .map(|(_, letter)| *letter)
+        })
+    }
+
+    /// The only volume with the Windows folder of SystemRoot
+    fn system_volume(&self, volumes: &[VolumeInformation]) -> Option<usize> {
+        let system_root = self.system_root.as_deref()?;
+        let windows_folder = system_root.get(2..)?.trim_end_matches('\\');
+        let mut candidates = volumes.iter().enumerate().filter(|(_, volume)| {
+            volume.directory_strings.iter().any(|directory| {
+                VolumePath::parse(directory)
+                    .map(|v| {
+                        v.relative
+                            .trim_end_matches('\\')
+                            .eq_ignore_ascii_case(windows_folder)
+                    })
+                    .unwrap_or(false)
+            })
+        });
+        let (position, _) = candidates.next()?;
+        if candidates.next().is_some() {
+            return None;
+        }
+        Some(position)
+    }
+}
+
+fn system_root_letter(system_root: &str) -> Option<char> {
+    let mut chars = system_root.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => {
+            Some(letter.to_ascii_uppercase())
+        }
+        _ => None,
+    }
+}
+
+/// Device interface of an EMDMgmt key without the `_??_` prefix. The label and serial number follow the interface GUID
+fn emd_device_interface(key: &str) -> Option<&str> {
+    let end = key.rfind('}')?;
+    key.get(4..=end)
+}
+
+fn dos_path(letter: char, relative: &str) -> String {
+    if relative.is_empty() {
+        format!("{}:\\", letter)
+    } else {
+        format!("{}:{}", letter, relative)
+    }
+}
+
+fn read_mounted_devices(registry: &dyn RegistryReader) -> ForensicResult<Vec<MountedDevice>> {
+    let key = match registry.open_key(RegHiveKey::HkeyLocalMachine, MOUNTED_DEVICES_KEY) {
+        Ok(v) => v,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut devices = Vec::new();
+    for name in registry.enumerate_values(key)? {
+        if let Ok(RegValue::Binary(data)) = registry.read_value(key, &name) {
+            devices.push(MountedDevice {
+                data: MountedDeviceData::from_bytes(&data),
+                name,
+            });
+        }
+    }
+    registry.close_key(key);
+    Ok(devices)
+}
+
+fn read_mount_points(registry: &dyn RegistryReader, user: &str) -> Vec<String> {
+    let key = match registry.open_key(
+        RegHiveKey::HkeyUsers,
+        &format!("{}\\{}", user, MOUNT_POINTS_KEY),
+    ) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+    let names = registry.enumerate_keys(key).unwrap_or_default();
+    registry.close_key(key);
+    names
+}
+
+/// Formats a GUID stored in little endian. Ex: `{0d1c1a4b-6d47-4bd2-a7fa-8ac0ecd4f8a9}`
+fn format_guid(data: &[u8]) -> String {
+    format!(
+        "{{{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}}}",
+        u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+        u16::from_le_bytes([data[4], data[5]]),
+        u16::from_le_bytes([data[6], data[7]]),
+        data[8],
+        data[9],
+        data[10],
+        data[11],
+        data[12],
+        data[13],
+        data[14],
+        data[15]
+    )
+}
+
+#[test]
+fn should_read_mounted_devices_and_user_mount_points() {
+    use forensic_rs::utils::testing::TestingRegistry;
+    let mut registry = TestingRegistry::new();
+    let mbr = [0x2c, 0x1d, 0x69, 0xa2, 0, 0, 0x10, 0, 0, 0, 0, 0];
+    let usb: Vec<u8> = r"_??_USBSTOR#Disk&Ven_Kingston&Prod_DataTraveler_3.0&Rev_PMAP#60A44C3FAE8BBFB0F9370026&0#{53f56307-b6bf-11d0-94f2-00a0c91efb8b}"
+        .encode_utf16()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let mounted = r"HKLM\SYSTEM\MountedDevices";
+    registry.add_value(mounted, r"\DosDevices\C:", RegValue::Binary(mbr.to_vec()));
+    registry.add_value(
+        mounted,
+        r"\??\Volume{4c1b02c1-d990-11dc-99ae-806e6f6e6963}",
+        RegValue::Binary(mbr.to_vec()),
+    );
+    registry.add_value(mounted, r"\DosDevices\E:", RegValue::Binary(usb.clone()));
+    registry.add_value(
+        mounted,
+        r"\??\Volume{a1b2c3d4-0000-11ee-8c90-0800200c9a66}",
+        RegValue::Binary(usb),
+    );
+    let user = "S-1-5-21-1366093794-4292800403-1155380978-513";
+    let mut gpt = b"DMIO:ID:".to_vec();
+    gpt.extend_from_slice(&[
+        0x4b, 0x1a, 0x1c, 0x0d, 0x47, 0x6d, 0xd2, 0x4b, 0xa7, 0xfa, 0x8a, 0xc0, 0xec, 0xd4, 0xf8,
+        0xa9,
+    ]);
+    registry.add_value(mounted, r"\DosDevices\D:", RegValue::Binary(gpt));
+    registry.add_value(
+        &format!(
+            r"HKU\{}\{}\{{a1b2c3d4-0000-11ee-8c90-0800200c9a66}}",
+            user, MOUNT_POINTS_KEY
+        ),
+        "_LabelFromReg",
+        RegValue::from_str("USB"),
+    );
+    registry.add_value(
+        r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion",
+        "SystemRoot",
+        RegValue::from_str(r"C:\Windows"),
+    );
+    registry.add_value(
+        &format!(
+            r"HKLM\{}\_??_USBSTOR#Disk&Ven_Kingston&Prod_DataTraveler_3.0&Rev_PMAP#60A44C3FAE8BBFB0F9370026&0#{{53f56307-b6bf-11d0-94f2-00a0c91efb8b}}KINGSTON_1727287740",
+            crate::removable::EMDMGMT_KEY
+        ),
+        "LastTestedTime",
+        RegValue::QWord(0),
+    );
+    let mapper = DriveMapper::from_registry(&registry).unwrap();
+    assert_eq!(5, mapper.mounted_devices.len());
+    assert!(mapper
+        .mounted_devices
+        .iter()
+        .any(|v| v.letter() == Some('D')
+            && v.data
+                == MountedDeviceData::Gpt {
+                    partition_guid: "{0d1c1a4b-6d47-4bd2-a7fa-8ac0ecd4f8a9}".into()
+                }));
+    assert_eq!(
+        Some('C'),
+        mapper.volume_letter("{4c1b02c1-d990-11dc-99ae-806e6f6e6963}")
+    );
+    assert_eq!(1, mapper.user_mount_points.len());
+    assert_eq!(user, mapper.user_mount_points[0].user);
+    assert_eq!(Some('E'), mapper.user_mount_points[0].letter);
+    assert_eq!(Some(r"C:\Windows"), mapper.system_root.as_deref());
+    // The USB volume is linked by its serial number through EMDMgmt
+    let prefetch = PrefetchFile {
+        volume: vec![
+            VolumeInformation {
+                device_path: r"\VOLUME{01d861d36906cd20-22690e22}".into(),
+                serial_number: 0x22690e22,
+                ..Default::default()
+            },
+            VolumeInformation {
+                device_path: r"\VOLUME{01da1f4b8e4b1a36-66f451bc}".into(),
+                serial_number: 0x66f451bc,
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![
+            None,
+            Some(DriveLetter {
+                letter: 'E',
+                source: DriveLetterSource::MountedDevices
+            })
+        ],
+        mapper.drive_letters(&prefetch)
+    );
+    let mount_points = mapper.mount_points_of(&prefetch.volume[1]);
+    assert_eq!(1, mount_points.len());
+    assert_eq!(user, mount_points[0].user);
+    assert!(mapper.mount_points_of(&prefetch.volume[0]).is_empty());
+}
@@ -0,0 +1,75 @@
+use forensic_rs::err::ForensicError;
+
+/// Step of the parsing process of a prefetch file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseStage {
+    /// Opening the file in the filesystem
+    Open,
+    /// Reading the content of the file
+    Read,
+    /// MAM header and decompression of the content
+    Decompression,
+    /// CRC32 of a compressed file
+    Crc,
+    /// SCCA header and file information
+    Header,
+    /// Metrics array, filename strings and trace chains
+    Metrics,
+    /// Volume information
+    Volumes,
+    /// Byte ranges of the provenance mode
+    Provenance,
+    /// The parser panicked in a worker thread of the parallel scanner, the step is unknown
+    Panic,
+}
+
+/// Error of a prefetch file with the stage where the parsing failed
+#[derive(Debug, Clone)]
+pub struct PrefetchParseError {
+    pub stage: ParseStage,
+    pub error: ForensicError,
+}
+
+impl PrefetchParseError {
+    pub fn new(stage: ParseStage, error: ForensicError) -> Self {
+        Self { stage, error }
+    }
+    /// Name of the error variant: permission, no_more_data, other, missing, bad_format, io, cast or illegal_timestamp
+    pub fn kind(&self) -> &'static str {
+        match &self.error {
+            ForensicError::PermissionError => "permission",
+            ForensicError::NoMoreData => "no_more_data",
+            ForensicError::Other(_) => "other",
+            ForensicError::Missing(_) => "missing",
+            ForensicError::BadFormat(_) => "bad_format",
+            ForensicError::Io(_) => "io",
+            ForensicError::CastError => "cast",
+            ForensicError::IllegalTimestamp(_) => "illegal_timestamp",
+        }
+    }
+}
+
+impl std::fmt::Display for PrefetchParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} stage: {}", self.stage, self.error)
+    }
+}
+
+impl From<PrefetchParseError> for ForensicError {
+    fn from(value: PrefetchParseError) -> Self {
+        value.error
+    }
+}
+
+pub type PrefetchParseResult<T> = Result<T, PrefetchParseError>;
+
+/// Attaches the parsing stage to a forensic error
+pub(crate) trait InStage<T> {
+    fn in_stage(self, stage: ParseStage) -> PrefetchParseResult<T>;
+}
+
+impl<T> InStage<T> for Result<T, ForensicError> {
+    fn in_stage(self, stage: ParseStage) -> PrefetchParseResult<T> {
+        self.map_err(|e| PrefetchParseError::new(stage, e))
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    common::{Metric, PrefetchFile},
+    hash::{compute_prefetch_hash, find_device_path_for_hash},
+    volume_path::{relative_path, VolumePath},
+};
+
+/// Maximum number of characters of the executable name stored in the header (60 bytes with the null terminator)
+pub const MAX_EXECUTABLE_NAME_LENGTH: usize = 29;
+
+/// How the file name of a metric matches the executable name of the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExecutableNameMatch {
+    /// The header name is the first 29 characters of the file name
+    Truncated,
+    Exact,
+}
+
+/// How sure the resolver is about the executable path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExecutableConfidence {
+    /// No metric matches the executable name, the path is the name of the header
+    Unresolved,
+    /// Several metrics match the name and the hash does not choose between them
+    Low,
+    /// A single metric matches the name but the hash could not be verified. Ex: hosting applications
+    Medium,
+    /// The path generates the hash of the prefetch
+    High,
+}
+
+/// Metric whose file name matches the executable name
+#[derive(Debug, Clone)]
+pub struct ExecutableCandidate<'a> {
+    /// Position in `PrefetchFile::metrics`
+    pub index: usize,
+    pub metric: &'a Metric,
+    pub name_match: ExecutableNameMatch,
+    /// Full device path that generates the prefetch hash. Ex: `\DEVICE\HARDDISKVOLUME3\WINDOWS\SYSWOW64\CMD.EXE`
+    pub hash_device_path: Option<String>,
+}
+
+impl ExecutableCandidate<'_> {
+    pub fn hash_matches(&self) -> bool {
+        self.hash_device_path.is_some()
+    }
+
+    fn rank(&self) -> (bool, ExecutableNameMatch, bool) {
+        (
+            self.hash_matches(),
+            self.name_match,
+            self.metric.has_executable_block(),
+        )
+    }
+}
+
+/// Executable path of a prefetch with the candidates it was chosen from, best first
+#[derive(Debug, Clone)]
+pub struct ExecutablePath<'a> {
+    pub path: &'a str,
+    pub confidence: ExecutableConfidence,
+    pub candidates: Vec<ExecutableCandidate<'a>>,
+}
+
+impl<'a> ExecutablePath<'a> {
+    /// Chosen metric, None when unresolved
+    pub fn metric(&self) -> Option<&'a Metric> {
+        self.candidates.first().map(|v| v.metric)
+    }
+
+    /// Position of the chosen metric in `PrefetchFile::metrics`, None when unresolved
+    pub fn index(&self) -> Option<usize> {
+        self.candidates.first().map(|v| v.index)
+    }
+
+    /// The header name was cut to 29 characters
+    pub fn is_truncated(&self) -> bool {
+        self.candidates
+            .first()
+            .map(|v| v.name_match == ExecutableNameMatch::Truncated)
+            .unwrap_or(false)
+    }
+}
+
+/// Finds the metric of the executable. The header stores only 29 characters of the name, so longer names are matched by prefix.
+/// When several metrics match (Ex: `SYSTEM32\CMD.EXE` and `SYSWOW64\CMD.EXE`) the path hash chooses between them.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{executable::*, prelude::*};
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/30/C/Windows/Prefetch/CMD.EXE-6D6290C5.pf")).unwrap();
+/// let prefetch = read_prefetch_file("CMD.EXE-6D6290C5.pf", file).unwrap();
+/// let resolved = resolve_executable_path(&prefetch);
+/// assert_eq!(r"\VOLUME{01d861d36906cd20-22690e22}\WINDOWS\SYSWOW64\CMD.EXE", resolved.path);
+/// assert_eq!(ExecutableConfidence::High, resolved.confidence);
+/// ```
+pub fn resolve_executable_path(prefetch: &PrefetchFile) -> ExecutablePath<'_> {
+    let mut candidates: Vec<ExecutableCandidate<'_>> = prefetch
+        .metrics
+        .iter()
+        .enumerate()
+        .filter_map(|(index, metric)| {
+            let name_match = match_executable_name(&metric.file, &prefetch.name)?;
+            Some(ExecutableCandidate {
+                index,
+                metric,
+                name_match,
+                hash_device_path: None,
+            })
+        })
+        .collect();
+    if candidates.is_empty() {
+        return ExecutablePath {
+            path: &prefetch.name,
+            confidence: ExecutableConfidence::Unresolved,
+            candidates,
+        };
+    }
+    for candidate in candidates.iter_mut() {
+        candidate.hash_device_path = hash_device_path(prefetch, &candidate.metric.file);
+    }
+    // Stable sort: same rank keeps the order of the metrics
+    candidates.sort_by_key(|v| std::cmp::Reverse(v.rank()));
+    let verified = candidates.iter().filter(|v| v.hash_matches()).count();
+    let confidence = if verified == 1 {
+        ExecutableConfidence::High
+    } else if candidates.len() == 1 {
+        ExecutableConfidence::Medium
+    } else {
+        ExecutableConfidence::Low
+    };
+    ExecutablePath {
+        path: &candidates[0].metric.file,
+        confidence,
+        candidates,
+    }
+}
+
+/// Compares the file name of a path with the executable name of the header
+pub fn match_executable_name(path: &str, name: &str) -> Option<ExecutableNameMatch> {
+    let file_name = path.rsplit('\\').next()?;
+    if name.is_empty() {
+        return None;
+    }
+    if file_name.eq_ignore_ascii_case(name) {
+        return Some(ExecutableNameMatch::Exact);
+    }
+    if name.chars().count() < MAX_EXECUTABLE_NAME_LENGTH {
+        return None;
+    }
+    let prefix_matches = file_name
+        .get(..name.len())
+        .map(|v| v.eq_ignore_ascii_case(name))
+        .unwrap_or(false);
+    if prefix_matches {
+        Some(ExecutableNameMatch::Truncated)
+    } else {
+        None
+    }
+}
+
+/// Device path that generates the prefetch hash, trying the version 30 hash string first
+fn hash_device_path(prefetch: &PrefetchFile, path: &str) -> Option<String> {
+    let relative = relative_path(path).unwrap_or(path);
+    if let Some(hash_string) = &prefetch.file_information.hash_string {
+        let same_path = VolumePath::parse(hash_string)
+            .map(|v| v.relative.eq_ignore_ascii_case(relative))
+            .unwrap_or(false);
+        if same_path
+            && compute_prefetch_hash(hash_string, prefetch.version).ok() == Some(prefetch.hash)
+        {
+            return Some(hash_string.clone());
+        }
+    }
+    if path.starts_with(r"\DEVICE\")
+        && compute_prefetch_hash(path, prefetch.version).ok() == Some(prefetch.hash)
+    {
+        return Some(path.to_string());
+    }
+    find_device_path_for_hash(relative, prefetch.version, prefetch.hash)
+}
+
+#[test]
+fn should_match_truncated_executable_names() {
+    let name = "SOME_VERY_LONG_INSTALLER_NAME";
+    assert_eq!(MAX_EXECUTABLE_NAME_LENGTH, name.len());
+    assert_eq!(
+        Some(ExecutableNameMatch::Truncated),
+        match_executable_name(r"C:\TMP\SOME_VERY_LONG_INSTALLER_NAME_V2.EXE", name)
+    );
+    assert_eq!(
+        Some(ExecutableNameMatch::Exact),
+        match_executable_name(r"C:\WINDOWS\SYSTEM32\CMD.EXE", "cmd.exe")
+    );
+    // Short names must match the whole file name
+    assert_eq!(
+        None,
+        match_executable_name(r"C:\WINDOWS\SYSTEM32\XCMD.EXE", "CMD.EXE")
+    );
+    assert_eq!(
+        None,
+        match_executable_name(r"C:\WINDOWS\SYSTEM32\CMD.EXE.MUI", "CMD.EXE")
+    );
+}
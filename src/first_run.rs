@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use forensic_rs::{traits::vfs::VMetadata, utils::time::Filetime};
+
+use crate::common::PrefetchFile;
+
+/// Seconds between 1601-01-01 (FILETIME) and 1970-01-01 (UNIX)
+const UNIX_EPOCH_SECONDS: u64 = 11_644_473_600;
+/// The prefetch file is written by the cache manager about ten seconds after the process starts
+pub const FIRST_RUN_DELAY: Duration = Duration::from_secs(10);
+
+/// Timestamps of the `.pf` file in the filesystem
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrefetchFileTimes {
+    pub created: Option<Filetime>,
+    pub modified: Option<Filetime>,
+    pub accessed: Option<Filetime>,
+}
+
+impl PrefetchFileTimes {
+    pub fn from_metadata(metadata: &VMetadata) -> Self {
+        Self {
+            created: metadata.created_opt().map(|v| unix_to_filetime(*v as u64)),
+            modified: metadata.modified_opt().map(|v| unix_to_filetime(*v as u64)),
+            accessed: metadata.accessed_opt().map(|v| unix_to_filetime(*v as u64)),
+        }
+    }
+}
+
+/// Evidence used to estimate the first execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirstRunSource {
+    /// The run count fits in the last run times, so the oldest one is the first execution
+    RunTimes,
+    /// Creation time of the `.pf` file minus `FIRST_RUN_DELAY`. Only used when it is not later than the oldest run time
+    FileCreation,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FirstRunEstimate {
+    pub time: Filetime,
+    pub source: FirstRunSource,
+}
+
+/// Estimates the first execution of the program. The prefetch only keeps the last eight run times,
+/// so when the program ran more times the creation time of the `.pf` file is the only evidence left.
+/// A `.pf` file created after the oldest run time was copied or restored, so its creation time is discarded and None is returned.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{first_run::FirstRunSource, prelude::*};
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/30/C/Windows/Prefetch/RUST_OUT.EXE-5D2C8541.pf")).unwrap();
+/// let prefetch = read_prefetch_file("RUST_OUT.EXE-5D2C8541.pf", file).unwrap();
+/// assert!(prefetch.file_times.is_some());
+/// let estimate = prefetch.first_run_estimate().unwrap();
+/// assert_eq!(FirstRunSource::RunTimes, estimate.source);
+/// ```
+pub fn estimate_first_run(prefetch: &PrefetchFile) -> Option<FirstRunEstimate> {
+    let oldest = prefetch
+        .last_run_times
+        .iter()
+        .filter(|v| v.filetime() != 0)
+        .min_by_key(|v| v.filetime());
+    if let Some(oldest) = oldest {
+        if prefetch.run_count > 0 && prefetch.run_count as usize <= prefetch.last_run_times.len() {
+            return Some(FirstRunEstimate {
+                time: *oldest,
+                source: FirstRunSource::RunTimes,
+            });
+        }
+    }
+    let time = prefetch.file_times?.created? - FIRST_RUN_DELAY;
+    if oldest
+        .map(|v| time.filetime() > v.filetime())
+        .unwrap_or(false)
+    {
+        return None;
+    }
+    Some(FirstRunEstimate {
+        time,
+        source: FirstRunSource::FileCreation,
+    })
+}
+
+/// Converts UNIX seconds (`VMetadata` times) to a FILETIME
+pub(crate) fn unix_to_filetime(seconds: u64) -> Filetime {
+    Filetime::new((seconds + UNIX_EPOCH_SECONDS) * 10_000_000)
+}
+
+#[test]
+fn should_estimate_first_run_from_file_creation() {
+    let created = Filetime::new(133_514_430_230_000_000);
+    let mut prefetch = PrefetchFile {
+        run_count: 20,
+        last_run_times: (0..8)
+            .map(|i| Filetime::new(133_600_000_000_000_000 - i * 10_000_000))
+            .collect(),
+        file_times: Some(PrefetchFileTimes {
+            created: Some(created),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let estimate = estimate_first_run(&prefetch).unwrap();
+    assert_eq!(FirstRunSource::FileCreation, estimate.source);
+    assert_eq!(created.filetime() - 100_000_000, estimate.time.filetime());
+    prefetch.run_count = 8;
+    let estimate = estimate_first_run(&prefetch).unwrap();
+    assert_eq!(FirstRunSource::RunTimes, estimate.source);
+    assert_eq!(
+        133_600_000_000_000_000 - 70_000_000,
+        estimate.time.filetime()
+    );
+    prefetch.run_count = 20;
+    // Copied file: created after the oldest run
+    prefetch.file_times = Some(PrefetchFileTimes {
+        created: Some(Filetime::new(133_700_000_000_000_000)),
+        ..Default::default()
+    });
+    assert!(estimate_first_run(&prefetch).is_none());
+    prefetch.file_times = Some(PrefetchFileTimes::default());
+    assert!(estimate_first_run(&prefetch).is_none());
+    prefetch.file_times = None;
+    assert!(estimate_first_run(&prefetch).is_none());
+    assert_eq!(116_444_736_000_000_000, unix_to_filetime(0).filetime());
+}
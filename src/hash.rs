@@ -0,0 +1,244 @@
+use forensic_rs::err::{ForensicError, ForensicResult};
+
+use crate::{common::PrefetchFile, hosting::is_hosting_application, volume_path::VolumePath};
+
+/// Maximum volume number tried when searching which `\DEVICE\HARDDISKVOLUMEx` produced a hash
+pub const MAX_HARDDISK_VOLUME: u32 = 64;
+
+/// Algorithms used by Windows to compute the hash of the prefetch file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefetchHashAlgorithm {
+    /// Windows XP and 2003 (version 17)
+    Xp,
+    /// Windows Vista and 7 (version 23)
+    Vista,
+    /// Windows 8 and later (version 26, 30 and 31)
+    Win8,
+}
+
+impl PrefetchHashAlgorithm {
+    /// Selects the algorithm used by a prefetch format version
+    pub fn from_version(version: u32) -> Option<Self> {
+        match version {
+            17 => Some(Self::Xp),
+            23 => Some(Self::Vista),
+            26 | 30 | 31 => Some(Self::Win8),
+            _ => None,
+        }
+    }
+}
+
+/// Computes the prefetch hash of the full device path of an executable. Ex: `\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\CMD.EXE`
+///
+/// ```rust
+/// use frnsc_prefetch::hash::compute_prefetch_hash;
+/// let hash = compute_prefetch_hash(r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\CMD.EXE", 26).unwrap();
+/// assert_eq!(0x4A81B364, hash);
+/// ```
+pub fn compute_prefetch_hash(device_path: &str, version: u32) -> ForensicResult<u32> {
+    let algorithm = PrefetchHashAlgorithm::from_version(version).ok_or_else(|| {
+        ForensicError::bad_format_string(format!(
+            "No hash algorithm known for prefetch version {}",
+            version
+        ))
+    })?;
+    Ok(hash_with_algorithm(device_path, algorithm))
+}
+
+/// Computes the prefetch hash of a device path with the selected algorithm. The path is hashed as UTF-16LE bytes.
+pub fn hash_with_algorithm(device_path: &str, algorithm: PrefetchHashAlgorithm) -> u32 {
+    let bytes: Vec<u8> = device_path
+        .encode_utf16()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    match algorithm {
+        PrefetchHashAlgorithm::Xp => scca_xp_hash(&bytes),
+        PrefetchHashAlgorithm::Vista => scca_vista_hash(&bytes),
+        PrefetchHashAlgorithm::Win8 => scca_2008_hash(&bytes),
+    }
+}
+
+/// Searches which `\DEVICE\HARDDISKVOLUMEx` prefix, followed by `volume_relative_path`, generates the hash. Returns the full device path.
+///
+/// ```rust
+/// use frnsc_prefetch::hash::find_device_path_for_hash;
+/// let path = find_device_path_for_hash(r"\WINDOWS\SYSWOW64\CMD.EXE", 30, 0x6D6290C5).unwrap();
+/// assert_eq!(r"\DEVICE\HARDDISKVOLUME3\WINDOWS\SYSWOW64\CMD.EXE", path);
+/// ```
+pub fn find_device_path_for_hash(
+    volume_relative_path: &str,
+    version: u32,
+    hash: u32,
+) -> Option<String> {
+    let algorithm = PrefetchHashAlgorithm::from_version(version)?;
+    (1..=MAX_HARDDISK_VOLUME)
+        .map(|i| format!(r"\DEVICE\HARDDISKVOLUME{}{}", i, volume_relative_path))
+        .find(|path| hash_with_algorithm(path, algorithm) == hash)
+}
+
+/// Outcome of re-hashing the executable path of a prefetch file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrefetchHashStatus {
+    /// The executable path generates the stored hash
+    Matches,
+    /// No volume of the executable path generates the stored hash: the file may have been renamed or forged
+    #[default]
+    Mismatch,
+    /// Hosting application (svchost, rundll32, dllhost or mmc): the hash includes the command line, so the path is not checked
+    HostingApplication,
+}
+
+/// Result of re-hashing the executable path of a prefetch file
+#[derive(Debug, Clone, Default)]
+pub struct PrefetchHashCheck {
+    /// Hash stored in the prefetch header
+    pub stored_hash: u32,
+    pub status: PrefetchHashStatus,
+    /// Full device path that generates the stored hash. Ex: `\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\CMD.EXE`
+    pub device_path: Option<String>,
+}
+
+impl PrefetchHashCheck {
+    /// The stored hash was generated by the executable path
+    pub fn matches(&self) -> bool {
+        self.status == PrefetchHashStatus::Matches
+    }
+    /// The executable path does not generate the stored hash. Hosting applications are never a mismatch
+    pub fn is_mismatch(&self) -> bool {
+        self.status == PrefetchHashStatus::Mismatch
+    }
+}
+
+/// Re-hashes the executable path of the prefetch and checks it against the hash stored in the header.
+/// The hash string of version 30 files is the exact path that was hashed, so it is tried first.
+/// Hosting applications are not re-hashed and return `HostingApplication`.
+pub fn verify_prefetch_hash(prefetch: &PrefetchFile) -> PrefetchHashCheck {
+    let mut check = PrefetchHashCheck {
+        stored_hash: prefetch.hash,
+        ..Default::default()
+    };
+    if is_hosting_application(&prefetch.name) {
+        check.status = PrefetchHashStatus::HostingApplication;
+        return check;
+    }
+    check.device_path = find_executable_device_path(prefetch);
+    if check.device_path.is_some() {
+        check.status = PrefetchHashStatus::Matches;
+    }
+    check
+}
+
+fn find_executable_device_path(prefetch: &PrefetchFile) -> Option<String> {
+    if let Some(hash_string) = &prefetch.file_information.hash_string {
+        if compute_prefetch_hash(hash_string, prefetch.version).ok() == Some(prefetch.hash) {
+            return Some(hash_string.clone());
+        }
+    }
+    let executable_path = prefetch.executable_path();
+    if executable_path.starts_with(r"\DEVICE\")
+        && compute_prefetch_hash(executable_path, prefetch.version).ok() == Some(prefetch.hash)
+    {
+        return Some(executable_path.to_string());
+    }
+    let path = VolumePath::parse(executable_path)?;
+    find_device_path_for_hash(path.relative, prefetch.version, prefetch.hash)
+}
+
+fn scca_xp_hash(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0;
+    for &byte in bytes {
+        hash = hash.wrapping_mul(37).wrapping_add(byte as u32);
+    }
+    let mut hash = hash.wrapping_mul(314159269) as u64;
+    if hash > 0x80000000 {
+        hash = 0x100000000 - hash;
+    }
+    (hash % 1000000007) as u32
+}
+
+fn scca_vista_hash(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 314159;
+    for &byte in bytes {
+        hash = hash.wrapping_mul(37).wrapping_add(byte as u32);
+    }
+    hash
+}
+
+fn scca_2008_hash(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 314159;
+    let mut pos = 0;
+    while pos + 8 < bytes.len() {
+        let chunk = &bytes[pos..pos + 8];
+        let mut value = (chunk[1] as u32).wrapping_mul(37);
+        for &byte in &chunk[2..7] {
+            value = value.wrapping_add(byte as u32).wrapping_mul(37);
+        }
+        value = value
+            .wrapping_add((chunk[0] as u32).wrapping_mul(442596621))
+            .wrapping_add(chunk[7] as u32);
+        hash = value.wrapping_sub(hash.wrapping_mul(803794207));
+        pos += 8;
+    }
+    for &byte in &bytes[pos..] {
+        hash = hash.wrapping_mul(37).wrapping_add(byte as u32);
+    }
+    hash
+}
+
+#[test]
+fn should_compute_xp_hash() {
+    let hash =
+        compute_prefetch_hash(r"\DEVICE\HARDDISKVOLUME1\WINDOWS\SYSTEM32\CMD.EXE", 17).unwrap();
+    assert_eq!(0x087B4001, hash);
+}
+
+#[test]
+fn should_compute_vista_hash() {
+    let hash =
+        compute_prefetch_hash(r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\NOTEPAD.EXE", 23).unwrap();
+    assert_eq!(0xD8414F97, hash);
+}
+
+#[test]
+fn should_compute_win8_hash() {
+    let hash =
+        compute_prefetch_hash(r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\CMD.EXE", 26).unwrap();
+    assert_eq!(0x4A81B364, hash);
+    let hash = compute_prefetch_hash(
+        r"\DEVICE\HARDDISKVOLUME3\WINDOWS\SYSWOW64\WINDOWSPOWERSHELL\V1.0\POWERSHELL.EXE",
+        30,
+    )
+    .unwrap();
+    assert_eq!(0xAE8EDC9B, hash);
+}
+
+#[test]
+fn should_not_report_hosting_applications_as_mismatch() {
+    let prefetch = PrefetchFile {
+        version: 30,
+        name: "SVCHOST.EXE".into(),
+        hash: 0x12345678,
+        metrics: vec![crate::common::Metric {
+            file: r"\VOLUME{01d861d36906cd20-22690e22}\WINDOWS\SYSTEM32\SVCHOST.EXE".into(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let check = verify_prefetch_hash(&prefetch);
+    assert_eq!(PrefetchHashStatus::HostingApplication, check.status);
+    assert!(!check.matches());
+    assert!(!check.is_mismatch());
+    let check = verify_prefetch_hash(&PrefetchFile {
+        name: "CMD.EXE".into(),
+        ..prefetch
+    });
+    assert!(check.is_mismatch());
+}
+
+#[test]
+fn should_find_device_path_for_hash() {
+    let path = find_device_path_for_hash(r"\WINDOWS\SYSTEM32\CMD.EXE", 30, 0xD269B812).unwrap();
+    assert_eq!(r"\DEVICE\HARDDISKVOLUME8\WINDOWS\SYSTEM32\CMD.EXE", path);
+    assert!(find_device_path_for_hash(r"\WINDOWS\SYSTEM32\CMD.EXE", 30, 0x12345678).is_none());
+    assert!(compute_prefetch_hash(r"\WINDOWS\SYSTEM32\CMD.EXE", 12).is_err());
+}
@@ -0,0 +1,19 @@
+/// Executables that host code from other files. Windows includes the command line in the prefetch hash of these programs,
+/// so there is a prefetch file for each service group, DLL or COM object. Which bytes of the command line are hashed is
+/// not documented and no hosting prefetch file is available to verify it, so their command line is not searched.
+pub const HOSTING_APPLICATIONS: [&str; 4] =
+    ["SVCHOST.EXE", "RUNDLL32.EXE", "DLLHOST.EXE", "MMC.EXE"];
+
+/// Checks if the executable name belongs to a known hosting application
+pub fn is_hosting_application(executable_name: &str) -> bool {
+    HOSTING_APPLICATIONS
+        .iter()
+        .any(|v| v.eq_ignore_ascii_case(executable_name))
+}
+
+#[test]
+fn should_detect_hosting_applications() {
+    assert!(is_hosting_application("SVCHOST.EXE"));
+    assert!(is_hosting_application("rundll32.exe"));
+    assert!(!is_hosting_application("CMD.EXE"));
+}
@@ -0,0 +1,267 @@
+use forensic_rs::utils::time::Filetime;
+
+use crate::{
+    common::{PrefetchFile, VolumeInformation},
+    volume_path::VolumePath,
+};
+
+/// Kind of device of a volume, from its NT device path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeDeviceType {
+    /// `\DEVICE\HARDDISKVOLUME2`: partition of a fixed or removable disk (USB sticks included)
+    HardDisk,
+    /// `\DEVICE\CDROM0`: optical drive or mounted ISO image
+    CdRom,
+    /// `\DEVICE\MUP\...` or `\DEVICE\LANMANREDIRECTOR\...`: network share
+    Network,
+    /// `\DEVICE\FLOPPY0`
+    Floppy,
+    /// `\DEVICE\VHDHARDDISK{...}`: virtual hard disk
+    Vhd,
+    /// Windows 10 and later only store `\VOLUME{<creation>-<serial>}` without the device
+    Unknown,
+}
+
+impl VolumeDeviceType {
+    pub fn from_device_path(device_path: &str) -> Self {
+        let upper = device_path.to_ascii_uppercase();
+        let device = match upper.strip_prefix(r"\DEVICE\") {
+            Some(v) => v,
+            None => return Self::Unknown,
+        };
+        if device.starts_with("HARDDISKVOLUME") {
+            Self::HardDisk
+        } else if device.starts_with("CDROM") {
+            Self::CdRom
+        } else if device.starts_with("MUP")
+            || device.starts_with("LANMANREDIRECTOR")
+            || device.starts_with("WEBDAVREDIRECTOR")
+        {
+            Self::Network
+        } else if device.starts_with("FLOPPY") {
+            Self::Floppy
+        } else if device.starts_with("VHDHARDDISK") {
+            Self::Vhd
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// Executable that ran from a volume of the inventory
+#[derive(Debug, Clone)]
+pub struct InventoryExecutable {
+    /// Full path of the executable as stored in the prefetch. Ex: `\VOLUME{01d861d36906cd20-22690e22}\WINDOWS\SYSWOW64\CMD.EXE`
+    pub path: String,
+    /// Names of the prefetch files of the executable, as they were parsed. Ex: `CMD.EXE-087B4001.pf`
+    pub prefetch_files: Vec<String>,
+    pub run_count: u32,
+    pub first_run: Option<Filetime>,
+    pub last_run: Option<Filetime>,
+}
+
+/// Volume seen in one or more prefetch files, identified by its serial number and creation time
+#[derive(Debug, Clone)]
+pub struct InventoryVolume {
+    pub serial_number: u32,
+    pub creation_time: u64,
+    /// Distinct device paths of the volume in the prefetch files
+    pub device_paths: Vec<String>,
+    pub device_type: VolumeDeviceType,
+    /// Number of prefetch files that reference the volume
+    pub prefetch_count: usize,
+    pub executables: Vec<InventoryExecutable>,
+    /// Oldest run time of the executables of the volume
+    pub first_run: Option<Filetime>,
+    /// Most recent run time of the executables of the volume
+    pub last_run: Option<Filetime>,
+}
+
+impl InventoryVolume {
+    fn new(volume: &VolumeInformation) -> Self {
+        Self {
+            serial_number: volume.serial_number,
+            creation_time: volume.creation_time,
+            device_paths: Vec::new(),
+            device_type: VolumeDeviceType::Unknown,
+            prefetch_count: 0,
+            executables: Vec::new(),
+            first_run: None,
+            last_run: None,
+        }
+    }
+
+    pub fn creation_time(&self) -> Filetime {
+        Filetime::new(self.creation_time)
+    }
+
+    /// Some programs were executed from the volume, not only libraries or data files loaded
+    pub fn has_executables(&self) -> bool {
+        !self.executables.is_empty()
+    }
+
+    fn is(&self, volume: &VolumeInformation) -> bool {
+        self.serial_number == volume.serial_number && self.creation_time == volume.creation_time
+    }
+}
+
+/// De-duplicated list of the volumes referenced by a set of prefetch files, in order of appearance.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{inventory::*, prelude::*};
+/// let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+/// let prefetches = scan_prefetch_report(&mut fs).unwrap().prefetches;
+/// let inventory = VolumeInventory::from_prefetches(&prefetches);
+/// let volume = inventory.volume(0x22690e22, 0x01d861d36906cd20).unwrap();
+/// assert_eq!(2, volume.prefetch_count);
+/// assert_eq!(2, volume.executables.len());
+/// assert_eq!(VolumeDeviceType::Unknown, volume.device_type);
+/// assert!(volume.first_run.unwrap().filetime() <= volume.last_run.unwrap().filetime());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VolumeInventory {
+    pub volumes: Vec<InventoryVolume>,
+}
+
+impl VolumeInventory {
+    pub fn from_prefetches(prefetches: &[PrefetchFile]) -> Self {
+        let mut inventory = Self::default();
+        for prefetch in prefetches {
+            inventory.add(prefetch);
+        }
+        inventory
+    }
+
+    /// Adds the volumes and the executable of a prefetch file
+    pub fn add(&mut self, prefetch: &PrefetchFile) {
+        let executable = prefetch.executable_path();
+        let executable_volume = VolumePath::parse(executable)
+            .and_then(|v| v.position_in(&prefetch.volume))
+            .or_else(|| single_volume(prefetch));
+        for (i, volume) in prefetch.volume.iter().enumerate() {
+            let position = self.position_or_insert(volume);
+            let entry = &mut self.volumes[position];
+            entry.prefetch_count += 1;
+            if !entry
+                .device_paths
+                .iter()
+                .any(|v| v.eq_ignore_ascii_case(&volume.device_path))
+            {
+                entry.device_paths.push(volume.device_path.clone());
+            }
+            if entry.device_type == VolumeDeviceType::Unknown {
+                entry.device_type = VolumeDeviceType::from_device_path(&volume.device_path);
+            }
+            if executable_volume == Some(i) {
+                add_executable(entry, prefetch, executable);
+            }
+        }
+    }
+
+    /// Volume with the given serial number and creation time
+    pub fn volume(&self, serial_number: u32, creation_time: u64) -> Option<&InventoryVolume> {
+        self.volumes
+            .iter()
+            .find(|v| v.serial_number == serial_number && v.creation_time == creation_time)
+    }
+
+    /// Volumes of a device type. Ex: `VolumeDeviceType::Network` for the shares programs were run from
+    pub fn of_type(&self, device_type: VolumeDeviceType) -> impl Iterator<Item = &InventoryVolume> {
+        self.volumes
+            .iter()
+            .filter(move |v| v.device_type == device_type)
+    }
+
+    fn position_or_insert(&mut self, volume: &VolumeInformation) -> usize {
+        match self.volumes.iter().position(|v| v.is(volume)) {
+            Some(v) => v,
+            None => {
+                self.volumes.push(InventoryVolume::new(volume));
+                self.volumes.len() - 1
+            }
+        }
+    }
+}
+
+/// Volume of the executable when its path has no volume prefix and the prefetch has only one volume
+fn single_volume(prefetch: &PrefetchFile) -> Option<usize> {
+    if prefetch.volume.len() == 1 && prefetch.executable_path() != prefetch.name {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+fn add_executable(volume: &mut InventoryVolume, prefetch: &PrefetchFile, path: &str) {
+    let first_run = oldest(prefetch.last_run_times.iter().copied());
+    let last_run = newest(prefetch.last_run_times.iter().copied());
+    let file_name = if prefetch.file_name.is_empty() {
+        format!("{}-{:08X}.pf", prefetch.name, prefetch.hash)
+    } else {
+        prefetch.file_name.clone()
+    };
+    match volume
+        .executables
+        .iter_mut()
+        .find(|v| v.path.eq_ignore_ascii_case(path))
+    {
+        Some(executable) => {
+            executable.prefetch_files.push(file_name);
+            executable.run_count += prefetch.run_count;
+            executable.first_run = oldest(executable.first_run.into_iter().chain(first_run));
+            executable.last_run = newest(executable.last_run.into_iter().chain(last_run));
+        }
+        None => volume.executables.push(InventoryExecutable {
+            path: path.to_string(),
+            prefetch_files: vec![file_name],
+            run_count: prefetch.run_count,
+            first_run,
+            last_run,
+        }),
+    }
+    volume.first_run = oldest(volume.first_run.into_iter().chain(first_run));
+    volume.last_run = newest(volume.last_run.into_iter().chain(last_run));
+}
+
+fn oldest(times: impl Iterator<Item = Filetime>) -> Option<Filetime> {
+    times
+        .filter(|v| v.filetime() != 0)
+        .min_by_key(|v| v.filetime())
+}
+
+fn newest(times: impl Iterator<Item = Filetime>) -> Option<Filetime> {
+    times
+        .filter(|v| v.filetime() != 0)
+        .max_by_key(|v| v.filetime())
+}
+
+#[test]
+fn should_classify_device_paths() {
+    assert_eq!(
+        VolumeDeviceType::HardDisk,
+        VolumeDeviceType::from_device_path(r"\DEVICE\HARDDISKVOLUME2")
+    );
+    assert_eq!(
+        VolumeDeviceType::CdRom,
+        VolumeDeviceType::from_device_path(r"\Device\CdRom0")
+    );
+    assert_eq!(
+        VolumeDeviceType::Network,
+        VolumeDeviceType::from_device_path(r"\DEVICE\MUP\;LANMANREDIRECTOR\SERVER\SHARE")
+    );
+    assert_eq!(
+        VolumeDeviceType::Floppy,
+        VolumeDeviceType::from_device_path(r"\DEVICE\FLOPPY0")
+    );
+    assert_eq!(
+        VolumeDeviceType::Vhd,
+        VolumeDeviceType::from_device_path(
+            r"\DEVICE\VHDHARDDISK{4C1B02C1-D990-11DC-99AE-806E6F6E6963}"
+        )
+    );
+    assert_eq!(
+        VolumeDeviceType::Unknown,
+        VolumeDeviceType::from_device_path(r"\VOLUME{01d861d36906cd20-22690e22}")
+    );
+}
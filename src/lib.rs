@@ -1,17 +1,41 @@
+pub mod attribution;
+pub mod category;
 pub mod common;
+pub mod consistency;
 pub mod decompress;
+pub mod drive;
+pub mod error;
+pub mod executable;
+pub mod first_run;
+pub mod hash;
+pub mod hosting;
+pub mod inventory;
 pub mod metrics;
+pub mod mft;
+pub mod mounted_image;
 pub mod prefetch;
+pub mod prefetcher;
+pub mod provenance;
+pub mod references;
+pub mod removable;
+pub mod runs;
+pub mod scan;
+pub mod serialize;
 pub mod trace;
+pub mod view;
 pub mod volume;
+pub mod volume_path;
 
 #[cfg(test)]
 pub(crate) mod tst;
 
 pub mod prelude {
     pub use crate::common::PrefetchFile;
+    #[allow(deprecated)]
+    pub use crate::prefetch::read_prefetch_form_fs;
     pub use crate::prefetch::{
         read_prefetch_file, read_prefetch_file_compressed, read_prefetch_file_no_compressed,
-        read_prefetch_form_fs,
+        read_prefetch_file_with_provenance, write_mam,
     };
+    pub use crate::scan::scan_prefetch_report;
 }
@@ -0,0 +1,336 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use forensic_rs::{
+    err::{ForensicError, ForensicResult},
+    traits::vfs::{VirtualFile, VirtualFileSystem},
+};
+
+use crate::{
+    common::{u16_at_pos, u32_at_pos, u64_at_pos, utf16_from_bytes, Metric, NtfsFile},
+    references::VolumeReferences,
+};
+
+/// MFT entry of the root directory of a NTFS volume
+pub const MFT_ROOT_ENTRY: u64 = 5;
+/// Folder used for the files whose parent directory no longer exists, as in The Sleuth Kit
+pub const ORPHAN_FOLDER: &str = r"\$OrphanFiles";
+
+const DEFAULT_RECORD_SIZE: usize = 1024;
+const SECTOR_SIZE: usize = 512;
+const ATTRIBUTE_FILE_NAME: u32 = 0x30;
+const ATTRIBUTE_END: u32 = 0xFFFF_FFFF;
+const RECORD_IN_USE: u16 = 0x01;
+const RECORD_DIRECTORY: u16 = 0x02;
+const NAMESPACE_DOS: u8 = 2;
+const MAX_PATH_DEPTH: usize = 256;
+
+/// Header and name of a $MFT record
+#[derive(Debug, Clone, Default)]
+pub struct MftRecord {
+    pub entry: u64,
+    pub seq_number: u16,
+    pub in_use: bool,
+    pub is_directory: bool,
+    /// Parent directory from the $FILE_NAME attribute
+    pub parent: Option<NtfsFile>,
+    /// Long name of the $FILE_NAME attribute (Win32 or POSIX namespace when available)
+    pub name: Option<String>,
+}
+
+/// State of the record pointed by a prefetch file reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MftReferenceStatus {
+    /// The record is in use with the same sequence number
+    Present,
+    /// The record is in use with a different sequence number: the file was deleted and the entry reused by another file
+    Replaced { current_seq_number: u16 },
+    /// The record is not in use: the file was deleted
+    Deleted { current_seq_number: u16 },
+    /// The entry is not a valid record or is beyond the end of the $MFT
+    Missing,
+}
+
+/// Result of looking up a prefetch file reference in the $MFT
+#[derive(Debug, Clone)]
+pub struct ResolvedReference<'a> {
+    pub reference: NtfsFile,
+    /// Metric of the file when the reference is stored in a metric entry
+    pub metric: Option<&'a Metric>,
+    pub status: MftReferenceStatus,
+    pub is_directory: bool,
+    /// Path of the record inside the volume. Ex: `\Windows\System32\ntdll.dll`
+    pub current_path: Option<String>,
+    /// The path of the record matches the path of the metric (ignoring case)
+    pub path_matches: Option<bool>,
+}
+
+/// Looks up NTFS file references in the $MFT of a volume. Records are read on demand.
+///
+/// ```rust,no_run
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{mft::MftResolver, prelude::*};
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./image/C/Windows/Prefetch/CMD.EXE-4A81B364.pf")).unwrap();
+/// let prefetch = read_prefetch_file("CMD.EXE-4A81B364.pf", file).unwrap();
+/// let mut resolver = MftResolver::from_fs(&mut fs, Path::new("./image/C/$MFT")).unwrap();
+/// for volume in prefetch.volume_references() {
+///     for resolved in resolver.resolve_volume(&volume).unwrap() {
+///         println!("{:?} {:?} {:?}", resolved.reference, resolved.status, resolved.current_path);
+///     }
+/// }
+/// ```
+pub struct MftResolver {
+    file: Box<dyn VirtualFile>,
+    record_size: usize,
+    records: u64,
+    cache: HashMap<u64, Option<MftRecord>>,
+}
+
+impl MftResolver {
+    /// Opens the $MFT of a mounted volume. Ex: `C:\$MFT`
+    pub fn from_fs<F: VirtualFileSystem>(fs: &mut F, path: &Path) -> ForensicResult<Self> {
+        Self::new(fs.open(path)?)
+    }
+
+    pub fn new(mut file: Box<dyn VirtualFile>) -> ForensicResult<Self> {
+        let size = file.metadata()?.size;
+        let mut header = [0u8; 32];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+        if &header[0..4] != b"FILE" {
+            return Err(ForensicError::bad_format_str(
+                "Invalid $MFT record signature",
+            ));
+        }
+        let record_size = match u32_at_pos(&header, 0x1C) as usize {
+            0 => DEFAULT_RECORD_SIZE,
+            v if v % SECTOR_SIZE == 0 && v <= 4096 => v,
+            _ => return Err(ForensicError::bad_format_str("Invalid $MFT record size")),
+        };
+        Ok(Self {
+            file,
+            record_size,
+            records: size / record_size as u64,
+            cache: HashMap::new(),
+        })
+    }
+
+    /// Number of records in the $MFT
+    pub fn record_count(&self) -> u64 {
+        self.records
+    }
+
+    /// Reads a record. Returns None for entries beyond the end of the $MFT or without a valid signature.
+    pub fn record(&mut self, entry: u64) -> ForensicResult<Option<MftRecord>> {
+        if let Some(record) = self.cache.get(&entry) {
+            return Ok(record.clone());
+        }
+        let record = self.read_record(entry)?;
+        self.cache.insert(entry, record.clone());
+        Ok(record)
+    }
+
+    fn read_record(&mut self, entry: u64) -> ForensicResult<Option<MftRecord>> {
+        if entry >= self.records {
+            return Ok(None);
+        }
+        let mut buffer = vec![0u8; self.record_size];
+        self.file
+            .seek(SeekFrom::Start(entry * self.record_size as u64))?;
+        self.file.read_exact(&mut buffer)?;
+        Ok(parse_record(entry, &mut buffer))
+    }
+
+    /// Path of a record inside the volume, following the parent directories up to the root.
+    /// Files whose parent was deleted or reused are placed under `\$OrphanFiles`.
+    pub fn path_of(&mut self, entry: u64) -> ForensicResult<Option<String>> {
+        let mut names = Vec::new();
+        let mut current = match self.record(entry)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if current.entry == MFT_ROOT_ENTRY {
+            return Ok(Some(r"\".into()));
+        }
+        let mut orphan = false;
+        loop {
+            let name = match &current.name {
+                Some(v) => v.clone(),
+                None => return Ok(None),
+            };
+            names.push(name);
+            let parent = match &current.parent {
+                Some(v) => v.clone(),
+                None => {
+                    orphan = true;
+                    break;
+                }
+            };
+            if parent.mft_entry == MFT_ROOT_ENTRY {
+                break;
+            }
+            if names.len() >= MAX_PATH_DEPTH {
+                orphan = true;
+                break;
+            }
+            current = match self.record(parent.mft_entry)? {
+                Some(v) if v.in_use && v.is_directory && v.seq_number == parent.seq_number => v,
+                _ => {
+                    orphan = true;
+                    break;
+                }
+            };
+        }
+        let mut path = if orphan {
+            ORPHAN_FOLDER.to_string()
+        } else {
+            String::new()
+        };
+        for name in names.iter().rev() {
+            path.push('\\');
+            path.push_str(name);
+        }
+        Ok(Some(path))
+    }
+
+    /// Looks up a file reference
+    pub fn resolve(&mut self, reference: &NtfsFile) -> ForensicResult<ResolvedReference<'static>> {
+        let record = match self.record(reference.mft_entry)? {
+            Some(v) => v,
+            None => {
+                return Ok(ResolvedReference {
+                    reference: reference.clone(),
+                    metric: None,
+                    status: MftReferenceStatus::Missing,
+                    is_directory: false,
+                    current_path: None,
+                    path_matches: None,
+                })
+            }
+        };
+        let status = if !record.in_use {
+            MftReferenceStatus::Deleted {
+                current_seq_number: record.seq_number,
+            }
+        } else if record.seq_number != reference.seq_number {
+            MftReferenceStatus::Replaced {
+                current_seq_number: record.seq_number,
+            }
+        } else {
+            MftReferenceStatus::Present
+        };
+        Ok(ResolvedReference {
+            reference: reference.clone(),
+            metric: None,
+            status,
+            is_directory: record.is_directory,
+            current_path: self.path_of(reference.mft_entry)?,
+            path_matches: None,
+        })
+    }
+
+    /// Looks up the references of the metrics of a volume and the references not linked to any metric.
+    /// The $MFT must belong to the same volume.
+    pub fn resolve_volume<'a>(
+        &mut self,
+        volume: &VolumeReferences<'a>,
+    ) -> ForensicResult<Vec<ResolvedReference<'a>>> {
+        let mut resolved = Vec::with_capacity(volume.files.len() + volume.unclaimed.len());
+        for file in &volume.files {
+            let reference = match &file.reference {
+                Some(v) => v,
+                None => continue,
+            };
+            let mut result: ResolvedReference<'a> = self.resolve(reference)?;
+            let relative = file.metric.relative_path();
+            result.path_matches = match (&result.current_path, relative) {
+                (Some(current), Some(relative)) => Some(current.eq_ignore_ascii_case(relative)),
+                _ => None,
+            };
+            result.metric = Some(file.metric);
+            resolved.push(result);
+        }
+        for reference in &volume.unclaimed {
+            resolved.push(self.resolve(reference)?);
+        }
+        Ok(resolved)
+    }
+}
+
+/// Applies the update sequence array and decodes the header and the $FILE_NAME of a record
+fn parse_record(entry: u64, buffer: &mut [u8]) -> Option<MftRecord> {
+    if &buffer[0..4] != b"FILE" {
+        return None;
+    }
+    apply_fixups(buffer)?;
+    let flags = u16_at_pos(buffer, 0x16);
+    let mut record = MftRecord {
+        entry,
+        seq_number: u16_at_pos(buffer, 0x10),
+        in_use: flags & RECORD_IN_USE != 0,
+        is_directory: flags & RECORD_DIRECTORY != 0,
+        ..Default::default()
+    };
+    let mut pos = u16_at_pos(buffer, 0x14) as usize;
+    let mut best_namespace = None;
+    while pos + 8 <= buffer.len() {
+        let attribute_type = u32_at_pos(buffer, pos);
+        let length = u32_at_pos(buffer, pos + 4) as usize;
+        if attribute_type == ATTRIBUTE_END || length == 0 || pos + length > buffer.len() {
+            break;
+        }
+        let attribute = &buffer[pos..pos + length];
+        // Only resident attributes, $FILE_NAME is always resident
+        if attribute_type == ATTRIBUTE_FILE_NAME && attribute.len() >= 0x18 && attribute[8] == 0 {
+            let content_size = u32_at_pos(attribute, 0x10) as usize;
+            let content_offset = u16_at_pos(attribute, 0x14) as usize;
+            if let Some(content) = attribute.get(content_offset..content_offset + content_size) {
+                if content.len() >= 0x42 {
+                    let name_length = content[0x40] as usize;
+                    let namespace = content[0x41];
+                    let better = match best_namespace {
+                        None => true,
+                        Some(NAMESPACE_DOS) => namespace != NAMESPACE_DOS,
+                        Some(_) => false,
+                    };
+                    if let Some(name) = content.get(0x42..0x42 + name_length * 2) {
+                        if better {
+                            best_namespace = Some(namespace);
+                            record.name = Some(utf16_from_bytes(name));
+                            record.parent = NtfsFile::from_reference(u64_at_pos(content, 0));
+                        }
+                    }
+                }
+            }
+        }
+        pos += length;
+    }
+    Some(record)
+}
+
+/// Restores the last two bytes of each sector from the update sequence array
+fn apply_fixups(buffer: &mut [u8]) -> Option<()> {
+    let offset = u16_at_pos(buffer, 0x04) as usize;
+    let count = u16_at_pos(buffer, 0x06) as usize;
+    if count == 0 {
+        return Some(());
+    }
+    let array = buffer.get(offset..offset + count * 2)?.to_vec();
+    let sequence = [array[0], array[1]];
+    for i in 1..count {
+        let end = i * SECTOR_SIZE;
+        if end > buffer.len() {
+            break;
+        }
+        if buffer[end - 2..end] != sequence {
+            return None;
+        }
+        buffer[end - 2..end].copy_from_slice(&array[i * 2..i * 2 + 2]);
+    }
+    Some(())
+}
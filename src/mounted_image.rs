@@ -0,0 +1,208 @@
+use crate::{
+    common::{Metric, PrefetchFile, VolumeInformation},
+    inventory::VolumeDeviceType,
+    removable::{is_system_volume, metrics_in_volume, DEFAULT_SYSTEM_FOLDER},
+};
+
+/// Number of FILETIME intervals (100 ns) in a day
+const FILETIME_DAY: u64 = 864_000_000_000;
+
+/// Characteristic of a volume that points to a mounted ISO, IMG or VHD file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MountedImageIndicator {
+    /// `\DEVICE\CDROM<n>`: optical drive or mounted ISO/IMG image
+    OpticalDevice,
+    /// `\DEVICE\VHDHARDDISK{...}`: mounted virtual hard disk
+    VirtualDiskDevice,
+    /// The volume was created a few days before one of the runs. Images keep the creation time of the authoring tool
+    RecentVolume { days_before_run: u64 },
+    /// No NTFS file references (version 23 and later, Windows Vista onwards): CDFS, UDF or FAT file system
+    NoFileReferences,
+    /// Few directories were accessed in the volume
+    FewDirectories { count: usize },
+}
+
+impl MountedImageIndicator {
+    /// The device path alone identifies the volume as an image
+    pub fn is_device(&self) -> bool {
+        matches!(self, Self::OpticalDevice | Self::VirtualDiskDevice)
+    }
+}
+
+/// How sure the detector is that the volume is a mounted image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MountedImageConfidence {
+    /// Non NTFS volume with few directories. Also matches USB sticks
+    Low,
+    /// Recent volume with another characteristic of an image
+    Medium,
+    /// CD-ROM or virtual disk device path
+    High,
+}
+
+/// Prefetch with the executable or loaded files in a volume that looks like a mounted image
+#[derive(Debug, Clone)]
+pub struct MountedImageFinding<'a> {
+    pub prefetch: &'a PrefetchFile,
+    pub volume: &'a VolumeInformation,
+    /// The executable itself is in the volume
+    pub executable: bool,
+    /// Files of the volume loaded by the executable
+    pub loaded: Vec<&'a Metric>,
+    pub indicators: Vec<MountedImageIndicator>,
+    pub confidence: MountedImageConfidence,
+}
+
+/// Finds prefetch files whose executable or DLLs were loaded from a mounted ISO, IMG or VHD file.
+/// These containers are used to deliver payloads without the Mark-of-the-Web.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{mounted_image::*, prelude::*};
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/30/C/Windows/Prefetch/CMD.EXE-D269B812.pf")).unwrap();
+/// let mut prefetch = read_prefetch_file("CMD.EXE-D269B812.pf", file).unwrap();
+/// let detector = MountedImageDetector::new();
+/// assert!(detector.detect(&prefetch).is_empty());
+/// // The volume with the clink DLL mounted from an ISO file
+/// let position = prefetch.volume.iter().position(|v| v.serial_number == 0x66f451bc).unwrap();
+/// prefetch.volume[position].device_path = r"\DEVICE\CDROM1".into();
+/// let found = detector.detect(&prefetch);
+/// assert_eq!(MountedImageConfidence::High, found[0].confidence);
+/// assert!(!found[0].executable);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MountedImageDetector {
+    /// Windows folder inside the system volume, which is never reported. Ex: `\WINDOWS`
+    pub system_folder: String,
+    /// Maximum days between the creation of the volume and a run to consider the volume recent
+    pub recent_days: u64,
+    /// Maximum number of directories of a volume to consider it small
+    pub max_directories: usize,
+    /// Findings below this confidence are discarded
+    pub min_confidence: MountedImageConfidence,
+}
+
+impl Default for MountedImageDetector {
+    fn default() -> Self {
+        Self {
+            system_folder: DEFAULT_SYSTEM_FOLDER.into(),
+            recent_days: 30,
+            max_directories: 3,
+            min_confidence: MountedImageConfidence::Medium,
+        }
+    }
+}
+
+impl MountedImageDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Characteristics of an image found in a volume of the prefetch
+    pub fn indicators(
+        &self,
+        prefetch: &PrefetchFile,
+        volume: &VolumeInformation,
+    ) -> Vec<MountedImageIndicator> {
+        let mut indicators = Vec::new();
+        match VolumeDeviceType::from_device_path(&volume.device_path) {
+            VolumeDeviceType::CdRom => indicators.push(MountedImageIndicator::OpticalDevice),
+            VolumeDeviceType::Vhd => indicators.push(MountedImageIndicator::VirtualDiskDevice),
+            _ => {}
+        }
+        let days_before_run = prefetch
+            .last_run_times
+            .iter()
+            .map(|v| v.filetime())
+            .filter(|run| *run != 0 && volume.creation_time != 0 && *run >= volume.creation_time)
+            .map(|run| (run - volume.creation_time) / FILETIME_DAY)
+            .min();
+        if let Some(days_before_run) = days_before_run {
+            if days_before_run <= self.recent_days {
+                indicators.push(MountedImageIndicator::RecentVolume { days_before_run });
+            }
+        }
+        // Version 17 does not store file references
+        if prefetch.version >= 23 && volume.file_references.is_empty() {
+            indicators.push(MountedImageIndicator::NoFileReferences);
+        }
+        let count = volume.directory_strings.len();
+        if count <= self.max_directories {
+            indicators.push(MountedImageIndicator::FewDirectories { count });
+        }
+        indicators
+    }
+
+    /// Volumes of the prefetch outside the system volume that look like a mounted image
+    pub fn detect<'a>(&self, prefetch: &'a PrefetchFile) -> Vec<MountedImageFinding<'a>> {
+        let executable = prefetch.executable_path();
+        let mut found = Vec::new();
+        for volume in &prefetch.volume {
+            if is_system_volume(volume, &self.system_folder) {
+                continue;
+            }
+            let indicators = self.indicators(prefetch, volume);
+            let confidence = match confidence_of(&indicators) {
+                Some(v) if v >= self.min_confidence => v,
+                _ => continue,
+            };
+            let loaded = metrics_in_volume(prefetch, volume);
+            if loaded.is_empty() {
+                continue;
+            }
+            found.push(MountedImageFinding {
+                prefetch,
+                volume,
+                executable: loaded.iter().any(|v| v.file == executable),
+                loaded,
+                indicators,
+                confidence,
+            });
+        }
+        found
+    }
+
+    /// Runs `detect` over a set of prefetch files
+    pub fn detect_all<'a>(&self, prefetches: &'a [PrefetchFile]) -> Vec<MountedImageFinding<'a>> {
+        prefetches.iter().flat_map(|v| self.detect(v)).collect()
+    }
+}
+
+fn confidence_of(indicators: &[MountedImageIndicator]) -> Option<MountedImageConfidence> {
+    if indicators.iter().any(|v| v.is_device()) {
+        return Some(MountedImageConfidence::High);
+    }
+    let recent = indicators
+        .iter()
+        .any(|v| matches!(v, MountedImageIndicator::RecentVolume { .. }));
+    let others = indicators
+        .iter()
+        .filter(|v| !matches!(v, MountedImageIndicator::RecentVolume { .. }))
+        .count();
+    match (recent, others) {
+        (true, n) if n >= 1 => Some(MountedImageConfidence::Medium),
+        (false, n) if n >= 2 => Some(MountedImageConfidence::Low),
+        _ => None,
+    }
+}
+
+#[test]
+fn should_grade_mounted_image_indicators() {
+    use MountedImageIndicator::*;
+    assert_eq!(
+        Some(MountedImageConfidence::High),
+        confidence_of(&[OpticalDevice])
+    );
+    assert_eq!(
+        Some(MountedImageConfidence::Medium),
+        confidence_of(&[RecentVolume { days_before_run: 0 }, NoFileReferences])
+    );
+    assert_eq!(
+        Some(MountedImageConfidence::Low),
+        confidence_of(&[NoFileReferences, FewDirectories { count: 1 }])
+    );
+    assert_eq!(None, confidence_of(&[RecentVolume { days_before_run: 2 }]));
+    assert_eq!(None, confidence_of(&[NoFileReferences]));
+}
@@ -1,61 +1,51 @@
-use std::{io::Read, path::Path};
+use std::io::Read;
 
 use forensic_rs::{
-    artifact::WindowsArtifacts,
     err::{ForensicError, ForensicResult},
     notifications::NotificationType,
-    notify_high, notify_low,
-    traits::vfs::{VDirEntry, VirtualFile, VirtualFileSystem},
-    utils::time::Filetime,
+    notify_low,
+    traits::vfs::{VirtualFile, VirtualFileSystem},
 };
 
 use crate::{
-    common::{u32_at_pos, u64_at_pos, PrefetchFile, PrefetchFileInformation},
-    decompress::{decompress, CompressionAlgorithm},
-    metrics::*,
-    volume::*,
+    common::{u32_at_pos, utf16_from_bytes, PrefetchFile, PrefetchHeader},
+    decompress::{decompress, decompress_with_chunks, xpress_huff_compress, CompressionAlgorithm},
+    error::{InStage, ParseStage, PrefetchParseError, PrefetchParseResult},
+    first_run::PrefetchFileTimes,
+    hash::PrefetchHashCheck,
+    metrics::check_anomaly_in_metrics,
+    provenance::{collect_provenance, CompressionMap},
+    scan::scan_prefetch_folder,
+    view::PrefetchView,
 };
 
 const PREFETCH_SIZE_LIMIT: u64 = 1_000_000;
 /// Signature = MAM
 const PREFETCH_COMPRESS_SIGNATURE: u32 = u32::from_le_bytes([b'M', b'A', b'M', b'\0']);
 const PREFETC_COMPRESS_SIGNATURE_U8: &[u8] = b"MAM";
+/// The compressed prefetch has a CRC32 after the header
+const PREFETCH_COMPRESS_CRC_FLAG: u32 = 0x80000000;
 
-/// Reads all prefetch files on the folder C:\Windows\Prefetch.
+/// Reads all prefetch files on the folder C:\Windows\Prefetch. Files that fail to parse are only logged and dropped:
+/// use [`scan_prefetch_report`](crate::scan::scan_prefetch_report), which returns them with the failing stage.
 ///
 /// ```rust
+/// # #![allow(deprecated)]
 /// use forensic_rs::prelude::*;
 /// use frnsc_prefetch::prelude::*;
 /// let mut fs = ChRootFileSystem::new("./artifacts/17", Box::new(StdVirtualFS::new()));
 /// let _list = read_prefetch_form_fs(&mut fs).expect("Must read all prefetch from filesystem");
 /// ```
+#[deprecated(note = "failures are dropped, use scan_prefetch_report")]
 pub fn read_prefetch_form_fs(fs: &mut impl VirtualFileSystem) -> ForensicResult<Vec<PrefetchFile>> {
-    forensic_rs::context::set_artifact(WindowsArtifacts::Prefetch);
-    let prefetch_folder = Path::new(r"C:\Windows\Prefetch");
-    let prefetch_files = match fs.read_dir(prefetch_folder) {
-        Ok(v) => v,
-        Err(e) => {
-            notify_high!(NotificationType::AntiForensicsDetected, "No prefetch found");
-            return Err(e);
-        }
-    };
     let mut prefetches = Vec::with_capacity(128);
-    for file in prefetch_files {
-        let file_name = match file {
-            VDirEntry::File(v) => v,
-            _ => continue,
-        };
-        if !file_name.ends_with(".pf") {
-            continue;
-        }
-        let file = fs.open(prefetch_folder.join(&file_name).as_path())?;
-
-        match read_prefetch_file(&file_name, file) {
+    for scanned in scan_prefetch_folder(fs)? {
+        match scanned.result {
             Ok(v) => {
                 prefetches.push(v);
             }
             Err(e) => {
-                forensic_rs::info!("Error procesing prefetch {}: {}", file_name, e);
+                forensic_rs::info!("Error procesing prefetch {}: {}", scanned.file_name, e);
             }
         };
     }
@@ -73,19 +63,114 @@ pub fn read_prefetch_form_fs(fs: &mut impl VirtualFileSystem) -> ForensicResult<
 /// ```
 pub fn read_prefetch_file(
     artifact_name: &str,
-    mut file: Box<dyn VirtualFile>,
+    file: Box<dyn VirtualFile>,
 ) -> ForensicResult<PrefetchFile> {
-    let mut buffer = [0u8; 64];
-    file.read_exact(&mut buffer)?;
-    if file_is_compressed(&buffer) {
-        read_prefetch_file_compressed(artifact_name, file)
+    Ok(read_prefetch(artifact_name, file, false)?)
+}
+
+/// Parses a single prefetch file and records where each value is located inside the (decompressed) file.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::prefetch::read_prefetch_file_with_provenance;
+/// let mut fs = ChRootFileSystem::new("./artifacts/17", Box::new(StdVirtualFS::new()));
+/// let file = fs.open(std::path::Path::new("C:\\Windows\\Prefetch\\CMD.EXE-087B4001.pf")).unwrap();
+/// let prefetch = read_prefetch_file_with_provenance("CMD.EXE-087B4001.pf", file).unwrap();
+/// let provenance = prefetch.provenance.unwrap();
+/// assert_eq!(prefetch.metrics.len(), provenance.metrics.len());
+/// ```
+pub fn read_prefetch_file_with_provenance(
+    artifact_name: &str,
+    file: Box<dyn VirtualFile>,
+) -> ForensicResult<PrefetchFile> {
+    Ok(read_prefetch(artifact_name, file, true)?)
+}
+
+/// Parses a prefetch file keeping the stage where the parsing failed
+pub(crate) fn read_prefetch(
+    artifact_name: &str,
+    file: Box<dyn VirtualFile>,
+    with_provenance: bool,
+) -> PrefetchParseResult<PrefetchFile> {
+    let file_times = file
+        .metadata()
+        .ok()
+        .map(|v| PrefetchFileTimes::from_metadata(&v));
+    let buffer = read_file_content(artifact_name, file).in_stage(ParseStage::Read)?;
+    let mut prefetch = process_file_data(artifact_name, &buffer, with_provenance)?;
+    prefetch.file_times = file_times;
+    Ok(prefetch)
+}
+
+/// Parses a prefetch file already loaded in memory, compressed or not.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::prefetch::read_prefetch_bytes;
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let buffer = fs.read_all(Path::new("./artifacts/30/C/Windows/Prefetch/RUST_OUT.EXE-5D2C8541.pf")).unwrap();
+/// let prefetch = read_prefetch_bytes("RUST_OUT.EXE-5D2C8541.pf", &buffer).unwrap();
+/// assert_eq!("RUST_OUT.EXE", prefetch.name);
+/// ```
+pub fn read_prefetch_bytes(artifact_name: &str, buffer: &[u8]) -> ForensicResult<PrefetchFile> {
+    Ok(read_prefetch_bytes_staged(artifact_name, buffer)?)
+}
+
+/// Same as [`read_prefetch_bytes`] keeping the stage where the parsing failed
+pub(crate) fn read_prefetch_bytes_staged(
+    artifact_name: &str,
+    buffer: &[u8],
+) -> PrefetchParseResult<PrefetchFile> {
+    if buffer.len() as u64 > PREFETCH_SIZE_LIMIT {
+        return Err(PrefetchParseError::new(
+            ParseStage::Read,
+            size_limit_error(artifact_name),
+        ));
+    }
+    process_file_data(artifact_name, buffer, false)
+}
+
+fn process_file_data(
+    artifact_name: &str,
+    buffer: &[u8],
+    with_provenance: bool,
+) -> PrefetchParseResult<PrefetchFile> {
+    if file_is_compressed(buffer) {
+        process_compressed_data(artifact_name, buffer, with_provenance)
     } else {
-        read_prefetch_file_no_compressed(artifact_name, file)
+        process_prefetch_data(artifact_name, buffer, with_provenance)
     }
 }
 
+/// Reads the full content of a prefetch file, refusing abnormally large files.
+pub(crate) fn read_file_content(
+    artifact_name: &str,
+    mut file: Box<dyn VirtualFile>,
+) -> ForensicResult<Vec<u8>> {
+    file.seek(std::io::SeekFrom::Start(0))?;
+    if file.metadata()?.size > PREFETCH_SIZE_LIMIT {
+        return Err(size_limit_error(artifact_name));
+    }
+    let mut buffer = Vec::with_capacity(4096);
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn size_limit_error(artifact_name: &str) -> ForensicError {
+    notify_low!(
+        NotificationType::AntiForensicsDetected,
+        "Prefetch file {} size is abnormally large",
+        artifact_name
+    );
+    ForensicError::bad_format_string(format!(
+        "Prefetch file {} size is abnormally large",
+        artifact_name
+    ))
+}
+
 fn file_is_compressed(buffer: &[u8]) -> bool {
-    PREFETC_COMPRESS_SIGNATURE_U8 == &buffer[0..3]
+    buffer.starts_with(PREFETC_COMPRESS_SIGNATURE_U8)
 }
 
 /// Parsers a prefetch file that is compressed.
@@ -100,34 +185,43 @@ fn file_is_compressed(buffer: &[u8]) -> bool {
 /// ```
 pub fn read_prefetch_file_compressed(
     artifact_name: &str,
-    mut file: Box<dyn VirtualFile>,
+    file: Box<dyn VirtualFile>,
 ) -> ForensicResult<PrefetchFile> {
-    file.seek(std::io::SeekFrom::Start(0))?;
-    if file.metadata()?.size > PREFETCH_SIZE_LIMIT {
-        notify_low!(
-            NotificationType::AntiForensicsDetected,
-            "File size is abnormally large"
-        );
-        return Err(ForensicError::bad_format_str(
-            "File size is abnormally large",
+    let buffer = read_file_content(artifact_name, file)?;
+    Ok(process_compressed_data(artifact_name, &buffer, false)?)
+}
+
+fn process_compressed_data(
+    artifact_name: &str,
+    buffer: &[u8],
+    with_provenance: bool,
+) -> PrefetchParseResult<PrefetchFile> {
+    if buffer.len() < 8 {
+        return Err(PrefetchParseError::new(
+            ParseStage::Decompression,
+            ForensicError::bad_format_str("The compressed prefetch is smaller than its header"),
         ));
     }
-    let mut buffer = Vec::with_capacity(4096);
-    file.read_to_end(&mut buffer)?;
     let header = &buffer[0..8];
-    let compressed = &buffer[8..];
+    let mut compressed = &buffer[8..];
     let signature = u32_at_pos(header, 0);
     let decompressed_size = u32_at_pos(header, 4);
     let compress_algorithm: CompressionAlgorithm = ((signature & 0x0F000000) >> 24).into();
     let crc_ck = (signature & 0xF0000000) >> 28;
     let magic = signature & 0x00FFFFFF;
     if magic != PREFETCH_COMPRESS_SIGNATURE {
-        return Err(ForensicError::bad_format_string(format!(
-            "Invalid prefetch signature: {}",
-            magic
-        )));
+        return Err(PrefetchParseError::new(
+            ParseStage::Decompression,
+            ForensicError::bad_format_string(format!("Invalid prefetch signature: {}", magic)),
+        ));
     }
     if crc_ck > 0 {
+        if compressed.len() < 4 {
+            return Err(PrefetchParseError::new(
+                ParseStage::Crc,
+                ForensicError::bad_format_str("The compressed prefetch is smaller than its header"),
+            ));
+        }
         let file_crc = u32_at_pos(compressed, 0);
         let mut hash = crc32fast::Hasher::new();
         hash.update(header);
@@ -142,14 +236,62 @@ pub fn read_prefetch_file_compressed(
                 file_crc,
                 crc32
             );
-            return Err(ForensicError::bad_format_str(
-                "The CRC of the prefetch does not match",
+            return Err(PrefetchParseError::new(
+                ParseStage::Crc,
+                ForensicError::bad_format_str("The CRC of the prefetch does not match"),
             ));
         }
+        compressed = &compressed[4..];
     }
     let mut decompressed = Vec::with_capacity(decompressed_size as usize);
-    decompress(compressed, &mut decompressed, compress_algorithm)?;
-    process_prefetch_data(artifact_name, &decompressed)
+    if !with_provenance {
+        decompress(compressed, &mut decompressed, compress_algorithm)
+            .in_stage(ParseStage::Decompression)?;
+        return process_prefetch_data(artifact_name, &decompressed, false);
+    }
+    let stream_offset = buffer.len() - compressed.len();
+    let mut chunks = decompress_with_chunks(compressed, &mut decompressed, compress_algorithm)
+        .in_stage(ParseStage::Decompression)?;
+    for chunk in chunks.iter_mut() {
+        chunk.compressed_offset += stream_offset;
+    }
+    let mut prefetch = process_prefetch_data(artifact_name, &decompressed, true)?;
+    if let Some(provenance) = prefetch.provenance.as_mut() {
+        provenance.compression = Some(CompressionMap { chunks });
+    }
+    Ok(prefetch)
+}
+
+/// Packs a decompressed prefetch into a MAM container compressed with Xpress Huffman, as Windows 10/11 does.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::prelude::*;
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let decompressed = fs.read_all(Path::new("./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf")).unwrap();
+/// let compressed = write_mam(&decompressed, true);
+/// assert_eq!(b"MAM", &compressed[0..3]);
+/// ```
+pub fn write_mam(decompressed: &[u8], with_crc: bool) -> Vec<u8> {
+    let compressed = xpress_huff_compress::compress(decompressed);
+    let mut signature = PREFETCH_COMPRESS_SIGNATURE
+        | ((CompressionAlgorithm::CompressionFormatXpressHuff as u32) << 24);
+    if with_crc {
+        signature |= PREFETCH_COMPRESS_CRC_FLAG;
+    }
+    let mut buffer = Vec::with_capacity(compressed.len() + 12);
+    buffer.extend_from_slice(&signature.to_le_bytes());
+    buffer.extend_from_slice(&(decompressed.len() as u32).to_le_bytes());
+    if with_crc {
+        let mut hash = crc32fast::Hasher::new();
+        hash.update(&buffer);
+        hash.update(&[0, 0, 0, 0]);
+        hash.update(&compressed);
+        buffer.extend_from_slice(&hash.finalize().to_le_bytes());
+    }
+    buffer.extend_from_slice(&compressed);
+    buffer
 }
 
 /// Parsers a prefetch file that is not compressed.
@@ -164,201 +306,84 @@ pub fn read_prefetch_file_compressed(
 /// ```
 pub fn read_prefetch_file_no_compressed(
     artifact_name: &str,
-    mut file: Box<dyn VirtualFile>,
+    file: Box<dyn VirtualFile>,
 ) -> ForensicResult<PrefetchFile> {
-    file.seek(std::io::SeekFrom::Start(0))?;
-    if file.metadata()?.size > PREFETCH_SIZE_LIMIT {
-        notify_low!(
-            NotificationType::AntiForensicsDetected,
-            "Prefetch file {} size is abnormally large",
-            artifact_name
-        );
-        return Err(ForensicError::bad_format_string(format!(
-            "Prefetch file {} size is abnormally large",
-            artifact_name
-        )));
-    }
-    let mut buffer = Vec::with_capacity(4096);
-    file.read_to_end(&mut buffer)?;
-    process_prefetch_data(artifact_name, &buffer)
+    let buffer = read_file_content(artifact_name, file)?;
+    Ok(process_prefetch_data(artifact_name, &buffer, false)?)
 }
 
-fn process_prefetch_data(artifact_name: &str, buffer: &[u8]) -> ForensicResult<PrefetchFile> {
-    let version = u32::from_le_bytes(buffer[0..4].try_into().unwrap());
-    let signature = &buffer[4..8];
-    if b"SCCA" != signature {
-        return Err(ForensicError::bad_format_str("Invalid prefetch signature"));
+/// Parses a decompressed prefetch. The provenance mode records the position of each value in the buffer.
+fn process_prefetch_data(
+    artifact_name: &str,
+    buffer: &[u8],
+    with_provenance: bool,
+) -> PrefetchParseResult<PrefetchFile> {
+    if buffer.len() < 84 {
+        return Err(PrefetchParseError::new(
+            ParseStage::Header,
+            ForensicError::bad_format_str("The prefetch is smaller than its header"),
+        ));
     }
-    //let file_size = u32::from_le_bytes(buffer[12..16].try_into().unwrap());
-    let name_buffer = &buffer[16..76];
-    let name_buffer: &[u16] = unsafe { std::mem::transmute(name_buffer) };
-    let end = name_buffer
-        .iter()
-        .position(|&v| v == 0)
-        .unwrap_or(name_buffer.len());
-    let executable_name = String::from_utf16_lossy(&name_buffer[0..end]);
-    let raw_hash = u32::from_le_bytes(buffer[76..80].try_into().unwrap());
-    check_prefetch_info_correct(artifact_name, &executable_name, raw_hash);
+    let header = prefetch_header(buffer);
+    if b"SCCA" != &header.signature {
+        return Err(PrefetchParseError::new(
+            ParseStage::Header,
+            ForensicError::bad_format_str("Invalid prefetch signature"),
+        ));
+    }
+    let version = header.version;
+    if header.file_size as usize != buffer.len() {
+        forensic_rs::info!(
+            "The prefetch {} declares a size of {} bytes but has {}",
+            artifact_name,
+            header.file_size,
+            buffer.len()
+        );
+    }
+    check_prefetch_info_correct(artifact_name, &header.executable_name, header.hash);
 
     let mut prefetch_content = PrefetchFile {
-        name: executable_name,
+        file_name: artifact_name
+            .rsplit(['\\', '/'])
+            .next()
+            .unwrap_or(artifact_name)
+            .to_string(),
+        name: header.executable_name.clone(),
+        hash: header.hash,
         version,
         ..Default::default()
     };
-    if version == 17 {
-        let info = file_information_17(&buffer[84..])?;
-        prefetch_content.metrics = metrics_array_17(buffer, &info)?;
-        prefetch_content.volume = volume_info_17(buffer, &info)?;
-        prefetch_content.last_run_times = info.last_run_times;
-        prefetch_content.run_count = info.run_count;
-    } else if version == 23 {
-        let info = file_information_23(&buffer[84..])?;
-        prefetch_content.metrics = metrics_array_23(buffer, &info)?;
-        prefetch_content.volume = volume_info_23(buffer, &info)?;
-        prefetch_content.last_run_times = info.last_run_times;
-        prefetch_content.run_count = info.run_count;
-    } else if version == 26 {
-        let info = file_information_26(&buffer[84..])?;
-        prefetch_content.metrics = metrics_array_26(buffer, &info)?;
-        prefetch_content.volume = volume_info_26(buffer, &info)?;
-        prefetch_content.last_run_times = info.last_run_times;
-        prefetch_content.run_count = info.run_count;
-    } else if version == 30 {
-        let info = file_information_30(&buffer[84..])?;
-        prefetch_content.metrics = metrics_array_30(buffer, &info)?;
-        prefetch_content.volume = volume_info_30(buffer, &info)?;
-        prefetch_content.last_run_times = info.last_run_times;
-        prefetch_content.run_count = info.run_count;
-    } else if version == 31 {
-        let info = file_information_30(&buffer[84..])?;
-        prefetch_content.metrics = metrics_array_30(buffer, &info)?;
-        prefetch_content.volume = volume_info_30(buffer, &info)?;
-        prefetch_content.last_run_times = info.last_run_times;
-        prefetch_content.run_count = info.run_count;
-    }else {
-        notify_low!(
-            NotificationType::Informational,
-            "The prefetch version is unknown: {}",
-            version
-        );
-        return Err(ForensicError::bad_format_string(format!(
-            "The prefetch version is unknown: {}",
-            version
-        )));
-    };
-    Ok(prefetch_content)
-}
-
-fn file_information_17(buffer: &[u8]) -> ForensicResult<PrefetchFileInformation> {
-    Ok(PrefetchFileInformation {
-        metrics_offsets: u32_at_pos(buffer, 0),
-        metrics_count: u32_at_pos(buffer, 4),
-        trace_chain_offset: u32_at_pos(buffer, 8),
-        trace_chain_count: u32_at_pos(buffer, 12),
-        filename_string_offset: u32_at_pos(buffer, 16),
-        filename_string_size: u32_at_pos(buffer, 20),
-        volume_information_offset: u32_at_pos(buffer, 24),
-        volume_count: u32_at_pos(buffer, 28),
-        volume_information_size: u32_at_pos(buffer, 32),
-        last_run_times: vec![Filetime::new(u64_at_pos(buffer, 36))],
-        run_count: u32_at_pos(buffer, 60),
-    })
-}
-
-fn file_information_23(buffer: &[u8]) -> ForensicResult<PrefetchFileInformation> {
-    Ok(PrefetchFileInformation {
-        metrics_offsets: u32_at_pos(buffer, 0),
-        metrics_count: u32_at_pos(buffer, 4),
-        trace_chain_offset: u32_at_pos(buffer, 8),
-        trace_chain_count: u32_at_pos(buffer, 12),
-        filename_string_offset: u32_at_pos(buffer, 16),
-        filename_string_size: u32_at_pos(buffer, 20),
-        volume_information_offset: u32_at_pos(buffer, 24),
-        volume_count: u32_at_pos(buffer, 28),
-        volume_information_size: u32_at_pos(buffer, 32),
-        last_run_times: vec![Filetime::new(u64_at_pos(buffer, 44))],
-        run_count: u32_at_pos(buffer, 68),
-    })
-}
-
-fn file_information_26(buffer: &[u8]) -> ForensicResult<PrefetchFileInformation> {
-    let mut last_run_times = Vec::with_capacity(8);
-    for i in (44..108).step_by(8) {
-        let run_time = u64_at_pos(buffer, i);
-        if run_time == 0 {
-            continue;
-        }
-        last_run_times.push(Filetime::new(run_time));
-    }
-    Ok(PrefetchFileInformation {
-        metrics_offsets: u32_at_pos(buffer, 0),
-        metrics_count: u32_at_pos(buffer, 4),
-        trace_chain_offset: u32_at_pos(buffer, 8),
-        trace_chain_count: u32_at_pos(buffer, 12),
-        filename_string_offset: u32_at_pos(buffer, 16),
-        filename_string_size: u32_at_pos(buffer, 20),
-        volume_information_offset: u32_at_pos(buffer, 24),
-        volume_count: u32_at_pos(buffer, 28),
-        volume_information_size: u32_at_pos(buffer, 32),
-        last_run_times,
-        run_count: u32_at_pos(buffer, 124),
-    })
-}
-
-fn file_information_30v1(buffer: &[u8]) -> ForensicResult<PrefetchFileInformation> {
-    let mut last_run_times = Vec::with_capacity(8);
-    for i in (44..108).step_by(8) {
-        let run_time = u64_at_pos(buffer, i);
-        if run_time == 0 {
-            continue;
-        }
-        last_run_times.push(Filetime::new(run_time));
-    }
-    Ok(PrefetchFileInformation {
-        metrics_offsets: u32_at_pos(buffer, 0),
-        metrics_count: u32_at_pos(buffer, 4),
-        trace_chain_offset: u32_at_pos(buffer, 8),
-        trace_chain_count: u32_at_pos(buffer, 12),
-        filename_string_offset: u32_at_pos(buffer, 16),
-        filename_string_size: u32_at_pos(buffer, 20),
-        volume_information_offset: u32_at_pos(buffer, 24),
-        volume_count: u32_at_pos(buffer, 28),
-        volume_information_size: u32_at_pos(buffer, 32),
-        last_run_times,
-        run_count: u32_at_pos(buffer, 124),
-    })
-}
-
-fn file_information_30v2(buffer: &[u8]) -> ForensicResult<PrefetchFileInformation> {
-    let mut last_run_times = Vec::with_capacity(8);
-    for i in (44..108).step_by(8) {
-        let run_time = u64_at_pos(buffer, i);
-        if run_time == 0 {
-            continue;
-        }
-        last_run_times.push(Filetime::new(run_time));
+    let view = PrefetchView::new_staged(buffer)?;
+    let info = view.to_file_information();
+    prefetch_content.metrics = view.to_metrics().in_stage(ParseStage::Metrics)?;
+    prefetch_content
+        .metrics
+        .iter()
+        .for_each(check_anomaly_in_metrics);
+    prefetch_content.volume = view.to_volumes().in_stage(ParseStage::Volumes)?;
+    prefetch_content.last_run_times = info.last_run_times.clone();
+    prefetch_content.run_count = info.run_count;
+    prefetch_content.header = header;
+    prefetch_content.file_information = info;
+    if with_provenance {
+        prefetch_content.provenance =
+            Some(collect_provenance(buffer, &prefetch_content).in_stage(ParseStage::Provenance)?);
     }
-    Ok(PrefetchFileInformation {
-        metrics_offsets: u32_at_pos(buffer, 0),
-        metrics_count: u32_at_pos(buffer, 4),
-        trace_chain_offset: u32_at_pos(buffer, 8),
-        trace_chain_count: u32_at_pos(buffer, 12),
-        filename_string_offset: u32_at_pos(buffer, 16),
-        filename_string_size: u32_at_pos(buffer, 20),
-        volume_information_offset: u32_at_pos(buffer, 24),
-        volume_count: u32_at_pos(buffer, 28),
-        volume_information_size: u32_at_pos(buffer, 32),
-        last_run_times,
-        run_count: u32_at_pos(buffer, 116),
-    })
+    prefetch_content.executable_index = prefetch_content.resolve_executable().index();
+    prefetch_content.hash_check = Some(check_prefetch_path_hash(&prefetch_content));
+    Ok(prefetch_content)
 }
 
-fn file_information_30(buffer: &[u8]) -> ForensicResult<PrefetchFileInformation> {
-    let metrics_offsets = u32::from_le_bytes(buffer[0..4].try_into().unwrap());
-    if metrics_offsets == 304 {
-        return file_information_30v1(buffer);
+fn prefetch_header(buffer: &[u8]) -> PrefetchHeader {
+    PrefetchHeader {
+        version: u32_at_pos(buffer, 0),
+        signature: buffer[4..8].try_into().unwrap_or_default(),
+        unknown_8: u32_at_pos(buffer, 8),
+        file_size: u32_at_pos(buffer, 12),
+        executable_name: utf16_from_bytes(&buffer[16..76]),
+        hash: u32_at_pos(buffer, 76),
+        flags: u32_at_pos(buffer, 80),
     }
-    file_information_30v2(buffer)
 }
 
 fn check_prefetch_info_correct(artifact_name: &str, executable_name: &str, hash: u32) {
@@ -386,11 +411,30 @@ fn check_prefetch_info_correct(artifact_name: &str, executable_name: &str, hash:
     }
 }
 
+fn check_prefetch_path_hash(prefetch: &PrefetchFile) -> PrefetchHashCheck {
+    let check = prefetch.verify_hash();
+    if check.is_mismatch() {
+        let executable_path = prefetch.executable_path();
+        forensic_rs::info!(
+            "The prefetch hash {:08X} was not generated by the executable path {}",
+            check.stored_hash,
+            executable_path
+        );
+        forensic_rs::notify_info!(
+            NotificationType::AntiForensicsDetected,
+            "The prefetch hash {:08X} was not generated by the executable path {}",
+            check.stored_hash,
+            executable_path
+        );
+    }
+    check
+}
+
 fn extract_hash_ands_signature(mut name: &str) -> ForensicResult<(&str, u32)> {
     if name.ends_with(".pf") {
         name = &name[0..name.len() - 3]
     }
-    name.split_once('-')
-        .map(|v| (v.0, v.1.parse::<u32>().unwrap_or_default()))
+    name.rsplit_once('-')
+        .map(|v| (v.0, u32::from_str_radix(v.1, 16).unwrap_or_default()))
         .ok_or_else(|| ForensicError::bad_format_str("Invalid prefetch artifact name"))
 }
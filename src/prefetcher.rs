@@ -0,0 +1,168 @@
+use forensic_rs::{
+    err::ForensicResult,
+    traits::registry::{RegHiveKey, RegValue, RegistryReader},
+};
+
+/// Key of the SYSTEM hive with the `EnablePrefetcher` value
+pub const PREFETCH_PARAMETERS_KEY: &str =
+    r"SYSTEM\CurrentControlSet\Control\Session Manager\Memory Management\PrefetchParameters";
+/// SysMain (Superfetch) service, which writes the prefetch files since Windows Vista
+pub const SYSMAIN_KEY: &str = r"SYSTEM\CurrentControlSet\Services\SysMain";
+
+/// Value of `EnablePrefetcher`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnablePrefetcher {
+    Disabled,
+    ApplicationLaunch,
+    BootOnly,
+    /// Application launch and boot. Default on workstations
+    Enabled,
+    Other(u32),
+}
+
+impl From<u32> for EnablePrefetcher {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Disabled,
+            1 => Self::ApplicationLaunch,
+            2 => Self::BootOnly,
+            3 => Self::Enabled,
+            v => Self::Other(v),
+        }
+    }
+}
+
+impl EnablePrefetcher {
+    /// The prefetcher creates `.pf` files for the executed programs
+    pub fn prefetches_applications(&self) -> bool {
+        matches!(self, Self::ApplicationLaunch | Self::Enabled)
+    }
+}
+
+/// `Start` value of a service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceStart {
+    Boot,
+    System,
+    Automatic,
+    Manual,
+    Disabled,
+    Other(u32),
+}
+
+impl From<u32> for ServiceStart {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Boot,
+            1 => Self::System,
+            2 => Self::Automatic,
+            3 => Self::Manual,
+            4 => Self::Disabled,
+            v => Self::Other(v),
+        }
+    }
+}
+
+/// Why the prefetch folder is missing or empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingPrefetchReason {
+    /// `EnablePrefetcher` does not prefetch applications or the SysMain service is disabled. Ex: servers and some SSD systems
+    DisabledByConfiguration,
+    /// Prefetching is enabled, so the folder or its files were probably deleted
+    LikelyWiped,
+    /// The `EnablePrefetcher` value is not in the registry
+    Unknown,
+}
+
+/// Prefetch configuration from the SYSTEM hive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrefetcherConfiguration {
+    pub enable_prefetcher: Option<EnablePrefetcher>,
+    /// None on Windows XP and 2003, which have no SysMain service
+    pub sysmain_start: Option<ServiceStart>,
+}
+
+impl PrefetcherConfiguration {
+    /// Reads `EnablePrefetcher` and the start type of SysMain. Missing keys or values are left as None.
+    ///
+    /// ```rust
+    /// use forensic_rs::{prelude::*, utils::testing::TestingRegistry};
+    /// use frnsc_prefetch::prefetcher::*;
+    /// let mut registry = TestingRegistry::new();
+    /// registry.add_value(&format!(r"HKLM\{}", PREFETCH_PARAMETERS_KEY), "EnablePrefetcher", RegValue::DWord(0));
+    /// let configuration = PrefetcherConfiguration::from_registry(&registry).unwrap();
+    /// assert_eq!(Some(EnablePrefetcher::Disabled), configuration.enable_prefetcher);
+    /// assert_eq!(MissingPrefetchReason::DisabledByConfiguration, configuration.missing_prefetch_reason());
+    /// ```
+    pub fn from_registry(registry: &dyn RegistryReader) -> ForensicResult<Self> {
+        Ok(Self {
+            enable_prefetcher: read_dword(registry, PREFETCH_PARAMETERS_KEY, "EnablePrefetcher")
+                .map(EnablePrefetcher::from),
+            sysmain_start: read_dword(registry, SYSMAIN_KEY, "Start").map(ServiceStart::from),
+        })
+    }
+
+    /// The system is configured not to create prefetch files for the executed programs
+    pub fn is_disabled(&self) -> bool {
+        let prefetcher_disabled = self
+            .enable_prefetcher
+            .map(|v| !v.prefetches_applications())
+            .unwrap_or(false);
+        prefetcher_disabled || self.sysmain_start == Some(ServiceStart::Disabled)
+    }
+
+    pub fn missing_prefetch_reason(&self) -> MissingPrefetchReason {
+        if self.is_disabled() {
+            MissingPrefetchReason::DisabledByConfiguration
+        } else if self.enable_prefetcher.is_some() {
+            MissingPrefetchReason::LikelyWiped
+        } else {
+            MissingPrefetchReason::Unknown
+        }
+    }
+}
+
+fn read_dword(registry: &dyn RegistryReader, key_path: &str, value: &str) -> Option<u32> {
+    let key = registry
+        .open_key(RegHiveKey::HkeyLocalMachine, key_path)
+        .ok()?;
+    let value = match registry.read_value(key, value) {
+        Ok(RegValue::DWord(v)) => Some(v),
+        _ => None,
+    };
+    registry.close_key(key);
+    value
+}
+
+#[test]
+fn should_explain_missing_prefetch() {
+    use forensic_rs::utils::testing::TestingRegistry;
+    let mut registry = TestingRegistry::new();
+    assert_eq!(
+        MissingPrefetchReason::Unknown,
+        PrefetcherConfiguration::from_registry(&registry)
+            .unwrap()
+            .missing_prefetch_reason()
+    );
+    let prefetch_parameters = format!(r"HKLM\{}", PREFETCH_PARAMETERS_KEY);
+    let sysmain = format!(r"HKLM\{}", SYSMAIN_KEY);
+    registry.add_value(&prefetch_parameters, "EnablePrefetcher", RegValue::DWord(3));
+    registry.add_value(&sysmain, "Start", RegValue::DWord(2));
+    let configuration = PrefetcherConfiguration::from_registry(&registry).unwrap();
+    assert_eq!(Some(ServiceStart::Automatic), configuration.sysmain_start);
+    assert_eq!(
+        MissingPrefetchReason::LikelyWiped,
+        configuration.missing_prefetch_reason()
+    );
+    registry.add_value(&sysmain, "Start", RegValue::DWord(4));
+    let configuration = PrefetcherConfiguration::from_registry(&registry).unwrap();
+    assert_eq!(
+        MissingPrefetchReason::DisabledByConfiguration,
+        configuration.missing_prefetch_reason()
+    );
+    registry.add_value(&sysmain, "Start", RegValue::DWord(2));
+    registry.add_value(&prefetch_parameters, "EnablePrefetcher", RegValue::DWord(2));
+    assert!(PrefetcherConfiguration::from_registry(&registry)
+        .unwrap()
+        .is_disabled());
+}
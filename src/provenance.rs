@@ -0,0 +1,224 @@
+use forensic_rs::err::{ForensicError, ForensicResult};
+
+use crate::{
+    common::{u16_at_pos, u32_at_pos, PrefetchFile},
+    decompress::CompressedChunk,
+    view::VersionLayout,
+};
+
+/// Position of a value inside the decompressed prefetch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl ByteRange {
+    pub fn new(offset: usize, length: usize) -> Self {
+        Self { offset, length }
+    }
+    /// Offset of the first byte after the range
+    pub fn end(&self) -> usize {
+        self.offset + self.length
+    }
+}
+
+/// Where each value of a `Metric` comes from
+#[derive(Debug, Clone, Default)]
+pub struct MetricProvenance {
+    /// Metrics array entry
+    pub entry: ByteRange,
+    /// UTF-16 file name, without the NUL terminator
+    pub file_name: ByteRange,
+    /// Trace chain entry of each trace, in the same order as `Metric::traces`
+    pub traces: Vec<ByteRange>,
+}
+
+/// Where each value of a `VolumeInformation` comes from
+#[derive(Debug, Clone, Default)]
+pub struct VolumeProvenance {
+    /// Volume information entry
+    pub entry: ByteRange,
+    /// UTF-16 device path, without the NUL terminator
+    pub device_path: ByteRange,
+    pub creation_time: ByteRange,
+    pub serial_number: ByteRange,
+    /// File references block, including its header
+    pub file_references: ByteRange,
+    /// UTF-16 text of each directory string, without the length and the NUL terminator
+    pub directory_strings: Vec<ByteRange>,
+}
+
+/// Compressed chunks of a MAM file. Offsets are relative to the start of the compressed file.
+#[derive(Debug, Clone, Default)]
+pub struct CompressionMap {
+    pub chunks: Vec<CompressedChunk>,
+}
+
+/// Offset and length in the decompressed prefetch of every parsed field.
+#[derive(Debug, Clone, Default)]
+pub struct PrefetchProvenance {
+    pub header: ByteRange,
+    pub executable_name: ByteRange,
+    pub hash: ByteRange,
+    pub file_information: ByteRange,
+    /// Slot of each last run time, in the same order as `PrefetchFile::last_run_times`
+    pub last_run_times: Vec<ByteRange>,
+    pub run_count: ByteRange,
+    /// Same order as `PrefetchFile::metrics`
+    pub metrics: Vec<MetricProvenance>,
+    /// Same order as `PrefetchFile::volume`
+    pub volumes: Vec<VolumeProvenance>,
+    /// Present when the prefetch was read from a MAM compressed file
+    pub compression: Option<CompressionMap>,
+}
+
+impl PrefetchProvenance {
+    /// Maps a range of the decompressed prefetch to the bytes of the file that contain it.
+    /// Uncompressed files are returned as is. For compressed files the result is the range of the
+    /// compressed chunks that produce the value, as bytes of a compressed chunk cannot be tied to a single output byte.
+    /// Returns None if the compression algorithm does not allow the mapping.
+    ///
+    /// ```rust
+    /// use forensic_rs::prelude::*;
+    /// use frnsc_prefetch::prefetch::read_prefetch_file_with_provenance;
+    /// use std::path::Path;
+    /// let mut fs = StdVirtualFS::new();
+    /// let file = fs.open(Path::new("./artifacts/30/C/Windows/Prefetch/RUST_OUT.EXE-5D2C8541.pf")).unwrap();
+    /// let prefetch = read_prefetch_file_with_provenance("RUST_OUT.EXE-5D2C8541.pf", file).unwrap();
+    /// let provenance = prefetch.provenance.as_ref().unwrap();
+    /// let compressed = provenance.compressed_range(&provenance.run_count).unwrap();
+    /// assert!(compressed.offset >= 8);
+    /// ```
+    pub fn compressed_range(&self, range: &ByteRange) -> Option<ByteRange> {
+        let compression = match &self.compression {
+            Some(v) => v,
+            None => return Some(*range),
+        };
+        let mut chunks = compression.chunks.iter().filter(|chunk| {
+            chunk.decompressed_offset < range.end().max(range.offset + 1)
+                && range.offset < chunk.decompressed_offset + chunk.decompressed_size
+        });
+        let first = chunks.next()?;
+        let last = chunks.next_back().unwrap_or(first);
+        Some(ByteRange::new(
+            first.compressed_offset,
+            last.compressed_offset + last.compressed_size - first.compressed_offset,
+        ))
+    }
+}
+
+/// Locates every parsed field of the prefetch inside the decompressed buffer it was parsed from.
+pub(crate) fn collect_provenance(
+    buffer: &[u8],
+    prefetch: &PrefetchFile,
+) -> ForensicResult<PrefetchProvenance> {
+    let layout = VersionLayout::new(prefetch.version, prefetch.file_information.size as usize)?;
+    let info = &prefetch.file_information;
+    let info_offset = 84;
+    let last_run_times = info
+        .last_run_time_slots
+        .iter()
+        .enumerate()
+        .filter(|(_, &slot)| slot != 0 || info.last_run_time_slots.len() == 1)
+        .map(|(i, _)| ByteRange::new(info_offset + layout.last_run_time_offset + i * 8, 8))
+        .collect();
+    Ok(PrefetchProvenance {
+        header: ByteRange::new(0, 84),
+        executable_name: ByteRange::new(
+            16,
+            prefetch.header.executable_name.encode_utf16().count() * 2,
+        ),
+        hash: ByteRange::new(76, 4),
+        file_information: ByteRange::new(info_offset, info.size as usize),
+        last_run_times,
+        run_count: ByteRange::new(info_offset + layout.run_count_offset, 4),
+        metrics: metrics_provenance(buffer, prefetch, &layout)?,
+        volumes: volumes_provenance(buffer, prefetch, &layout)?,
+        compression: None,
+    })
+}
+
+fn metrics_provenance(
+    buffer: &[u8],
+    prefetch: &PrefetchFile,
+    layout: &VersionLayout,
+) -> ForensicResult<Vec<MetricProvenance>> {
+    let info = &prefetch.file_information;
+    let mut metrics = Vec::with_capacity(prefetch.metrics.len());
+    for (i, metric) in prefetch.metrics.iter().enumerate() {
+        let entry = ByteRange::new(
+            info.metrics_offsets as usize + i * layout.metric_size,
+            layout.metric_size,
+        );
+        let data = checked_slice(buffer, &entry)?;
+        let filename_offset = u32_at_pos(data, layout.metric_filename_offset) as usize;
+        let filename_characters = u32_at_pos(data, layout.metric_filename_offset + 4) as usize;
+        let traces = metric
+            .trace_chain
+            .entries
+            .iter()
+            .map(|v| {
+                ByteRange::new(
+                    info.trace_chain_offset as usize + v.index as usize * layout.trace_size,
+                    layout.trace_size,
+                )
+            })
+            .collect();
+        metrics.push(MetricProvenance {
+            entry,
+            file_name: ByteRange::new(
+                info.filename_string_offset as usize + filename_offset,
+                filename_characters * 2,
+            ),
+            traces,
+        });
+    }
+    Ok(metrics)
+}
+
+fn volumes_provenance(
+    buffer: &[u8],
+    prefetch: &PrefetchFile,
+    layout: &VersionLayout,
+) -> ForensicResult<Vec<VolumeProvenance>> {
+    let volume_offset = prefetch.file_information.volume_information_offset as usize;
+    let mut volumes = Vec::with_capacity(prefetch.volume.len());
+    for (i, volume) in prefetch.volume.iter().enumerate() {
+        let entry = ByteRange::new(volume_offset + i * layout.volume_size, layout.volume_size);
+        let data = checked_slice(buffer, &entry)?;
+        let device_path_offset = u32_at_pos(data, 0) as usize;
+        let device_path_characters = u32_at_pos(data, 4) as usize;
+        let file_references_offset = u32_at_pos(data, 20) as usize;
+        let file_references_size = u32_at_pos(data, 24) as usize;
+        let mut pos = volume_offset + u32_at_pos(data, 28) as usize;
+        let mut directory_strings = Vec::with_capacity(volume.directory_strings.len());
+        for _ in 0..volume.directory_strings.len() {
+            let characters =
+                u16_at_pos(checked_slice(buffer, &ByteRange::new(pos, 2))?, 0) as usize;
+            directory_strings.push(ByteRange::new(pos + 2, characters * 2));
+            pos += 4 + characters * 2;
+        }
+        volumes.push(VolumeProvenance {
+            device_path: ByteRange::new(
+                volume_offset + device_path_offset,
+                device_path_characters * 2,
+            ),
+            creation_time: ByteRange::new(entry.offset + 8, 8),
+            serial_number: ByteRange::new(entry.offset + 16, 4),
+            file_references: ByteRange::new(
+                volume_offset + file_references_offset,
+                file_references_size,
+            ),
+            directory_strings,
+            entry,
+        });
+    }
+    Ok(volumes)
+}
+
+fn checked_slice<'a>(buffer: &'a [u8], range: &ByteRange) -> ForensicResult<&'a [u8]> {
+    buffer.get(range.offset..range.end()).ok_or_else(|| {
+        ForensicError::bad_format_str("The field position is greater than the file buffer")
+    })
+}
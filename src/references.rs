@@ -0,0 +1,162 @@
+use crate::common::{Metric, NtfsFile, PrefetchFile, VolumeInformation};
+
+/// NTFS reference of a loaded file with the directory that contains it
+#[derive(Debug, Clone)]
+pub struct MetricReference<'a> {
+    /// Position of the metric in `PrefetchFile::metrics`
+    pub index: usize,
+    pub metric: &'a Metric,
+    /// Reference stored in the metric entry. Version 17 does not store it.
+    pub reference: Option<NtfsFile>,
+    /// The reference is also in the file references of the volume
+    pub listed: bool,
+    /// Position in `directory_strings` of the folder that contains the file
+    pub directory: Option<usize>,
+}
+
+/// File references of a volume linked to the metrics and directory strings they belong to
+#[derive(Debug, Clone)]
+pub struct VolumeReferences<'a> {
+    pub volume: &'a VolumeInformation,
+    /// Metrics whose path is inside the volume
+    pub files: Vec<MetricReference<'a>>,
+    /// References of the volume not stored in any metric entry.
+    /// Versions 23 and 26 store here the references of the directories in `directory_strings`.
+    pub unclaimed: Vec<NtfsFile>,
+}
+
+impl VolumeReferences<'_> {
+    /// Metric of the file with the given NTFS reference
+    pub fn metric_of(&self, reference: &NtfsFile) -> Option<&MetricReference<'_>> {
+        self.files
+            .iter()
+            .find(|v| v.reference.as_ref() == Some(reference))
+    }
+}
+
+/// Links the file references of each volume with the metrics of the files inside the volume and with their directories.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::prelude::*;
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf")).unwrap();
+/// let prefetch = read_prefetch_file("NOTEPAD.EXE-D8414F97.pf", file).unwrap();
+/// let volumes = prefetch.volume_references();
+/// let ntdll = &volumes[0].files[0];
+/// assert!(ntdll.metric.file.ends_with(r"\WINDOWS\SYSTEM32\NTDLL.DLL"));
+/// assert_eq!(25654, ntdll.reference.as_ref().unwrap().mft_entry);
+/// assert!(ntdll.listed);
+/// assert_eq!(7, volumes[0].unclaimed.len());
+/// ```
+pub fn correlate_file_references(prefetch: &PrefetchFile) -> Vec<VolumeReferences<'_>> {
+    let mut volumes: Vec<VolumeReferences<'_>> = prefetch
+        .volume
+        .iter()
+        .map(|volume| VolumeReferences {
+            volume,
+            files: Vec::new(),
+            unclaimed: Vec::new(),
+        })
+        .collect();
+    for (index, metric) in prefetch.metrics.iter().enumerate() {
+        let reference = metric.file_reference().cloned();
+        let position = metric
+            .volume_path()
+            .and_then(|v| v.position_in(&prefetch.volume))
+            .or_else(|| {
+                let reference = reference.as_ref()?;
+                volumes
+                    .iter()
+                    .position(|v| v.volume.file_references.contains(reference))
+            });
+        let volume = match position {
+            Some(v) => &mut volumes[v],
+            None => continue,
+        };
+        let listed = reference
+            .as_ref()
+            .map(|v| volume.volume.file_references.contains(v))
+            .unwrap_or(false);
+        let directory = parent_directory(&metric.file).and_then(|parent| {
+            volume
+                .volume
+                .directory_strings
+                .iter()
+                .position(|v| v.trim_end_matches('\\').eq_ignore_ascii_case(parent))
+        });
+        volume.files.push(MetricReference {
+            index,
+            metric,
+            reference,
+            listed,
+            directory,
+        });
+    }
+    for volume in volumes.iter_mut() {
+        volume.unclaimed = volume
+            .volume
+            .file_references
+            .iter()
+            .filter(|&reference| volume.metric_of(reference).is_none())
+            .cloned()
+            .collect();
+    }
+    volumes
+}
+
+fn parent_directory(file: &str) -> Option<&str> {
+    file.rsplit_once('\\').map(|(parent, _)| parent)
+}
+
+#[test]
+fn should_link_metrics_with_volume_and_directory() {
+    let reference = |mft_entry| NtfsFile {
+        mft_entry,
+        seq_number: 1,
+    };
+    let metric = |file: &str, mft_reference| Metric {
+        file: file.into(),
+        mft_reference,
+        ..Default::default()
+    };
+    let prefetch = PrefetchFile {
+        metrics: vec![
+            metric(
+                r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\NTDLL.DLL",
+                Some(reference(10)),
+            ),
+            metric(
+                r"\DEVICE\HARDDISKVOLUME3\TOOLS\PAYLOAD.DLL",
+                Some(reference(20)),
+            ),
+            metric(r"\DEVICE\HARDDISKVOLUME2\$MFT", None),
+        ],
+        volume: vec![
+            VolumeInformation {
+                device_path: r"\DEVICE\HARDDISKVOLUME2".into(),
+                file_references: vec![reference(10), reference(5)],
+                directory_strings: vec![
+                    r"\DEVICE\HARDDISKVOLUME2\WINDOWS".into(),
+                    r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32".into(),
+                ],
+                ..Default::default()
+            },
+            VolumeInformation {
+                device_path: r"\DEVICE\HARDDISKVOLUME3".into(),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let volumes = correlate_file_references(&prefetch);
+    assert_eq!(2, volumes[0].files.len());
+    assert_eq!(Some(1), volumes[0].files[0].directory);
+    assert!(volumes[0].files[0].listed);
+    assert_eq!(None, volumes[0].files[1].reference);
+    assert_eq!(vec![reference(5)], volumes[0].unclaimed);
+    assert_eq!(1, volumes[1].files[0].index);
+    assert!(!volumes[1].files[0].listed);
+    assert_eq!(None, volumes[1].files[0].directory);
+}
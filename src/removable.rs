@@ -0,0 +1,432 @@
+use forensic_rs::{
+    err::ForensicResult,
+    traits::registry::{RegHiveKey, RegValue, RegistryReader},
+};
+
+use crate::{
+    common::{Metric, PrefetchFile, VolumeInformation},
+    volume_path::VolumePath,
+};
+
+/// Key of the SYSTEM hive with the USB mass storage devices connected to the machine
+pub const USBSTOR_KEY: &str = r"SYSTEM\CurrentControlSet\Enum\USBSTOR";
+/// Key of the SOFTWARE hive with the devices mounted through Windows Portable Devices
+pub const PORTABLE_DEVICES_KEY: &str = r"SOFTWARE\Microsoft\Windows Portable Devices\Devices";
+/// Key of the SOFTWARE hive where ReadyBoost stores the label and serial number of the volumes of removable devices
+pub const EMDMGMT_KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\EMDMgmt";
+
+/// Folder whose `SYSTEM32` directory marks the system volume
+pub const DEFAULT_SYSTEM_FOLDER: &str = r"\WINDOWS";
+
+/// USB mass storage device of the USBSTOR key
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsbDevice {
+    pub vendor: String,
+    pub product: String,
+    pub revision: String,
+    /// Serial number of the device. When the second character is `&` the serial was generated by Windows
+    pub serial: String,
+    pub friendly_name: Option<String>,
+}
+
+impl UsbDevice {
+    fn is(&self, id: &UsbStorId) -> bool {
+        self.serial.eq_ignore_ascii_case(&id.serial)
+            && self.vendor.eq_ignore_ascii_case(&id.vendor)
+            && self.product.eq_ignore_ascii_case(&id.product)
+    }
+}
+
+/// Device of the Windows Portable Devices key
+#[derive(Debug, Clone, Default)]
+pub struct PortableDevice {
+    /// Name of the key. Ex: `WPDBUSENUMROOT#UMB#2&37C186B&1&STORAGE#VOLUME#_??_USBSTOR#DISK&VEN_KINGSTON&...`
+    pub key: String,
+    /// Volume label or drive letter shown to the user
+    pub friendly_name: Option<String>,
+}
+
+/// Volume of a removable device registered by ReadyBoost
+#[derive(Debug, Clone, Default)]
+pub struct EmdVolume {
+    /// Name of the key. Ex: `_??_USBSTOR#Disk&Ven_Kingston&...#{53f56307-b6bf-11d0-94f2-00a0c91efb8b}KINGSTON_1727287740`
+    pub key: String,
+    pub label: String,
+    pub serial_number: u32,
+}
+
+/// Device identity inside a USBSTOR device instance path
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UsbStorId {
+    vendor: String,
+    product: String,
+    revision: String,
+    serial: String,
+}
+
+impl UsbStorId {
+    /// Parses `...USBSTOR#Disk&Ven_<vendor>&Prod_<product>&Rev_<revision>#<serial>#...`
+    fn parse(path: &str) -> Option<Self> {
+        let upper = path.to_ascii_uppercase();
+        let start = upper.find("USBSTOR#")? + 8;
+        let mut parts = path[start..].split('#');
+        let (vendor, product, revision) = parse_device_class(parts.next()?)?;
+        let serial = parts.next()?;
+        if serial.is_empty() {
+            return None;
+        }
+        Some(Self {
+            vendor,
+            product,
+            revision,
+            serial: serial.to_string(),
+        })
+    }
+}
+
+/// USB storage history of the registry: USBSTOR, Windows Portable Devices and EMDMgmt
+#[derive(Debug, Clone, Default)]
+pub struct RemovableDeviceHistory {
+    pub usb_devices: Vec<UsbDevice>,
+    pub portable_devices: Vec<PortableDevice>,
+    pub emd_volumes: Vec<EmdVolume>,
+}
+
+impl RemovableDeviceHistory {
+    /// Reads the keys of the SYSTEM and SOFTWARE hives. Missing keys are skipped.
+    pub fn from_registry(registry: &dyn RegistryReader) -> ForensicResult<Self> {
+        Ok(Self {
+            usb_devices: read_usb_devices(registry)?,
+            portable_devices: read_portable_devices(registry)?,
+            emd_volumes: read_emd_volumes(registry)?,
+        })
+    }
+
+    /// Device whose volume had the serial number
+    pub fn device_of_serial(&self, serial_number: u32) -> Option<RemovableDeviceMatch<'_>> {
+        let volume = self
+            .emd_volumes
+            .iter()
+            .find(|v| v.serial_number == serial_number)?;
+        let id = UsbStorId::parse(&volume.key);
+        let device = id
+            .as_ref()
+            .and_then(|id| self.usb_devices.iter().find(|v| v.is(id)));
+        let portable_names = self
+            .portable_devices
+            .iter()
+            .filter(|v| match (&id, UsbStorId::parse(&v.key)) {
+                (Some(id), Some(portable)) => portable.serial.eq_ignore_ascii_case(&id.serial),
+                _ => false,
+            })
+            .filter_map(|v| v.friendly_name.as_deref())
+            .collect();
+        Some(RemovableDeviceMatch {
+            volume,
+            device,
+            vendor: id.as_ref().map(|v| v.vendor.clone()),
+            product: id.as_ref().map(|v| v.product.clone()),
+            serial: id.map(|v| v.serial),
+            portable_names,
+        })
+    }
+}
+
+/// Device found in the registry for a volume serial number
+#[derive(Debug, Clone)]
+pub struct RemovableDeviceMatch<'a> {
+    pub volume: &'a EmdVolume,
+    /// USBSTOR entry of the device when it is still in the registry
+    pub device: Option<&'a UsbDevice>,
+    pub vendor: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+    /// Friendly names of the device in Windows Portable Devices
+    pub portable_names: Vec<&'a str>,
+}
+
+/// Prefetch with the image or some loaded files in a volume that is not the system volume
+#[derive(Debug, Clone)]
+pub struct RemovableExecution<'a> {
+    pub prefetch: &'a PrefetchFile,
+    pub volume: &'a VolumeInformation,
+    /// The executable itself is in the volume
+    pub executable: bool,
+    /// Files of the volume loaded by the executable
+    pub loaded: Vec<&'a Metric>,
+    /// Device of the volume when the registry history is available and has its serial number
+    pub device: Option<RemovableDeviceMatch<'a>>,
+}
+
+/// Finds executables run from, or loading libraries from, volumes other than the system volume.
+///
+/// A volume is the system volume when it holds the `SYSTEM32` directory of the Windows folder.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::{prelude::*, removable::RemovableMediaDetector};
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/30/C/Windows/Prefetch/CMD.EXE-D269B812.pf")).unwrap();
+/// let prefetch = read_prefetch_file("CMD.EXE-D269B812.pf", file).unwrap();
+/// let detector = RemovableMediaDetector::new();
+/// let found = detector.detect(&prefetch);
+/// assert_eq!(1, found.len());
+/// assert_eq!(0x66f451bc, found[0].volume.serial_number);
+/// assert!(!found[0].executable);
+/// assert!(found[0].loaded.iter().any(|v| v.file.ends_with(r"\CLINK_DLL_X64.DLL")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RemovableMediaDetector {
+    /// Windows folder inside the system volume. Ex: `\WINDOWS`
+    pub system_folder: String,
+    pub history: Option<RemovableDeviceHistory>,
+}
+
+impl Default for RemovableMediaDetector {
+    fn default() -> Self {
+        Self {
+            system_folder: DEFAULT_SYSTEM_FOLDER.into(),
+            history: None,
+        }
+    }
+}
+
+impl RemovableMediaDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the registry device history to match the volume serial numbers with USB devices
+    pub fn with_history(history: RemovableDeviceHistory) -> Self {
+        Self {
+            history: Some(history),
+            ..Default::default()
+        }
+    }
+
+    /// The volume holds the `SYSTEM32` directory of the Windows folder
+    pub fn is_system_volume(&self, volume: &VolumeInformation) -> bool {
+        is_system_volume(volume, &self.system_folder)
+    }
+
+    /// Volumes of the prefetch outside the system volume with the executable or loaded files in them
+    pub fn detect<'a>(&'a self, prefetch: &'a PrefetchFile) -> Vec<RemovableExecution<'a>> {
+        let executable = prefetch.executable_path();
+        let mut found = Vec::new();
+        for volume in &prefetch.volume {
+            if self.is_system_volume(volume) {
+                continue;
+            }
+            let loaded = metrics_in_volume(prefetch, volume);
+            if loaded.is_empty() {
+                continue;
+            }
+            found.push(RemovableExecution {
+                prefetch,
+                volume,
+                executable: loaded.iter().any(|v| v.file == executable),
+                loaded,
+                device: self
+                    .history
+                    .as_ref()
+                    .and_then(|v| v.device_of_serial(volume.serial_number)),
+            });
+        }
+        found
+    }
+
+    /// Runs `detect` over a set of prefetch files
+    pub fn detect_all<'a>(&'a self, prefetches: &'a [PrefetchFile]) -> Vec<RemovableExecution<'a>> {
+        prefetches.iter().flat_map(|v| self.detect(v)).collect()
+    }
+}
+
+/// The volume holds the `SYSTEM32` directory of the Windows folder. Ex: `\WINDOWS`
+pub(crate) fn is_system_volume(volume: &VolumeInformation, system_folder: &str) -> bool {
+    let system32 = format!("{}\\SYSTEM32", system_folder.trim_end_matches('\\'));
+    volume.directory_strings.iter().any(|directory| {
+        VolumePath::parse(directory)
+            .map(|v| {
+                v.relative
+                    .trim_end_matches('\\')
+                    .eq_ignore_ascii_case(&system32)
+            })
+            .unwrap_or(false)
+    })
+}
+
+/// Metrics of the prefetch whose file is in the volume
+pub(crate) fn metrics_in_volume<'a>(
+    prefetch: &'a PrefetchFile,
+    volume: &VolumeInformation,
+) -> Vec<&'a Metric> {
+    prefetch
+        .metrics
+        .iter()
+        .filter(|metric| {
+            metric
+                .volume_path()
+                .map(|v| v.is_in(volume))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Parses `Disk&Ven_<vendor>&Prod_<product>&Rev_<revision>`
+fn parse_device_class(class: &str) -> Option<(String, String, String)> {
+    let mut vendor = None;
+    let mut product = None;
+    let mut revision = String::new();
+    for part in class.split('&') {
+        let upper = part.to_ascii_uppercase();
+        if upper.starts_with("VEN_") {
+            vendor = Some(part[4..].to_string());
+        } else if upper.starts_with("PROD_") {
+            product = Some(part[5..].to_string());
+        } else if upper.starts_with("REV_") {
+            revision = part[4..].to_string();
+        }
+    }
+    Some((vendor?, product?, revision))
+}
+
+fn read_string(registry: &dyn RegistryReader, key: RegHiveKey, name: &str) -> Option<String> {
+    match registry.read_value(key, name).ok()? {
+        RegValue::SZ(v) | RegValue::ExpandSZ(v) => Some(v),
+        _ => None,
+    }
+}
+
+fn read_usb_devices(registry: &dyn RegistryReader) -> ForensicResult<Vec<UsbDevice>> {
+    let key = match registry.open_key(RegHiveKey::HkeyLocalMachine, USBSTOR_KEY) {
+        Ok(v) => v,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut devices = Vec::new();
+    for class in registry.enumerate_keys(key)? {
+        let (vendor, product, revision) = match parse_device_class(&class) {
+            Some(v) => v,
+            None => continue,
+        };
+        let class_key = match registry.open_key(key, &class) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        for serial in registry.enumerate_keys(class_key).unwrap_or_default() {
+            let friendly_name = match registry.open_key(class_key, &serial) {
+                Ok(instance) => {
+                    let name = read_string(registry, instance, "FriendlyName");
+                    registry.close_key(instance);
+                    name
+                }
+                Err(_) => None,
+            };
+            devices.push(UsbDevice {
+                vendor: vendor.clone(),
+                product: product.clone(),
+                revision: revision.clone(),
+                serial,
+                friendly_name,
+            });
+        }
+        registry.close_key(class_key);
+    }
+    registry.close_key(key);
+    Ok(devices)
+}
+
+fn read_portable_devices(registry: &dyn RegistryReader) -> ForensicResult<Vec<PortableDevice>> {
+    let key = match registry.open_key(RegHiveKey::HkeyLocalMachine, PORTABLE_DEVICES_KEY) {
+        Ok(v) => v,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut devices = Vec::new();
+    for name in registry.enumerate_keys(key)? {
+        let friendly_name = match registry.open_key(key, &name) {
+            Ok(device) => {
+                let friendly_name = read_string(registry, device, "FriendlyName");
+                registry.close_key(device);
+                friendly_name
+            }
+            Err(_) => None,
+        };
+        devices.push(PortableDevice {
+            key: name,
+            friendly_name,
+        });
+    }
+    registry.close_key(key);
+    Ok(devices)
+}
+
+pub(crate) fn read_emd_volumes(registry: &dyn RegistryReader) -> ForensicResult<Vec<EmdVolume>> {
+    let key = match registry.open_key(RegHiveKey::HkeyLocalMachine, EMDMGMT_KEY) {
+        Ok(v) => v,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let volumes = registry
+        .enumerate_keys(key)?
+        .into_iter()
+        .filter_map(|name| {
+            let (rest, serial) = name.rsplit_once('_')?;
+            let serial_number = serial.parse::<u32>().ok()?;
+            // The label follows the device interface GUID
+            let label = match rest.rfind('}') {
+                Some(pos) => rest[pos + 1..].to_string(),
+                None => String::new(),
+            };
+            Some(EmdVolume {
+                key: name,
+                label,
+                serial_number,
+            })
+        })
+        .collect();
+    registry.close_key(key);
+    Ok(volumes)
+}
+
+#[test]
+fn should_match_volume_serials_with_usb_devices() {
+    use forensic_rs::utils::testing::TestingRegistry;
+    let mut registry = TestingRegistry::new();
+    registry.add_value(
+        &format!(
+            r"HKLM\{}\Disk&Ven_Kingston&Prod_DataTraveler_3.0&Rev_PMAP\60A44C3FAE8BBFB0F9370026&0",
+            USBSTOR_KEY
+        ),
+        "FriendlyName",
+        RegValue::from_str("Kingston DataTraveler 3.0 USB Device"),
+    );
+    registry.add_value(
+        &format!(
+            r"HKLM\{}\WPDBUSENUMROOT#UMB#2&37C186B&1&STORAGE#VOLUME#_??_USBSTOR#DISK&VEN_KINGSTON&PROD_DATATRAVELER_3.0&REV_PMAP#60A44C3FAE8BBFB0F9370026&0#",
+            PORTABLE_DEVICES_KEY
+        ),
+        "FriendlyName",
+        RegValue::from_str("KINGSTON"),
+    );
+    registry.add_value(
+        &format!(
+            r"HKLM\{}\_??_USBSTOR#Disk&Ven_Kingston&Prod_DataTraveler_3.0&Rev_PMAP#60A44C3FAE8BBFB0F9370026&0#{{53f56307-b6bf-11d0-94f2-00a0c91efb8b}}KINGSTON_1727287740",
+            EMDMGMT_KEY
+        ),
+        "LastTestedTime",
+        RegValue::QWord(0),
+    );
+    let history = RemovableDeviceHistory::from_registry(&registry).unwrap();
+    assert_eq!(1, history.usb_devices.len());
+    assert_eq!("Kingston", history.usb_devices[0].vendor);
+    assert_eq!("PMAP", history.usb_devices[0].revision);
+    let found = history.device_of_serial(0x66f451bc).unwrap();
+    assert_eq!("KINGSTON", found.volume.label);
+    assert_eq!(Some("DataTraveler_3.0"), found.product.as_deref());
+    assert_eq!(Some("60A44C3FAE8BBFB0F9370026&0"), found.serial.as_deref());
+    assert_eq!(
+        Some("Kingston DataTraveler 3.0 USB Device"),
+        found.device.and_then(|v| v.friendly_name.as_deref())
+    );
+    assert_eq!(vec!["KINGSTON"], found.portable_names);
+    assert!(history.device_of_serial(0x1234).is_none());
+}
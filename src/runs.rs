@@ -0,0 +1,149 @@
+use crate::{
+    common::{Metric, PrefetchFile},
+    trace::TraceChainEntry,
+};
+
+/// Number of runs tracked by the used and prefetched bitfields of a trace
+pub const RUN_SLOTS: usize = 8;
+
+/// Dependencies that had used blocks in one of the last runs of the executable
+#[derive(Debug, Clone)]
+pub struct RunDependencies<'a> {
+    /// Position of the run bit. It is not matched with a slot of `last_run_times`, see [`normalize_run_bitfield`]
+    pub slot: usize,
+    /// Files with at least one used block in the run
+    pub metrics: Vec<&'a Metric>,
+}
+
+/// Moves the bits of the tracked runs of a trace bitfield to the low end: with `n` tracked runs, bits `0..n` are set.
+/// Versions 23 and 26 fill the runs from the low bit and version 30 from the high bit: in the test artifacts NTDLL.DLL
+/// has the bits of the 2 runs of the version 23 and 26 files at the bottom and the bits of the 4 runs of the version 30
+/// CMD.EXE at the top. NTDLL.DLL is loaded in every run, so this shows where the runs are but not which bit is the most
+/// recent one, and no public source documents it.
+///
+/// The other version 30 artifacts do not settle it either: RUST_OUT.EXE ran once and has the 8 used bits set, and the
+/// `__PSSCRIPTPOLICYTEST_*` scripts that POWERSHELL.EXE creates with a new name in each run have several bits set.
+/// The bits are therefore never lined up with `last_run_times`.
+///
+/// Version 17 returns None: its only artifact (2 runs) has 5 used bits set (`0b11111000`), so the bits do not follow
+/// the runs as in later versions.
+pub fn normalize_run_bitfield(version: u32, bitfield: u8) -> Option<u8> {
+    match version {
+        23 | 26 => Some(bitfield),
+        30 | 31 => Some(bitfield.reverse_bits()),
+        _ => None,
+    }
+}
+
+/// Runs in which any block of the file was used, as returned by [`normalize_run_bitfield`]
+pub fn metric_used_runs(version: u32, metric: &Metric) -> Option<u8> {
+    runs_of(version, &metric.trace_chain.entries, |v| v.used_bitfield)
+}
+
+/// Runs in which any block of the file was prefetched, as returned by [`normalize_run_bitfield`]
+pub fn metric_prefetched_runs(version: u32, metric: &Metric) -> Option<u8> {
+    runs_of(version, &metric.trace_chain.entries, |v| {
+        v.prefetched_bitfield
+    })
+}
+
+fn runs_of(
+    version: u32,
+    traces: &[TraceChainEntry],
+    bitfield: impl Fn(&TraceChainEntry) -> u8,
+) -> Option<u8> {
+    let bits = traces.iter().fold(0, |acc, trace| acc | bitfield(trace));
+    normalize_run_bitfield(version, bits)
+}
+
+/// Number of run slots with data: the run count, up to 8
+pub fn tracked_runs(prefetch: &PrefetchFile) -> usize {
+    (prefetch.run_count as usize).min(RUN_SLOTS)
+}
+
+/// Lists, for each tracked run bit, the files whose blocks were used. None when the run bits of the version are unknown.
+/// The run bits are not lined up with `last_run_times`.
+pub fn run_dependencies(prefetch: &PrefetchFile) -> Option<Vec<RunDependencies<'_>>> {
+    normalize_run_bitfield(prefetch.version, 0)?;
+    let used = prefetch
+        .metrics
+        .iter()
+        .map(|v| metric_used_runs(prefetch.version, v))
+        .collect::<Option<Vec<u8>>>()?;
+    let runs = (0..tracked_runs(prefetch))
+        .map(|slot| RunDependencies {
+            slot,
+            metrics: prefetch
+                .metrics
+                .iter()
+                .zip(used.iter())
+                .filter(|(_, &used)| used & (1 << slot) != 0)
+                .map(|(metric, _)| metric)
+                .collect(),
+        })
+        .collect();
+    Some(runs)
+}
+
+/// Files used in exactly one of the tracked runs, with the position of that run bit (see [`RunDependencies::slot`]).
+/// A library loaded a single time among several executions is worth a look. None when the run bits of the version are unknown.
+pub fn single_run_dependencies(prefetch: &PrefetchFile) -> Option<Vec<(&Metric, usize)>> {
+    normalize_run_bitfield(prefetch.version, 0)?;
+    let tracked = tracked_runs(prefetch);
+    if tracked < 2 {
+        return Some(Vec::new());
+    }
+    let tracked_mask = ((1u16 << tracked) - 1) as u8;
+    let mut single = Vec::new();
+    for metric in &prefetch.metrics {
+        let used = metric_used_runs(prefetch.version, metric)? & tracked_mask;
+        if used.count_ones() == 1 {
+            single.push((metric, used.trailing_zeros() as usize));
+        }
+    }
+    Some(single)
+}
+
+#[test]
+fn should_normalize_run_bitfield() {
+    assert_eq!(Some(0b0000_0011), normalize_run_bitfield(23, 0b0000_0011));
+    assert_eq!(Some(0b0000_0011), normalize_run_bitfield(30, 0b1100_0000));
+    assert_eq!(None, normalize_run_bitfield(17, 0b1111_1000));
+}
+
+#[test]
+fn should_list_dependencies_of_each_run() {
+    use forensic_rs::utils::time::Filetime;
+    let trace = |used_bitfield| TraceChainEntry {
+        used_bitfield,
+        ..Default::default()
+    };
+    let metric = |file: &str, used| Metric {
+        file: file.into(),
+        trace_chain: crate::trace::TraceChain {
+            entries: vec![trace(used)],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let prefetch = PrefetchFile {
+        version: 30,
+        run_count: 3,
+        last_run_times: vec![Filetime::new(3), Filetime::new(2), Filetime::new(1)],
+        metrics: vec![
+            metric("NTDLL.DLL", 0b1110_0000),
+            metric("PAYLOAD.DLL", 0b0010_0000),
+            metric("OLD.DLL", 0b0000_0001),
+        ],
+        ..Default::default()
+    };
+    let runs = run_dependencies(&prefetch).unwrap();
+    assert_eq!(3, runs.len());
+    assert_eq!(2, runs[2].metrics.len());
+    assert_eq!("PAYLOAD.DLL", runs[2].metrics[1].file);
+    assert_eq!(1, runs[0].metrics.len());
+    let single = single_run_dependencies(&prefetch).unwrap();
+    assert_eq!(1, single.len());
+    assert_eq!("PAYLOAD.DLL", single[0].0.file);
+    assert_eq!(2, single[0].1);
+}
@@ -0,0 +1,465 @@
+use std::path::{Path, PathBuf};
+
+use forensic_rs::{
+    artifact::WindowsArtifacts,
+    err::{ForensicError, ForensicResult},
+    notifications::NotificationType,
+    notify_high, notify_info, notify_medium,
+    traits::{
+        registry::RegistryReader,
+        vfs::{VDirEntry, VirtualFileSystem},
+    },
+};
+
+use crate::{
+    common::PrefetchFile,
+    error::{InStage, ParseStage, PrefetchParseError, PrefetchParseResult},
+    prefetch::read_prefetch,
+    prefetcher::{MissingPrefetchReason, PrefetcherConfiguration},
+};
+
+/// Content and filesystem timestamps of a `.pf` file read for the worker threads
+#[cfg(feature = "parallel")]
+type FileContent = (Vec<u8>, Option<crate::first_run::PrefetchFileTimes>);
+
+/// Folder where Windows stores the prefetch files
+pub const PREFETCH_FOLDER: &str = r"C:\Windows\Prefetch";
+
+/// Result of parsing one file of the prefetch folder
+#[derive(Debug)]
+pub struct ScannedPrefetch {
+    /// Name of the file inside the prefetch folder. Ex: `CMD.EXE-087B4001.pf`
+    pub file_name: String,
+    pub result: PrefetchParseResult<PrefetchFile>,
+}
+
+/// Entry of the prefetch folder that was not parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEntry {
+    pub name: String,
+    pub kind: SkippedKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkippedKind {
+    Directory,
+    Symlink,
+    /// A file without the `.pf` extension
+    NotPrefetch,
+}
+
+/// Prefetch file that could not be parsed
+#[derive(Debug, Clone)]
+pub struct PrefetchFailure {
+    pub file_name: String,
+    /// Error with the step of the parsing that failed
+    pub error: PrefetchParseError,
+}
+
+/// Outcome of scanning a prefetch folder: every entry of the folder is either parsed, failed or skipped.
+#[derive(Debug, Default)]
+pub struct PrefetchScanReport {
+    pub prefetches: Vec<PrefetchFile>,
+    pub failures: Vec<PrefetchFailure>,
+    pub skipped: Vec<SkippedEntry>,
+    /// Prefetch configuration of the SYSTEM hive, when a registry was supplied
+    pub configuration: Option<PrefetcherConfiguration>,
+    /// Why the prefetch folder could not be read or holds no `.pf` file, when a registry was supplied
+    pub missing_folder: Option<MissingPrefetchReason>,
+}
+
+impl PrefetchScanReport {
+    /// Builds the report consuming the results of a scanner
+    pub fn from_scanned<I: Iterator<Item = ScannedPrefetch>>(scanned: I) -> Self {
+        let mut report = Self::default();
+        report.extend(scanned);
+        report
+    }
+    fn extend<I: Iterator<Item = ScannedPrefetch>>(&mut self, scanned: I) {
+        for scanned in scanned {
+            match scanned.result {
+                Ok(v) => self.prefetches.push(v),
+                Err(error) => self.failures.push(PrefetchFailure {
+                    file_name: scanned.file_name,
+                    error,
+                }),
+            }
+        }
+    }
+}
+
+/// Parses all the files of the prefetch folder (C:\Windows\Prefetch) and reports the failures and the skipped entries.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::scan::scan_prefetch_report;
+/// let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+/// let report = scan_prefetch_report(&mut fs).unwrap();
+/// assert_eq!(4, report.prefetches.len());
+/// assert!(report.failures.is_empty());
+/// ```
+pub fn scan_prefetch_report<F: VirtualFileSystem>(
+    fs: &mut F,
+) -> ForensicResult<PrefetchScanReport> {
+    let mut scanner = scan_prefetch_folder(fs)?;
+    let mut report = PrefetchScanReport::default();
+    report.extend(scanner.by_ref());
+    report.skipped = scanner.skipped;
+    Ok(report)
+}
+
+/// Same as [`scan_prefetch_report`], but a missing or empty prefetch folder is explained with the `EnablePrefetcher` value
+/// and the SysMain service of the SYSTEM hive instead of returning an error. Other errors reading the folder (Ex: permissions)
+/// are returned.
+///
+/// ```rust
+/// use forensic_rs::{prelude::*, utils::testing::TestingRegistry};
+/// use frnsc_prefetch::{prefetcher::*, scan::scan_prefetch_report_with_registry};
+/// let mut registry = TestingRegistry::new();
+/// registry.add_value(&format!(r"HKLM\{}", PREFETCH_PARAMETERS_KEY), "EnablePrefetcher", RegValue::DWord(0));
+/// let mut fs = ChRootFileSystem::new("./artifacts/none", Box::new(StdVirtualFS::new()));
+/// let report = scan_prefetch_report_with_registry(&mut fs, &registry).unwrap();
+/// assert!(report.prefetches.is_empty());
+/// assert_eq!(Some(MissingPrefetchReason::DisabledByConfiguration), report.missing_folder);
+/// ```
+pub fn scan_prefetch_report_with_registry<F: VirtualFileSystem>(
+    fs: &mut F,
+    registry: &dyn RegistryReader,
+) -> ForensicResult<PrefetchScanReport> {
+    forensic_rs::context::set_artifact(WindowsArtifacts::Prefetch);
+    let configuration = PrefetcherConfiguration::from_registry(registry)?;
+    let mut report = PrefetchScanReport::default();
+    match list_folder(fs, Path::new(PREFETCH_FOLDER)) {
+        Ok(mut scanner) => {
+            report.extend(scanner.by_ref());
+            report.skipped = scanner.skipped;
+        }
+        Err(e) if is_not_found(&e) => {}
+        Err(e) => return Err(e),
+    }
+    if report.prefetches.is_empty() && report.failures.is_empty() {
+        let reason = configuration.missing_prefetch_reason();
+        notify_missing_prefetch(reason);
+        report.missing_folder = Some(reason);
+    }
+    report.configuration = Some(configuration);
+    Ok(report)
+}
+
+fn is_not_found(error: &ForensicError) -> bool {
+    match error {
+        ForensicError::Missing(_) => true,
+        ForensicError::Io(e) => e.kind() == std::io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
+/// Only a folder missing while prefetching is enabled is an anti-forensics alert
+fn notify_missing_prefetch(reason: MissingPrefetchReason) {
+    match reason {
+        MissingPrefetchReason::DisabledByConfiguration => notify_info!(
+            NotificationType::Informational,
+            "No prefetch found: prefetching is disabled by configuration"
+        ),
+        MissingPrefetchReason::LikelyWiped => notify_high!(
+            NotificationType::AntiForensicsDetected,
+            "No prefetch found: prefetching is enabled"
+        ),
+        MissingPrefetchReason::Unknown => notify_medium!(
+            NotificationType::AntiForensicsDetected,
+            "No prefetch found: the prefetcher configuration is unknown"
+        ),
+    }
+}
+
+/// Iterator that parses the prefetch files of a folder one at a time.
+pub struct PrefetchScanner<'a, F: VirtualFileSystem> {
+    fs: &'a mut F,
+    folder: PathBuf,
+    entries: std::vec::IntoIter<VDirEntry>,
+    skipped: Vec<SkippedEntry>,
+}
+
+/// Lists the prefetch folder (C:\Windows\Prefetch) and returns an iterator that parses each `.pf` file when requested.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::scan::scan_prefetch_folder;
+/// let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+/// for scanned in scan_prefetch_folder(&mut fs).unwrap() {
+///     match scanned.result {
+///         Ok(prefetch) => println!("{}: {} runs", scanned.file_name, prefetch.run_count),
+///         Err(e) => println!("{}: {}", scanned.file_name, e),
+///     }
+/// }
+/// ```
+pub fn scan_prefetch_folder<F: VirtualFileSystem>(
+    fs: &mut F,
+) -> ForensicResult<PrefetchScanner<'_, F>> {
+    scan_folder(fs, Path::new(PREFETCH_FOLDER))
+}
+
+/// Same as [`scan_prefetch_folder`] with a custom folder
+pub fn scan_folder<'a, F: VirtualFileSystem>(
+    fs: &'a mut F,
+    folder: &Path,
+) -> ForensicResult<PrefetchScanner<'a, F>> {
+    forensic_rs::context::set_artifact(WindowsArtifacts::Prefetch);
+    match list_folder(fs, folder) {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            // Without the prefetcher configuration a missing folder keeps the original alert
+            notify_high!(NotificationType::AntiForensicsDetected, "No prefetch found");
+            Err(e)
+        }
+    }
+}
+
+fn list_folder<'a, F: VirtualFileSystem>(
+    fs: &'a mut F,
+    folder: &Path,
+) -> ForensicResult<PrefetchScanner<'a, F>> {
+    let entries = fs.read_dir(folder)?;
+    Ok(PrefetchScanner {
+        fs,
+        folder: folder.to_path_buf(),
+        entries: entries.into_iter(),
+        skipped: Vec::new(),
+    })
+}
+
+impl<'a, F: VirtualFileSystem> PrefetchScanner<'a, F> {
+    /// Name of the next `.pf` file of the folder
+    fn next_file_name(&mut self) -> Option<String> {
+        for entry in self.entries.by_ref() {
+            let (name, kind) = match entry {
+                VDirEntry::File(v) if v.ends_with(".pf") => return Some(v),
+                VDirEntry::File(v) => (v, SkippedKind::NotPrefetch),
+                VDirEntry::Directory(v) => (v, SkippedKind::Directory),
+                VDirEntry::Symlink(v) => (v, SkippedKind::Symlink),
+            };
+            self.skipped.push(SkippedEntry { name, kind });
+        }
+        None
+    }
+
+    /// Entries of the folder that were not parsed so far
+    pub fn skipped(&self) -> &[SkippedEntry] {
+        &self.skipped
+    }
+
+    /// Reads the content of the next `.pf` file without parsing it
+    #[cfg(feature = "parallel")]
+    fn next_file_content(&mut self) -> Option<(String, PrefetchParseResult<FileContent>)> {
+        let file_name = self.next_file_name()?;
+        let content = self
+            .fs
+            .open(self.folder.join(&file_name).as_path())
+            .in_stage(ParseStage::Open)
+            .and_then(|file| {
+                let file_times = file
+                    .metadata()
+                    .ok()
+                    .map(|v| crate::first_run::PrefetchFileTimes::from_metadata(&v));
+                let content = crate::prefetch::read_file_content(&file_name, file)
+                    .in_stage(ParseStage::Read)?;
+                Ok((content, file_times))
+            });
+        Some((file_name, content))
+    }
+
+    /// Parses the files in worker threads. Only the parsing runs in parallel: the files are opened and read one at a time
+    /// by the calling thread. The results are returned in the order they are completed.
+    /// A file whose parser panics is returned as a failure of the `Panic` stage.
+    ///
+    /// ```rust
+    /// use forensic_rs::prelude::*;
+    /// use frnsc_prefetch::scan::scan_prefetch_folder;
+    /// let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+    /// let parsed = scan_prefetch_folder(&mut fs).unwrap().parallel(4).filter(|v| v.result.is_ok()).count();
+    /// assert_eq!(4, parsed);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn parallel(self, threads: usize) -> parallel::ParallelPrefetchScanner<'a, F> {
+        parallel::ParallelPrefetchScanner::new(self, threads)
+    }
+}
+
+impl<F: VirtualFileSystem> Iterator for PrefetchScanner<'_, F> {
+    type Item = ScannedPrefetch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let file_name = self.next_file_name()?;
+        let result = self
+            .fs
+            .open(self.folder.join(&file_name).as_path())
+            .in_stage(ParseStage::Open)
+            .and_then(|file| read_prefetch(&file_name, file, false));
+        Some(ScannedPrefetch { file_name, result })
+    }
+}
+
+#[cfg(feature = "parallel")]
+pub mod parallel {
+    use std::{
+        sync::{
+            mpsc::{channel, Receiver, Sender},
+            Arc, Mutex,
+        },
+        thread::JoinHandle,
+    };
+
+    use forensic_rs::{
+        artifact::WindowsArtifacts, err::ForensicError, traits::vfs::VirtualFileSystem,
+    };
+
+    use super::{PrefetchScanner, ScannedPrefetch};
+    use crate::{
+        common::PrefetchFile,
+        error::{ParseStage, PrefetchParseError, PrefetchParseResult},
+        first_run::PrefetchFileTimes,
+        prefetch::read_prefetch_bytes_staged,
+    };
+
+    /// File name, content and filesystem timestamps of a `.pf` file
+    type Job = (String, Vec<u8>, Option<PrefetchFileTimes>);
+
+    /// Iterator over the prefetch files parsed by a pool of threads. The files are still read by the calling thread.
+    pub struct ParallelPrefetchScanner<'a, F: VirtualFileSystem> {
+        source: PrefetchScanner<'a, F>,
+        source_finished: bool,
+        jobs: Option<Sender<Job>>,
+        results: Receiver<ScannedPrefetch>,
+        workers: Vec<JoinHandle<()>>,
+        pending: usize,
+        max_pending: usize,
+    }
+
+    impl<'a, F: VirtualFileSystem> ParallelPrefetchScanner<'a, F> {
+        pub(crate) fn new(source: PrefetchScanner<'a, F>, threads: usize) -> Self {
+            let threads = threads.max(1);
+            let (jobs, job_receiver) = channel::<Job>();
+            let (result_sender, results) = channel();
+            let job_receiver = Arc::new(Mutex::new(job_receiver));
+            let workers = (0..threads)
+                .map(|_| {
+                    let job_receiver = job_receiver.clone();
+                    let result_sender = result_sender.clone();
+                    std::thread::spawn(move || {
+                        forensic_rs::context::set_artifact(WindowsArtifacts::Prefetch);
+                        loop {
+                            let job = match job_receiver.lock() {
+                                Ok(v) => v.recv(),
+                                Err(_) => return,
+                            };
+                            let (file_name, content, file_times) = match job {
+                                Ok(v) => v,
+                                Err(_) => return,
+                            };
+                            let result = catch_parse_panic(|| {
+                                read_prefetch_bytes_staged(&file_name, &content)
+                            })
+                            .map(|mut prefetch| {
+                                prefetch.file_times = file_times;
+                                prefetch
+                            });
+                            if result_sender
+                                .send(ScannedPrefetch { file_name, result })
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    })
+                })
+                .collect();
+            Self {
+                source,
+                source_finished: false,
+                jobs: Some(jobs),
+                results,
+                workers,
+                pending: 0,
+                max_pending: threads * 2,
+            }
+        }
+    }
+
+    /// Turns a panic of the parser into an error, so the worker answers every job it takes
+    fn catch_parse_panic(
+        parse: impl FnOnce() -> PrefetchParseResult<PrefetchFile> + std::panic::UnwindSafe,
+    ) -> PrefetchParseResult<PrefetchFile> {
+        std::panic::catch_unwind(parse).unwrap_or_else(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|v| v.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(PrefetchParseError::new(
+                ParseStage::Panic,
+                ForensicError::Other(format!("The prefetch parser panicked: {}", message)),
+            ))
+        })
+    }
+
+    impl<F: VirtualFileSystem> ParallelPrefetchScanner<'_, F> {
+        /// Entries of the folder that were not parsed so far
+        pub fn skipped(&self) -> &[super::SkippedEntry] {
+            self.source.skipped()
+        }
+    }
+
+    impl<F: VirtualFileSystem> Iterator for ParallelPrefetchScanner<'_, F> {
+        type Item = ScannedPrefetch;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while !self.source_finished && self.pending < self.max_pending {
+                let (file_name, content) = match self.source.next_file_content() {
+                    Some(v) => v,
+                    None => {
+                        self.source_finished = true;
+                        self.jobs = None;
+                        break;
+                    }
+                };
+                let (content, file_times) = match content {
+                    Ok(v) => v,
+                    Err(e) => {
+                        return Some(ScannedPrefetch {
+                            file_name,
+                            result: Err(e),
+                        })
+                    }
+                };
+                if let Some(jobs) = &self.jobs {
+                    if jobs.send((file_name, content, file_times)).is_ok() {
+                        self.pending += 1;
+                    }
+                }
+            }
+            if self.pending == 0 {
+                return None;
+            }
+            let result = self.results.recv().ok()?;
+            self.pending -= 1;
+            Some(result)
+        }
+    }
+
+    impl<F: VirtualFileSystem> Drop for ParallelPrefetchScanner<'_, F> {
+        fn drop(&mut self) {
+            self.jobs = None;
+            for worker in self.workers.drain(..) {
+                let _ = worker.join();
+            }
+        }
+    }
+
+    #[test]
+    fn should_report_a_parser_panic_as_failure() {
+        let result = catch_parse_panic(|| panic!("corrupted table"));
+        let error = result.unwrap_err();
+        assert_eq!(ParseStage::Panic, error.stage);
+        assert!(error.to_string().contains("corrupted table"));
+    }
+}
@@ -0,0 +1,253 @@
+use forensic_rs::err::{ForensicError, ForensicResult};
+
+use crate::{
+    common::{Metric, PrefetchFile, VolumeInformation},
+    view::VersionLayout,
+};
+
+/// Maximum number of UTF-16 characters of the executable name stored in the header
+const EXECUTABLE_NAME_CHARACTERS: usize = 29;
+
+/// Builds a decompressed prefetch file (SCCA) for the selected format version: 17, 23, 26 or 30.
+/// The result can be packed with [`crate::prefetch::write_mam`] to get a Windows 10/11 like file.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::prelude::*;
+/// use frnsc_prefetch::serialize::serialize_prefetch;
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let file = fs.open(Path::new("./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf")).unwrap();
+/// let prefetch = read_prefetch_file("NOTEPAD.EXE-D8414F97.pf", file).unwrap();
+/// let buffer = serialize_prefetch(&prefetch, 30).unwrap();
+/// assert_eq!(b"SCCA", &buffer[4..8]);
+/// ```
+pub fn serialize_prefetch(prefetch: &PrefetchFile, version: u32) -> ForensicResult<Vec<u8>> {
+    let layout = layout_for_version(version)?;
+    let mut buffer = Vec::with_capacity(64 * 1024);
+    write_header(&mut buffer, prefetch, version);
+
+    let metrics_offset = 84 + layout.file_information_size;
+    buffer.resize(metrics_offset, 0);
+    let trace_count: usize = prefetch
+        .metrics
+        .iter()
+        .map(|v| v.trace_chain.entries.len())
+        .sum();
+    let trace_chain_offset = metrics_offset + prefetch.metrics.len() * layout.metric_size;
+    let filename_string_offset = trace_chain_offset + trace_count * layout.trace_size;
+
+    let strings = filename_strings(&prefetch.metrics);
+    let mut trace_index = 0;
+    for (metric, filename_offset) in prefetch.metrics.iter().zip(strings.offsets.iter()) {
+        write_metric(&mut buffer, &layout, metric, trace_index, *filename_offset);
+        trace_index += metric.trace_chain.entries.len();
+    }
+    let mut trace_index = 0;
+    for metric in &prefetch.metrics {
+        write_traces(&mut buffer, &layout, metric, trace_index);
+        trace_index += metric.trace_chain.entries.len();
+    }
+    buffer.extend_from_slice(&strings.data);
+    let hash_string_offset = buffer.len();
+    if layout.version == 30 {
+        if let Some(hash_string) = &prefetch.file_information.hash_string {
+            push_utf16(&mut buffer, hash_string);
+            align(&mut buffer, 4);
+        }
+    }
+    let hash_string_size = buffer.len() - hash_string_offset;
+    align(&mut buffer, 8);
+    let volume_information_offset = buffer.len();
+    write_volumes(&mut buffer, &layout, &prefetch.volume);
+    let volume_information_size = buffer.len() - volume_information_offset;
+
+    let file_size = buffer.len();
+    put_u32(&mut buffer, 12, file_size as u32);
+    let info = &mut buffer[84..metrics_offset];
+    put_u32(info, 0, metrics_offset as u32);
+    put_u32(info, 4, prefetch.metrics.len() as u32);
+    put_u32(info, 8, trace_chain_offset as u32);
+    put_u32(info, 12, trace_count as u32);
+    put_u32(info, 16, filename_string_offset as u32);
+    put_u32(info, 20, strings.data.len() as u32);
+    put_u32(info, 24, volume_information_offset as u32);
+    put_u32(info, 28, prefetch.volume.len() as u32);
+    put_u32(info, 32, volume_information_size as u32);
+    for (i, run_time) in prefetch
+        .last_run_times
+        .iter()
+        .take(layout.run_time_slots)
+        .enumerate()
+    {
+        put_u64(
+            info,
+            layout.last_run_time_offset + i * 8,
+            run_time.filetime(),
+        );
+    }
+    put_u32(info, layout.run_count_offset, prefetch.run_count);
+    if hash_string_size != 0 {
+        put_u32(info, 128, hash_string_offset as u32);
+        put_u32(info, 132, hash_string_size as u32);
+    }
+    Ok(buffer)
+}
+
+fn layout_for_version(version: u32) -> ForensicResult<VersionLayout> {
+    let file_information_size = match version {
+        17 => 68,
+        23 => 156,
+        26 => 224,
+        30 => 212,
+        _ => {
+            return Err(ForensicError::bad_format_string(format!(
+                "Cannot serialize prefetch version {}",
+                version
+            )))
+        }
+    };
+    VersionLayout::new(version, file_information_size)
+}
+
+fn write_header(buffer: &mut Vec<u8>, prefetch: &PrefetchFile, version: u32) {
+    buffer.extend_from_slice(&version.to_le_bytes());
+    buffer.extend_from_slice(b"SCCA");
+    let unknown: u32 = if version == 17 { 15 } else { 17 };
+    buffer.extend_from_slice(&unknown.to_le_bytes());
+    // File size, filled at the end
+    buffer.extend_from_slice(&[0, 0, 0, 0]);
+    let mut name = [0u8; 60];
+    for (i, character) in prefetch
+        .name
+        .encode_utf16()
+        .take(EXECUTABLE_NAME_CHARACTERS)
+        .enumerate()
+    {
+        name[i * 2..i * 2 + 2].copy_from_slice(&character.to_le_bytes());
+    }
+    buffer.extend_from_slice(&name);
+    buffer.extend_from_slice(&prefetch.hash.to_le_bytes());
+    buffer.extend_from_slice(&[0, 0, 0, 0]);
+}
+
+struct FilenameStrings {
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+fn filename_strings(metrics: &[Metric]) -> FilenameStrings {
+    let mut data = Vec::with_capacity(metrics.len() * 128);
+    let mut offsets = Vec::with_capacity(metrics.len());
+    for metric in metrics {
+        offsets.push(data.len());
+        push_utf16(&mut data, &metric.file);
+    }
+    FilenameStrings { data, offsets }
+}
+
+fn write_metric(
+    buffer: &mut Vec<u8>,
+    layout: &VersionLayout,
+    metric: &Metric,
+    trace_index: usize,
+    filename_offset: usize,
+) {
+    let characters = metric.file.encode_utf16().count() as u32;
+    buffer.extend_from_slice(&(trace_index as u32).to_le_bytes());
+    buffer.extend_from_slice(&(metric.trace_chain.entries.len() as u32).to_le_bytes());
+    if layout.version != 17 {
+        buffer.extend_from_slice(&metric.blocks_to_prefetch.to_le_bytes());
+    }
+    buffer.extend_from_slice(&(filename_offset as u32).to_le_bytes());
+    buffer.extend_from_slice(&characters.to_le_bytes());
+    buffer.extend_from_slice(&metric.flags.bits().to_le_bytes());
+    if layout.version != 17 {
+        let reference = metric
+            .mft_reference
+            .as_ref()
+            .map_or(0, |v| v.to_reference());
+        buffer.extend_from_slice(&reference.to_le_bytes());
+    }
+}
+
+fn write_traces(buffer: &mut Vec<u8>, layout: &VersionLayout, metric: &Metric, first_index: usize) {
+    let entries = &metric.trace_chain.entries;
+    for (i, trace) in entries.iter().enumerate() {
+        if layout.trace_size == 12 {
+            // Index of the next entry of the chain
+            let next = if i + 1 == entries.len() {
+                u32::MAX
+            } else {
+                (first_index + i + 1) as u32
+            };
+            buffer.extend_from_slice(&next.to_le_bytes());
+        }
+        buffer.extend_from_slice(&trace.block_load_count.to_le_bytes());
+        buffer.push(trace.flags.bits());
+        buffer.push(trace.unknown);
+        buffer.push(trace.used_bitfield);
+        buffer.push(trace.prefetched_bitfield);
+    }
+}
+
+fn write_volumes(buffer: &mut Vec<u8>, layout: &VersionLayout, volumes: &[VolumeInformation]) {
+    let start = buffer.len();
+    buffer.resize(start + volumes.len() * layout.volume_size, 0);
+    for (i, volume) in volumes.iter().enumerate() {
+        let device_path_offset = buffer.len() - start;
+        push_utf16(buffer, &volume.device_path);
+        align(buffer, 8);
+        let file_references_offset = buffer.len() - start;
+        let version: u32 = if layout.version == 17 { 1 } else { 3 };
+        buffer.extend_from_slice(&version.to_le_bytes());
+        buffer.extend_from_slice(&(volume.file_references.len() as u32).to_le_bytes());
+        if layout.file_references_header == 16 {
+            buffer.extend_from_slice(&[0; 8]);
+        }
+        for file in &volume.file_references {
+            let reference = (file.mft_entry & 0xffffffffffff) | ((file.seq_number as u64) << 48);
+            buffer.extend_from_slice(&reference.to_le_bytes());
+        }
+        let file_references_size = buffer.len() - start - file_references_offset;
+        align(buffer, 8);
+        let directory_strings_offset = buffer.len() - start;
+        for directory in &volume.directory_strings {
+            let characters = directory.encode_utf16().count() as u16;
+            buffer.extend_from_slice(&characters.to_le_bytes());
+            push_utf16(buffer, directory);
+        }
+        align(buffer, 8);
+
+        let entry = &mut buffer[start + i * layout.volume_size..];
+        put_u32(entry, 0, device_path_offset as u32);
+        put_u32(entry, 4, volume.device_path.encode_utf16().count() as u32);
+        put_u64(entry, 8, volume.creation_time);
+        put_u32(entry, 16, volume.serial_number);
+        put_u32(entry, 20, file_references_offset as u32);
+        put_u32(entry, 24, file_references_size as u32);
+        put_u32(entry, 28, directory_strings_offset as u32);
+        put_u32(entry, 32, volume.directory_strings.len() as u32);
+    }
+}
+
+/// Writes a NUL terminated UTF-16LE string
+fn push_utf16(buffer: &mut Vec<u8>, text: &str) {
+    for character in text.encode_utf16() {
+        buffer.extend_from_slice(&character.to_le_bytes());
+    }
+    buffer.extend_from_slice(&[0, 0]);
+}
+
+fn align(buffer: &mut Vec<u8>, alignment: usize) {
+    let size = buffer.len().div_ceil(alignment) * alignment;
+    buffer.resize(size, 0);
+}
+
+fn put_u32(buffer: &mut [u8], pos: usize, value: u32) {
+    buffer[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buffer: &mut [u8], pos: usize, value: u64) {
+    buffer[pos..pos + 8].copy_from_slice(&value.to_le_bytes());
+}
@@ -1,123 +1,342 @@
+use std::collections::BTreeSet;
+
 use forensic_rs::err::{ForensicError, ForensicResult};
 
-use crate::common::{u32_at_pos, PrefetchFileInformation, Trace};
+use crate::{
+    common::{u32_at_pos, BlockFlags, PrefetchFileInformation, Trace},
+    view::PrefetchView,
+};
+
+/// Next index value that marks the end of a trace chain (versions 17, 23 and 26)
+pub const TRACE_CHAIN_END: u32 = 0xFFFF_FFFF;
+
+/// Entry of the trace chain array with every stored field.
+///
+/// The first two fields follow the trace chain array entry of the libscca "Windows Prefetch File (PF) format"
+/// document: next array entry index (versions 17, 23 and 26 only) and total block load count. libscca lists the
+/// 4 bytes after them as unknown; the flags, used and prefetched bitfields are the names this crate already used for them.
+#[derive(Debug, Clone, Default)]
+pub struct TraceChainEntry {
+    /// Position of the entry in the trace chain array
+    pub index: u32,
+    /// Index of the next entry of the chain. None at the end of the chain and in version 30, where the entries of a file are contiguous.
+    pub next_index: Option<u32>,
+    /// Total block load count. It is the value [`Trace::block_offset`] exposes
+    pub block_load_count: u32,
+    /// Flags for loading blocks: executable, resource, non-prefetchable or force prefetch.
+    pub flags: BlockFlags,
+    /// Byte after the flags, unknown in libscca (maybe the sample duration in milliseconds). Kept to rebuild the file
+    pub unknown: u8,
+    /// Stores whether the block was used in each of the last eight runs (1 bit each)
+    pub used_bitfield: u8,
+    /// Stores whether the block was prefetched in each of the last eight runs (1 bit each)
+    pub prefetched_bitfield: u8,
+}
 
+impl TraceChainEntry {
+    /// Decodes an entry of 12 bytes (versions 17, 23 and 26) or 8 bytes (version 30)
+    pub fn from_bytes(index: u32, entry: &[u8]) -> ForensicResult<Self> {
+        let (next_index, entry) = match entry.len() {
+            12 => {
+                let next = u32_at_pos(entry, 0);
+                let next = if next == TRACE_CHAIN_END {
+                    None
+                } else {
+                    Some(next)
+                };
+                (next, &entry[4..])
+            }
+            8 => (None, entry),
+            _ => {
+                return Err(ForensicError::bad_format_str(
+                    "Invalid size for a trace chain entry",
+                ))
+            }
+        };
+        Ok(Self {
+            index,
+            next_index,
+            block_load_count: u32_at_pos(entry, 0),
+            flags: BlockFlags::from(entry[4]),
+            unknown: entry[5],
+            used_bitfield: entry[6],
+            prefetched_bitfield: entry[7],
+        })
+    }
+    pub fn to_trace(&self) -> Trace {
+        Trace {
+            flags: self.flags.clone(),
+            block_offset: self.block_load_count,
+            used_bitfield: self.used_bitfield,
+            prefetched_bitfield: self.prefetched_bitfield,
+        }
+    }
+}
+
+/// Inconsistency found while following the trace chain of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceChainAnomaly {
+    /// The next index points to an entry already visited in the same chain
+    Cycle { from: u32, to: u32 },
+    /// The first index or a next index is outside the trace chain array
+    Dangling { from: Option<u32>, to: u32 },
+    /// The next index is not the following entry of the array. Windows writes the chains contiguously.
+    NonContiguous { from: u32, to: u32 },
+    /// The number of entries in the chain differs from the trace count of the metric entry
+    LengthMismatch { expected: u32, found: u32 },
+}
+
+/// Trace chain of a file, in the order Windows follows it
+#[derive(Debug, Clone, Default)]
+pub struct TraceChain {
+    /// Index of the first entry, stored in the metric entry
+    pub first_index: u32,
+    /// Number of entries declared in the metric entry
+    pub declared_count: u32,
+    pub entries: Vec<TraceChainEntry>,
+    pub anomalies: Vec<TraceChainAnomaly>,
+}
+
+impl TraceChain {
+    /// Follows the chain from `first_index`. Versions 17, 23 and 26 use the next index of each entry until the end marker,
+    /// version 30 takes `count` consecutive entries.
+    pub fn follow(version: u32, array: &[TraceChainEntry], first_index: u32, count: u32) -> Self {
+        Self::follow_with(version, first_index, count, |i| {
+            array.get(i as usize).cloned()
+        })
+    }
+
+    /// Same as [`TraceChain::follow`] over the raw trace chain array, decoding each entry when it is reached
+    pub(crate) fn follow_bytes(
+        version: u32,
+        trace_array: &[u8],
+        entry_size: usize,
+        first_index: u32,
+        count: u32,
+    ) -> Self {
+        Self::follow_with(version, first_index, count, |i| {
+            let pos = (i as usize).checked_mul(entry_size)?;
+            let entry = trace_array.get(pos..pos + entry_size)?;
+            TraceChainEntry::from_bytes(i, entry).ok()
+        })
+    }
+
+    fn follow_with(
+        version: u32,
+        first_index: u32,
+        count: u32,
+        entry_at: impl Fn(u32) -> Option<TraceChainEntry>,
+    ) -> Self {
+        let mut chain = Self {
+            first_index,
+            declared_count: count,
+            ..Default::default()
+        };
+        if version < 30 {
+            chain.follow_links(&entry_at);
+        } else {
+            chain.follow_contiguous(&entry_at);
+        }
+        if chain.entries.len() != count as usize {
+            chain.anomalies.push(TraceChainAnomaly::LengthMismatch {
+                expected: count,
+                found: chain.entries.len() as u32,
+            });
+        }
+        chain
+    }
+
+    fn follow_links(&mut self, entry_at: &impl Fn(u32) -> Option<TraceChainEntry>) {
+        let mut visited = BTreeSet::new();
+        let mut from = None;
+        let mut current = self.first_index;
+        loop {
+            let entry = match entry_at(current) {
+                Some(v) => v,
+                None => {
+                    self.anomalies
+                        .push(TraceChainAnomaly::Dangling { from, to: current });
+                    return;
+                }
+            };
+            if !visited.insert(current) {
+                self.anomalies.push(TraceChainAnomaly::Cycle {
+                    from: from.unwrap_or(current),
+                    to: current,
+                });
+                return;
+            }
+            let next = entry.next_index;
+            self.entries.push(entry);
+            let next = match next {
+                Some(v) => v,
+                None => return,
+            };
+            if next != current.wrapping_add(1) {
+                self.anomalies.push(TraceChainAnomaly::NonContiguous {
+                    from: current,
+                    to: next,
+                });
+            }
+            from = Some(current);
+            current = next;
+        }
+    }
+
+    fn follow_contiguous(&mut self, entry_at: &impl Fn(u32) -> Option<TraceChainEntry>) {
+        let mut from = None;
+        for i in 0..self.declared_count {
+            let index = self.first_index.saturating_add(i);
+            match entry_at(index) {
+                Some(v) => self.entries.push(v),
+                None => {
+                    self.anomalies
+                        .push(TraceChainAnomaly::Dangling { from, to: index });
+                    return;
+                }
+            }
+            from = Some(index);
+        }
+    }
+
+    /// True when the chain ends with the end marker, has no cycles and matches the declared count
+    pub fn is_consistent(&self) -> bool {
+        self.anomalies
+            .iter()
+            .all(|v| matches!(v, TraceChainAnomaly::NonContiguous { .. }))
+    }
+
+    pub fn traces(&self) -> impl Iterator<Item = Trace> + '_ {
+        self.entries.iter().map(|v| v.to_trace())
+    }
+}
+
+/// Traces of the dependency whose chain starts at `index` (versions 17, 23 and 26). The file information is read again from the buffer.
+#[deprecated(note = "use MetricView::traces")]
 pub fn traces_for_dependency_v17(
     file_buffer: &[u8],
-    info: &PrefetchFileInformation,
+    _info: &PrefetchFileInformation,
     index: usize,
     size: usize,
 ) -> ForensicResult<Vec<Trace>> {
-    let end_pos = (info.trace_chain_offset + info.trace_chain_count * 12) as usize;
-    if end_pos > file_buffer.len() {
-        return Err(ForensicError::bad_format_str(
-            "The trace array position is greater than the file buffer length",
-        ));
-    }
-    let trace_array = &file_buffer[info.trace_chain_offset as usize..end_pos];
-    let mut traces = Vec::with_capacity(info.trace_chain_count as usize);
-    if (index + size) * 12 > trace_array.len() {
-        return Err(ForensicError::bad_format_str(
-            "The trace array position is greater than the file buffer length",
-        ));
-    }
-    for i in 0..size {
-        let pos = (index + i) * 12;
-        let entry = &trace_array[pos..];
-        let block_offset = u32_at_pos(entry, 4);
-        let flags = entry[8];
-        traces.push(Trace {
-            flags: flags.into(),
-            block_offset,
-            used_bitfield: entry[10],
-            prefetched_bitfield: entry[11],
-        });
-    }
-    Ok(traces)
+    Ok(PrefetchView::new(file_buffer)?
+        .trace_chain(index as u32, size as u32)
+        .traces()
+        .collect())
 }
 
+/// Every trace of the trace chain array (versions 17, 23 and 26). The file information is read again from the buffer.
+#[deprecated(note = "use PrefetchView::trace_chain_entries")]
 pub fn process_trace_chain_v17(
     file_buffer: &[u8],
-    info: &PrefetchFileInformation,
+    _info: &PrefetchFileInformation,
 ) -> ForensicResult<Vec<Trace>> {
-    let end_pos = (info.trace_chain_offset + info.trace_chain_count * 12) as usize;
-    if end_pos > file_buffer.len() {
-        return Err(ForensicError::bad_format_str(
-            "The trace array position is greater than the file buffer length",
-        ));
-    }
-    let trace_array = &file_buffer[info.trace_chain_offset as usize..end_pos];
-    let mut traces = Vec::with_capacity(info.trace_chain_count as usize);
-    for i in 0..(info.trace_chain_count as usize) {
-        let pos = i * 12;
-        let entry = &trace_array[pos..];
-        let block_offset = u32_at_pos(entry, 4);
-        let flags = entry[8];
-        traces.push(Trace {
-            flags: flags.into(),
-            block_offset,
-            used_bitfield: entry[10],
-            prefetched_bitfield: entry[11],
-        });
-    }
-    Ok(traces)
+    Ok(PrefetchView::new(file_buffer)?
+        .trace_chain_entries()
+        .map(|v| v.to_trace())
+        .collect())
 }
+
+/// Every trace of the trace chain array (version 30). The file information is read again from the buffer.
+#[deprecated(note = "use PrefetchView::trace_chain_entries")]
 pub fn process_trace_chain_v30(
     file_buffer: &[u8],
-    info: &PrefetchFileInformation,
+    _info: &PrefetchFileInformation,
 ) -> ForensicResult<Vec<Trace>> {
-    let end_pos = (info.trace_chain_offset + info.trace_chain_count * 8) as usize;
-    if end_pos > file_buffer.len() {
-        return Err(ForensicError::bad_format_str(
-            "The trace array position is greater than the file buffer length",
-        ));
-    }
-    let trace_array = &file_buffer[info.trace_chain_offset as usize..end_pos];
-    let mut traces = Vec::with_capacity(info.trace_chain_count as usize);
-    for i in 0..(info.trace_chain_count as usize) {
-        let pos = i * 8;
-        let entry = &trace_array[pos..];
-        let block_offset = u32_at_pos(entry, 0);
-        let flags = entry[4];
-        traces.push(Trace {
-            flags: flags.into(),
-            block_offset,
-            used_bitfield: entry[6],
-            prefetched_bitfield: entry[7],
-        });
-    }
-    Ok(traces)
+    Ok(PrefetchView::new(file_buffer)?
+        .trace_chain_entries()
+        .map(|v| v.to_trace())
+        .collect())
 }
+
+/// Traces of the dependency that starts at `index` (version 30). The file information is read again from the buffer.
+#[deprecated(note = "use MetricView::traces")]
 pub fn traces_for_dependency_v30(
     file_buffer: &[u8],
-    info: &PrefetchFileInformation,
+    _info: &PrefetchFileInformation,
     index: usize,
     size: usize,
 ) -> ForensicResult<Vec<Trace>> {
-    let end_pos = (info.trace_chain_offset + info.trace_chain_count * 8) as usize;
-    if end_pos > file_buffer.len() {
-        return Err(ForensicError::bad_format_str(
-            "The trace array position is greater than the file buffer length",
-        ));
-    }
-    let trace_array = &file_buffer[info.trace_chain_offset as usize..end_pos];
-    let mut traces = Vec::with_capacity(info.trace_chain_count as usize);
-    if (index + size) * 8 > trace_array.len() {
-        return Err(ForensicError::bad_format_str(
-            "The trace array position is greater than the file buffer length",
-        ));
-    }
-    for i in 0..size {
-        let pos = (index + i) * 8;
-        let entry = &trace_array[pos..];
-        let block_offset = u32_at_pos(entry, 0);
-        let flags = entry[4].into();
-        traces.push(Trace {
-            flags,
-            block_offset,
-            used_bitfield: entry[6],
-            prefetched_bitfield: entry[7],
-        });
-    }
-    Ok(traces)
+    Ok(PrefetchView::new(file_buffer)?
+        .trace_chain(index as u32, size as u32)
+        .traces()
+        .collect())
+}
+
+#[test]
+fn should_follow_trace_chain_links() {
+    let entry = |index, next_index| TraceChainEntry {
+        index,
+        next_index,
+        block_load_count: index * 10,
+        ..Default::default()
+    };
+    let array = vec![
+        entry(0, Some(2)),
+        entry(1, None),
+        entry(2, Some(1)),
+        entry(3, Some(4)),
+        entry(4, Some(3)),
+        entry(5, Some(9)),
+    ];
+    let chain = TraceChain::follow(26, &array, 0, 3);
+    assert!(chain.is_consistent());
+    assert_eq!(
+        vec![0, 2, 1],
+        chain.entries.iter().map(|v| v.index).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![
+            TraceChainAnomaly::NonContiguous { from: 0, to: 2 },
+            TraceChainAnomaly::NonContiguous { from: 2, to: 1 }
+        ],
+        chain.anomalies
+    );
+    let chain = TraceChain::follow(26, &array, 3, 2);
+    assert!(!chain.is_consistent());
+    assert_eq!(2, chain.entries.len());
+    assert!(chain
+        .anomalies
+        .contains(&TraceChainAnomaly::Cycle { from: 4, to: 3 }));
+    let chain = TraceChain::follow(26, &array, 5, 1);
+    assert_eq!(1, chain.entries.len());
+    assert!(chain.anomalies.contains(&TraceChainAnomaly::Dangling {
+        from: Some(5),
+        to: 9
+    }));
+    let chain = TraceChain::follow(30, &array[..2], 1, 2);
+    assert_eq!(
+        vec![
+            TraceChainAnomaly::Dangling {
+                from: Some(1),
+                to: 2
+            },
+            TraceChainAnomaly::LengthMismatch {
+                expected: 2,
+                found: 1
+            }
+        ],
+        chain.anomalies
+    );
+}
+
+#[test]
+fn should_decode_every_field_of_trace_chain_entry() {
+    let entry = TraceChainEntry::from_bytes(
+        7,
+        &[
+            0xff, 0xff, 0xff, 0xff, 0x00, 0x04, 0, 0, 0x1a, 8, 0xe0, 0x20,
+        ],
+    )
+    .unwrap();
+    assert_eq!(None, entry.next_index);
+    assert_eq!(1024, entry.block_load_count);
+    assert_eq!(0x1a, entry.flags.bits());
+    assert_eq!(8, entry.unknown);
+    assert_eq!(0xe0, entry.used_bitfield);
+    assert_eq!(0x20, entry.prefetched_bitfield);
+    let entry = TraceChainEntry::from_bytes(7, &[0x00, 0x04, 0, 0, 0x02, 1, 3, 1]).unwrap();
+    assert_eq!(None, entry.next_index);
+    assert_eq!(1, entry.unknown);
+    assert!(TraceChainEntry::from_bytes(7, &[0; 10]).is_err());
 }
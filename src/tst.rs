@@ -1,21 +1,78 @@
 use forensic_rs::{
     core::fs::{ChRootFileSystem, StdVirtualFS},
+    err::ForensicResult,
     traits::{
         forensic::{IntoActivity, IntoTimeline},
-        vfs::VirtualFileSystem,
+        vfs::{VFileType, VMetadata, VirtualFile, VirtualFileSystem},
     },
     utils::time::Filetime,
 };
-use std::path::Path;
+use std::{
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
 
 use crate::prefetch::{
-    read_prefetch_file_compressed, read_prefetch_file_no_compressed, read_prefetch_form_fs,
+    read_prefetch_bytes, read_prefetch_file, read_prefetch_file_compressed,
+    read_prefetch_file_no_compressed, read_prefetch_file_with_provenance, write_mam,
+};
+use crate::{
+    attribution::{ProfileList, PROFILE_LIST_KEY},
+    category::PathCategory,
+    common::{u32_at_pos, u64_at_pos, utf16_from_bytes, NtfsFile},
+    common::{PrefetchActivityIterator, FIRST_RUN_TIME_CONTEXT},
+    error::ParseStage,
+    executable::{ExecutableConfidence, ExecutableNameMatch},
+    first_run::FirstRunSource,
+    inventory::{VolumeDeviceType, VolumeInventory},
+    mft::MftReferenceStatus,
+    mounted_image::{MountedImageConfidence, MountedImageDetector, MountedImageIndicator},
+    provenance::ByteRange,
+    removable::{RemovableDeviceHistory, RemovableMediaDetector, EMDMGMT_KEY},
+    scan::{scan_folder, scan_prefetch_report, SkippedEntry, SkippedKind},
+    serialize::serialize_prefetch,
+    trace::TraceChainAnomaly,
+    view::PrefetchView,
 };
 
+/// In memory file used to parse generated prefetch files
+pub(crate) struct MemoryFile(Cursor<Vec<u8>>);
+
+impl MemoryFile {
+    pub fn boxed(data: Vec<u8>) -> Box<dyn VirtualFile> {
+        Box::new(Self(Cursor::new(data)))
+    }
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl VirtualFile for MemoryFile {
+    fn metadata(&self) -> ForensicResult<VMetadata> {
+        Ok(VMetadata {
+            created: None,
+            accessed: None,
+            modified: None,
+            file_type: VFileType::File,
+            size: self.0.get_ref().len() as u64,
+        })
+    }
+}
+
 #[test]
 fn should_parse_all_prefetchs_from_fs() {
     let mut fs = ChRootFileSystem::new("./artifacts/17", Box::new(StdVirtualFS::new()));
-    read_prefetch_form_fs(&mut fs).expect("Must read all prefetch from filesystem");
+    let report = scan_prefetch_report(&mut fs).expect("Must read all prefetch from filesystem");
+    assert!(report.failures.is_empty());
 }
 
 #[test]
@@ -111,6 +168,1109 @@ fn should_parse_prefetch_v30_cmd() {
 #[ignore]
 fn should_parse_current_prefetches() {
     let mut fs = StdVirtualFS::new();
-    let _pref = read_prefetch_form_fs(&mut fs).expect("Must read all prefetch from filesystem");
+    let _pref = scan_prefetch_report(&mut fs)
+        .expect("Must read all prefetch from filesystem")
+        .prefetches;
     //println!("{:?}", pref);
 }
+
+#[test]
+fn should_verify_prefetch_path_hash() {
+    let mut fs = StdVirtualFS::new();
+    for (path, device_path) in [
+        (
+            "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf",
+            r"\DEVICE\HARDDISKVOLUME1\WINDOWS\SYSTEM32\CMD.EXE",
+        ),
+        (
+            "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf",
+            r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\NOTEPAD.EXE",
+        ),
+        (
+            "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf",
+            r"\DEVICE\HARDDISKVOLUME2\WINDOWS\SYSTEM32\CMD.EXE",
+        ),
+        (
+            "./artifacts/30/C/Windows/Prefetch/CMD.EXE-6D6290C5.pf",
+            r"\DEVICE\HARDDISKVOLUME3\WINDOWS\SYSWOW64\CMD.EXE",
+        ),
+    ] {
+        let file = fs.open(Path::new(path)).unwrap();
+        let name = Path::new(path).file_name().unwrap().to_str().unwrap();
+        let pref = read_prefetch_file(name, file).unwrap();
+        let check = pref.verify_hash();
+        assert!(check.matches());
+        assert_eq!(Some(device_path), check.device_path.as_deref());
+        let parsed = pref.hash_check.as_ref().unwrap();
+        assert!(pref.executable_index.is_some());
+        assert_eq!(pref.resolve_executable().index(), pref.executable_index);
+        assert_eq!(check.device_path, parsed.device_path);
+    }
+}
+
+#[test]
+fn should_parse_written_mam_prefetch() {
+    let mut fs = StdVirtualFS::new();
+    let decompressed = fs
+        .read_all(Path::new(
+            "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf",
+        ))
+        .unwrap();
+    let original = read_prefetch_file(
+        "NOTEPAD.EXE-D8414F97.pf",
+        MemoryFile::boxed(decompressed.clone()),
+    )
+    .unwrap();
+    for with_crc in [false, true] {
+        let compressed = write_mam(&decompressed, with_crc);
+        let pref =
+            read_prefetch_file("NOTEPAD.EXE-D8414F97.pf", MemoryFile::boxed(compressed)).unwrap();
+        assert_eq!(original.name, pref.name);
+        assert_eq!(original.run_count, pref.run_count);
+        assert_eq!(original.metrics.len(), pref.metrics.len());
+    }
+    let mut compressed = write_mam(&decompressed, true);
+    let last = compressed.len() - 1;
+    compressed[last] ^= 0xff;
+    read_prefetch_file("NOTEPAD.EXE-D8414F97.pf", MemoryFile::boxed(compressed))
+        .expect_err("The CRC must not match");
+}
+
+#[test]
+fn should_serialize_prefetch_in_every_version() {
+    let mut fs = StdVirtualFS::new();
+    let file = fs
+        .open(Path::new(
+            "./artifacts/30/C/Windows/Prefetch/CMD.EXE-D269B812.pf",
+        ))
+        .unwrap();
+    let original = read_prefetch_file("CMD.EXE-D269B812.pf", file).unwrap();
+    for version in [17, 23, 26, 30] {
+        let buffer = serialize_prefetch(&original, version).unwrap();
+        let pref =
+            read_prefetch_file("CMD.EXE-D269B812.pf", MemoryFile::boxed(buffer.clone())).unwrap();
+        assert_eq!(version, pref.version);
+        assert_eq!(original.name, pref.name);
+        assert_eq!(original.hash, pref.hash);
+        assert_eq!(original.run_count, pref.run_count);
+        if version >= 26 {
+            assert_eq!(original.last_run_times, pref.last_run_times);
+        } else {
+            assert_eq!(original.last_run_times[0], pref.last_run_times[0]);
+        }
+        assert_eq!(original.metrics.len(), pref.metrics.len());
+        for (a, b) in original.metrics.iter().zip(pref.metrics.iter()) {
+            assert_eq!(a.file, b.file);
+            assert_eq!(a.flags.bits(), b.flags.bits());
+            if version != 17 {
+                assert_eq!(a.file_reference(), b.file_reference());
+            }
+            assert_eq!(a.trace_chain.entries.len(), b.trace_chain.entries.len());
+            for (ta, tb) in a.trace_chain.entries.iter().zip(&b.trace_chain.entries) {
+                assert_eq!(ta.block_load_count, tb.block_load_count);
+                assert_eq!(ta.flags.bits(), tb.flags.bits());
+                assert_eq!(ta.used_bitfield, tb.used_bitfield);
+                assert_eq!(ta.prefetched_bitfield, tb.prefetched_bitfield);
+                assert_eq!(ta.unknown, tb.unknown);
+            }
+        }
+        assert_eq!(original.volume.len(), pref.volume.len());
+        for (a, b) in original.volume.iter().zip(pref.volume.iter()) {
+            assert_eq!(a.device_path, b.device_path);
+            assert_eq!(a.serial_number, b.serial_number);
+            assert_eq!(a.creation_time, b.creation_time);
+            assert_eq!(a.directory_strings, b.directory_strings);
+            assert_eq!(a.file_references.len(), b.file_references.len());
+        }
+        if version == 30 {
+            let compressed = write_mam(&buffer, false);
+            read_prefetch_file("CMD.EXE-D269B812.pf", MemoryFile::boxed(compressed)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn should_keep_header_and_file_information() {
+    let mut fs = StdVirtualFS::new();
+    let file = fs
+        .open(Path::new(
+            "./artifacts/30/C/Windows/Prefetch/CMD.EXE-6D6290C5.pf",
+        ))
+        .unwrap();
+    let pref = read_prefetch_file("CMD.EXE-6D6290C5.pf", file).unwrap();
+    assert_eq!(b"SCCA", &pref.header.signature);
+    assert_eq!(30, pref.header.version);
+    assert_eq!(17, pref.header.unknown_8);
+    assert_eq!("CMD.EXE", pref.header.executable_name);
+    assert_eq!(0x6D6290C5, pref.header.hash);
+    assert_eq!(212, pref.file_information.size);
+    assert_eq!(8, pref.file_information.last_run_time_slots.len());
+    assert_eq!(100, pref.file_information.hash_string_size);
+    assert_eq!(
+        Some(r"\DEVICE\HARDDISKVOLUME3\WINDOWS\SYSWOW64\CMD.EXE"),
+        pref.file_information.hash_string.as_deref()
+    );
+    assert!(pref
+        .file_information
+        .unknown
+        .iter()
+        .all(|v| v.offset >= 84 + 36 && v.offset < 84 + 212));
+
+    let buffer = serialize_prefetch(&pref, 30).unwrap();
+    let written =
+        read_prefetch_file("CMD.EXE-6D6290C5.pf", MemoryFile::boxed(buffer.clone())).unwrap();
+    assert_eq!(buffer.len(), written.header.file_size as usize);
+    assert_eq!(
+        pref.file_information.hash_string,
+        written.file_information.hash_string
+    );
+
+    let file = fs
+        .open(Path::new(
+            "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf",
+        ))
+        .unwrap();
+    let pref = read_prefetch_file("CMD.EXE-087B4001.pf", file).unwrap();
+    assert_eq!(15, pref.header.unknown_8);
+    assert_eq!(68, pref.file_information.size);
+    assert!(pref.file_information.hash_string.is_none());
+}
+
+#[test]
+fn should_record_provenance_of_every_field() {
+    let mut fs = StdVirtualFS::new();
+    for path in [
+        "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf",
+        "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf",
+        "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf",
+        "./artifacts/30/C/Windows/Prefetch/CMD.EXE-D269B812.pf",
+    ] {
+        let raw = fs.read_all(Path::new(path)).unwrap();
+        let name = path.rsplit('/').next().unwrap();
+        let file = fs.open(Path::new(path)).unwrap();
+        let pref = read_prefetch_file_with_provenance(name, file).unwrap();
+        let compressed = raw.starts_with(b"MAM");
+        let decompressed = if compressed {
+            let mut decompressed = Vec::with_capacity(u32_at_pos(&raw, 4) as usize);
+            crate::decompress::decompress(
+                &raw[8..],
+                &mut decompressed,
+                crate::decompress::CompressionAlgorithm::CompressionFormatXpressHuff,
+            )
+            .unwrap();
+            decompressed
+        } else {
+            raw.clone()
+        };
+        let provenance = pref.provenance.as_ref().unwrap();
+        assert_eq!(compressed, provenance.compression.is_some());
+        let text = |range: &ByteRange| utf16_from_bytes(&decompressed[range.offset..range.end()]);
+        assert_eq!(pref.name, text(&provenance.executable_name));
+        assert_eq!(
+            pref.run_count,
+            u32_at_pos(&decompressed, provenance.run_count.offset)
+        );
+        assert_eq!(pref.last_run_times.len(), provenance.last_run_times.len());
+        for (time, range) in pref.last_run_times.iter().zip(&provenance.last_run_times) {
+            assert_eq!(time.filetime(), u64_at_pos(&decompressed, range.offset));
+        }
+        assert_eq!(pref.metrics.len(), provenance.metrics.len());
+        for (metric, range) in pref.metrics.iter().zip(&provenance.metrics) {
+            assert_eq!(metric.file, text(&range.file_name));
+            assert_eq!(metric.trace_chain.entries.len(), range.traces.len());
+            for (trace, range) in metric.traces().zip(&range.traces) {
+                let pos = range.offset + if range.length == 12 { 4 } else { 0 };
+                assert_eq!(trace.block_offset, u32_at_pos(&decompressed, pos));
+            }
+        }
+        for (volume, range) in pref.volume.iter().zip(&provenance.volumes) {
+            assert_eq!(volume.device_path, text(&range.device_path));
+            assert_eq!(
+                volume.serial_number,
+                u32_at_pos(&decompressed, range.serial_number.offset)
+            );
+            for (directory, range) in volume
+                .directory_strings
+                .iter()
+                .zip(&range.directory_strings)
+            {
+                assert_eq!(directory, &text(range));
+            }
+        }
+        let mapped = provenance.compressed_range(&provenance.run_count).unwrap();
+        if compressed {
+            assert!(mapped.offset >= 8 && mapped.end() <= raw.len());
+        } else {
+            assert_eq!(provenance.run_count, mapped);
+        }
+    }
+}
+
+#[test]
+fn should_decode_prefetch_view_lazily() {
+    let mut fs = StdVirtualFS::new();
+    let path = "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf";
+    let buffer = fs.read_all(Path::new(path)).unwrap();
+    let pref =
+        read_prefetch_file("CMD.EXE-4A81B364.pf", fs.open(Path::new(path)).unwrap()).unwrap();
+    let view = PrefetchView::new(&buffer).unwrap();
+    assert_eq!(pref.version, view.version());
+    assert_eq!(pref.hash, view.hash());
+    assert_eq!(pref.run_count, view.run_count());
+    assert!(view.executable_name().eq_str(&pref.name));
+    assert_eq!(
+        pref.last_run_times,
+        view.last_run_times().collect::<Vec<_>>()
+    );
+    assert_eq!(pref.metrics.len(), view.metric_count());
+    for (metric, entry) in pref.metrics.iter().zip(view.metrics()) {
+        let entry = entry.unwrap();
+        assert!(entry.file_name().eq_str(&metric.file));
+        assert_eq!(metric.trace_chain.entries.len(), entry.trace_count());
+        assert_eq!(
+            metric.traces().map(|v| v.block_offset).collect::<Vec<_>>(),
+            entry.traces().map(|v| v.block_offset).collect::<Vec<_>>()
+        );
+    }
+    for (volume, entry) in pref.volume.iter().zip(view.volumes()) {
+        let entry = entry.unwrap();
+        assert_eq!(volume.device_path, entry.device_path().to_string());
+        assert_eq!(
+            volume.file_references.len(),
+            entry.file_references().count()
+        );
+        let directories: Vec<String> = entry
+            .directory_strings()
+            .map(|v| v.unwrap().to_string())
+            .collect();
+        assert_eq!(volume.directory_strings, directories);
+    }
+
+    // A file name outside the strings table is an error of that metric, not a panic
+    let mut broken = buffer.clone();
+    let metrics_offset = u32_at_pos(&broken, 84) as usize;
+    broken[metrics_offset + 12..metrics_offset + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+    let view = PrefetchView::new(&broken).unwrap();
+    assert!(view.metrics().next().unwrap().is_err());
+    assert!(view.metrics().nth(1).unwrap().is_ok());
+    assert!(PrefetchView::new(&broken[0..200]).is_err());
+
+    // A linked chain is not required to fit after its first index: the declared count only produces an anomaly
+    let mut linked = buffer.clone();
+    let trace_count = view.trace_count() as u32;
+    linked[metrics_offset + 36..metrics_offset + 40].copy_from_slice(&trace_count.to_le_bytes());
+    let view = PrefetchView::new(&linked).unwrap();
+    let metric = view.metrics().nth(1).unwrap().unwrap();
+    assert_ne!(0, metric.trace_index());
+    assert!(matches!(
+        metric.trace_chain().anomalies[..],
+        [TraceChainAnomaly::LengthMismatch { .. }]
+    ));
+
+    // The lazy walk finds the same entries as the full trace chain in every version
+    for path in [
+        "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf",
+        "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf",
+        "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf",
+    ] {
+        let buffer = fs.read_all(Path::new(path)).unwrap();
+        let view = PrefetchView::new(&buffer).unwrap();
+        for metric in view.metrics() {
+            let metric = metric.unwrap();
+            let chain = metric.trace_chain();
+            assert!(metric
+                .trace_entries()
+                .map(|v| v.index)
+                .eq(chain.entries.iter().map(|v| v.index)));
+        }
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn should_keep_deprecated_parsers_as_view_wrappers() {
+    let mut fs = StdVirtualFS::new();
+    let path = "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf";
+    let buffer = fs.read_all(Path::new(path)).unwrap();
+    let pref =
+        read_prefetch_file("CMD.EXE-087B4001.pf", fs.open(Path::new(path)).unwrap()).unwrap();
+    let info = &pref.file_information;
+    let metrics = crate::metrics::metrics_array_17(&buffer, info).unwrap();
+    assert_eq!(pref.metrics.len(), metrics.len());
+    assert_eq!(pref.metrics[0].file, metrics[0].file);
+    let volumes = crate::volume::volume_info_17(&buffer, info).unwrap();
+    assert_eq!(
+        pref.volume[0].directory_strings,
+        volumes[0].directory_strings
+    );
+    let all_traces = crate::trace::process_trace_chain_v17(&buffer, info).unwrap();
+    assert_eq!(info.trace_chain_count as usize, all_traces.len());
+    let first = &pref.metrics[0].trace_chain;
+    let traces = crate::trace::traces_for_dependency_v17(
+        &buffer,
+        info,
+        first.first_index as usize,
+        first.declared_count as usize,
+    )
+    .unwrap();
+    assert_eq!(first.entries.len(), traces.len());
+}
+
+/// Prefetch folder with a valid file, a corrupted file and a file that is not a prefetch
+fn mixed_prefetch_folder(name: &str) -> std::path::PathBuf {
+    let folder = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::copy(
+        "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf",
+        folder.join("NOTEPAD.EXE-D8414F97.pf"),
+    )
+    .unwrap();
+    std::fs::write(folder.join("BROKEN.EXE-12345678.pf"), b"SCCA broken").unwrap();
+    std::fs::write(folder.join("Layout.ini"), b"").unwrap();
+    folder
+}
+
+#[test]
+fn should_scan_prefetch_folder_file_by_file() {
+    let folder = mixed_prefetch_folder("frnsc_prefetch_scan");
+    let mut fs = StdVirtualFS::new();
+    let mut scanned: Vec<_> = scan_folder(&mut fs, &folder).unwrap().collect();
+    scanned.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    assert_eq!(2, scanned.len());
+    assert_eq!("BROKEN.EXE-12345678.pf", scanned[0].file_name);
+    assert!(scanned[0].result.is_err());
+    assert_eq!("NOTEPAD.EXE-D8414F97.pf", scanned[1].file_name);
+    assert!(scanned[1].result.is_ok());
+    let _ = std::fs::remove_dir_all(&folder);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn should_scan_prefetch_folder_in_parallel() {
+    let folder = mixed_prefetch_folder("frnsc_prefetch_scan_parallel");
+    let mut fs = StdVirtualFS::new();
+    let mut scanned: Vec<_> = scan_folder(&mut fs, &folder).unwrap().parallel(2).collect();
+    scanned.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    assert_eq!(2, scanned.len());
+    assert!(scanned[0].result.is_err());
+    assert_eq!("NOTEPAD.EXE", scanned[1].result.as_ref().unwrap().name);
+    assert!(scanned[1].result.as_ref().unwrap().file_times.is_some());
+    let _ = std::fs::remove_dir_all(&folder);
+}
+
+#[test]
+fn should_report_failures_and_skipped_entries() {
+    let root = std::env::temp_dir().join("frnsc_prefetch_report");
+    let _ = std::fs::remove_dir_all(&root);
+    let folder = root.join("C").join("Windows").join("Prefetch");
+    std::fs::create_dir_all(folder.join("ReadyBoot")).unwrap();
+    let decompressed =
+        std::fs::read("./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf").unwrap();
+    std::fs::write(folder.join("NOTEPAD.EXE-D8414F97.pf"), &decompressed).unwrap();
+    let mut bad_crc = write_mam(&decompressed, true);
+    bad_crc[8] ^= 0xff;
+    std::fs::write(folder.join("BADCRC.EXE-11111111.pf"), bad_crc).unwrap();
+    std::fs::write(folder.join("SHORT.EXE-22222222.pf"), b"SCCA broken").unwrap();
+    let mut bad_metrics = decompressed.clone();
+    bad_metrics[84..88].copy_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(folder.join("METRICS.EXE-33333333.pf"), bad_metrics).unwrap();
+    std::fs::write(folder.join("Layout.ini"), b"").unwrap();
+
+    let mut fs = ChRootFileSystem::new(&root, Box::new(StdVirtualFS::new()));
+    let report = scan_prefetch_report(&mut fs).unwrap();
+    assert_eq!(1, report.prefetches.len());
+    let stage = |name: &str| {
+        report
+            .failures
+            .iter()
+            .find(|v| v.file_name == name)
+            .map(|v| v.error.stage)
+    };
+    assert_eq!(Some(ParseStage::Crc), stage("BADCRC.EXE-11111111.pf"));
+    assert_eq!(Some(ParseStage::Header), stage("SHORT.EXE-22222222.pf"));
+    assert_eq!(Some(ParseStage::Metrics), stage("METRICS.EXE-33333333.pf"));
+    assert!(report
+        .failures
+        .iter()
+        .all(|v| v.error.kind() == "bad_format"));
+    let mut skipped = report.skipped.clone();
+    skipped.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(
+        vec![
+            SkippedEntry {
+                name: "Layout.ini".into(),
+                kind: SkippedKind::NotPrefetch
+            },
+            SkippedEntry {
+                name: "ReadyBoot".into(),
+                kind: SkippedKind::Directory
+            }
+        ],
+        skipped
+    );
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn should_follow_trace_chains() {
+    let mut fs = StdVirtualFS::new();
+    for path in [
+        "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf",
+        "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf",
+        "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf",
+        "./artifacts/30/C/Windows/Prefetch/CMD.EXE-6D6290C5.pf",
+    ] {
+        let file = fs.open(Path::new(path)).unwrap();
+        let pref = read_prefetch_file(path, file).unwrap();
+        let mut expected_index = 0;
+        for metric in &pref.metrics {
+            let chain = &metric.trace_chain;
+            assert!(chain.anomalies.is_empty(), "{}", metric.file);
+            assert_eq!(expected_index, chain.first_index);
+            assert_eq!(metric.traces().count(), chain.entries.len());
+            for (i, entry) in chain.entries.iter().enumerate() {
+                assert_eq!(expected_index + i as u32, entry.index);
+                let next = if pref.version < 30 && i + 1 < chain.entries.len() {
+                    Some(entry.index + 1)
+                } else {
+                    None
+                };
+                assert_eq!(next, entry.next_index);
+            }
+            expected_index += chain.declared_count;
+        }
+    }
+    let path = "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf";
+    let mut buffer = fs.read_all(Path::new(path)).unwrap();
+    // Second entry of the first chain points back to the first one
+    let trace_chain_offset = u32_at_pos(&buffer, 84 + 8) as usize;
+    buffer[trace_chain_offset + 12..trace_chain_offset + 16].copy_from_slice(&0u32.to_le_bytes());
+    let pref = read_prefetch_bytes(path, &buffer).unwrap();
+    let chain = &pref.metrics[0].trace_chain;
+    assert_eq!(2, chain.entries.len());
+    assert!(!chain.is_consistent());
+    assert!(chain
+        .anomalies
+        .contains(&TraceChainAnomaly::Cycle { from: 1, to: 0 }));
+    assert!(pref.metrics[1].trace_chain.is_consistent());
+}
+
+/// Builds a 1024 bytes $MFT record with a $FILE_NAME attribute and the update sequence array
+fn mft_record(seq_number: u16, flags: u16, parent: u64, name: &str) -> Vec<u8> {
+    let mut record = vec![0u8; 1024];
+    record[0..4].copy_from_slice(b"FILE");
+    record[0x04..0x06].copy_from_slice(&0x30u16.to_le_bytes());
+    record[0x06..0x08].copy_from_slice(&3u16.to_le_bytes());
+    record[0x10..0x12].copy_from_slice(&seq_number.to_le_bytes());
+    record[0x14..0x16].copy_from_slice(&0x38u16.to_le_bytes());
+    record[0x16..0x18].copy_from_slice(&flags.to_le_bytes());
+    record[0x1C..0x20].copy_from_slice(&1024u32.to_le_bytes());
+    let name: Vec<u8> = name.encode_utf16().flat_map(|v| v.to_le_bytes()).collect();
+    let content_size = 0x42 + name.len();
+    let length = (0x18 + content_size + 7) & !7;
+    let attribute = &mut record[0x38..0x38 + length];
+    attribute[0..4].copy_from_slice(&0x30u32.to_le_bytes());
+    attribute[4..8].copy_from_slice(&(length as u32).to_le_bytes());
+    attribute[0x10..0x14].copy_from_slice(&(content_size as u32).to_le_bytes());
+    attribute[0x14..0x16].copy_from_slice(&0x18u16.to_le_bytes());
+    let content = &mut attribute[0x18..];
+    content[0..8].copy_from_slice(&parent.to_le_bytes());
+    content[0x40] = (name.len() / 2) as u8;
+    content[0x41] = 1;
+    content[0x42..0x42 + name.len()].copy_from_slice(&name);
+    record[0x38 + length..0x3C + length].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+    // Update sequence number 0x0101 and the original last bytes of each sector
+    record[0x30..0x36].copy_from_slice(&[1, 1, 0, 0, 0, 0]);
+    record[510..512].copy_from_slice(&[1, 1]);
+    record[1022..1024].copy_from_slice(&[1, 1]);
+    record
+}
+
+#[test]
+fn should_resolve_file_references_in_mft() {
+    let mut fs = StdVirtualFS::new();
+    let path = "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf";
+    let mut pref = read_prefetch_file(path, fs.open(Path::new(path)).unwrap()).unwrap();
+    pref.metrics.truncate(3);
+    let windows = pref.volume[0].file_references[32].clone();
+    let reference = |v: &NtfsFile| v.to_reference();
+    let root = 5 | (5 << 48);
+    let system32 = NtfsFile {
+        mft_entry: 100,
+        seq_number: 1,
+    };
+    let mut records: Vec<Vec<u8>> = (0..25700).map(|_| vec![0u8; 1024]).collect();
+    records[0] = mft_record(1, 1, root, "$MFT");
+    records[5] = mft_record(5, 3, root, ".");
+    records[windows.mft_entry as usize] = mft_record(windows.seq_number, 3, root, "Windows");
+    records[100] = mft_record(1, 3, reference(&windows), "System32");
+    // NTDLL.DLL is present, KERNEL32.DLL was deleted and the entry of APISETSCHEMA.DLL was reused
+    records[25654] = mft_record(1, 1, reference(&system32), "ntdll.dll");
+    records[25104] = mft_record(2, 0, reference(&system32), "kernel32.dll");
+    records[23783] = mft_record(4, 1, reference(&system32), "other.dll");
+    let mft = MemoryFile::boxed(records.concat());
+    let mut resolver = crate::mft::MftResolver::new(mft).unwrap();
+    assert_eq!(25700, resolver.record_count());
+    let volumes = pref.volume_references();
+    let resolved = resolver.resolve_volume(&volumes[0]).unwrap();
+    assert_eq!(3 + volumes[0].unclaimed.len(), resolved.len());
+    assert_eq!(MftReferenceStatus::Present, resolved[0].status);
+    assert_eq!(
+        Some(r"\Windows\System32\ntdll.dll"),
+        resolved[0].current_path.as_deref()
+    );
+    assert_eq!(Some(true), resolved[0].path_matches);
+    assert_eq!(
+        MftReferenceStatus::Deleted {
+            current_seq_number: 2
+        },
+        resolved[1].status
+    );
+    assert_eq!(
+        MftReferenceStatus::Replaced {
+            current_seq_number: 4
+        },
+        resolved[2].status
+    );
+    assert_eq!(Some(false), resolved[2].path_matches);
+    let windows = resolved.iter().find(|v| v.reference == windows).unwrap();
+    assert!(windows.is_directory && windows.metric.is_none());
+    assert_eq!(Some(r"\Windows"), windows.current_path.as_deref());
+    assert!(resolved
+        .iter()
+        .filter(|v| v.metric.is_none() && v.reference != windows.reference)
+        .all(|v| v.status == MftReferenceStatus::Missing));
+}
+
+#[test]
+fn should_link_paths_to_their_volume() {
+    let mut fs = StdVirtualFS::new();
+    for path in [
+        "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf",
+        "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf",
+        "./artifacts/30/C/Windows/Prefetch/CMD.EXE-D269B812.pf",
+    ] {
+        let pref = read_prefetch_file(path, fs.open(Path::new(path)).unwrap()).unwrap();
+        for metric in &pref.metrics {
+            let volume = pref.volume_of(&metric.file).unwrap();
+            let volume_path = metric.volume_path().unwrap();
+            assert!(volume_path.is_in(volume));
+            assert!(metric.relative_path().unwrap().starts_with('\\'));
+        }
+        for volume in &pref.volume {
+            for directory in &volume.directory_strings {
+                assert_eq!(
+                    volume.device_path,
+                    pref.volume_of(directory).unwrap().device_path
+                );
+            }
+        }
+    }
+    let path = "./artifacts/30/C/Windows/Prefetch/CMD.EXE-D269B812.pf";
+    let pref = read_prefetch_file(path, fs.open(Path::new(path)).unwrap()).unwrap();
+    let clink = pref
+        .metrics
+        .iter()
+        .find(|v| v.file.ends_with("CLINK_DLL_X64.DLL"))
+        .unwrap();
+    let volume_path = clink.volume_path().unwrap();
+    assert_eq!(Some(0x66f451bc), volume_path.serial_number());
+    assert_eq!(
+        Some(Filetime::new(0x01d12173f395296c)),
+        volume_path.creation_time()
+    );
+    assert_eq!(
+        r"\CMDER129\VENDOR\CLINK\CLINK_DLL_X64.DLL",
+        volume_path.relative
+    );
+}
+
+#[test]
+fn should_build_volume_inventory() {
+    let mut prefetches = Vec::new();
+    for version in ["17", "23", "26", "30"] {
+        let mut fs = ChRootFileSystem::new(
+            format!("./artifacts/{}", version),
+            Box::new(StdVirtualFS::new()),
+        );
+        prefetches.extend(scan_prefetch_report(&mut fs).unwrap().prefetches);
+    }
+    let inventory = VolumeInventory::from_prefetches(&prefetches);
+    // CMD.EXE and RUST_OUT.EXE share the volume 22690e22
+    assert_eq!(7, inventory.volumes.len());
+    assert_eq!(3, inventory.of_type(VolumeDeviceType::HardDisk).count());
+    let v17 = inventory.volume(0x24cb074b, 0x01ce1d78c9fc1046).unwrap();
+    assert_eq!(
+        vec![r"\DEVICE\HARDDISKVOLUME1".to_string()],
+        v17.device_paths
+    );
+    assert_eq!(1, v17.executables.len());
+    assert!(v17.executables[0].path.ends_with(r"\CMD.EXE"));
+    assert_eq!(
+        v17.last_run.unwrap().filetime(),
+        prefetches[0].last_run_times[0].filetime()
+    );
+    // Volume only used to load files: no executable ran from it
+    let clink = inventory.volume(0x66f451bc, 0x01d12173f395296c).unwrap();
+    assert!(!clink.has_executables());
+    assert!(clink.first_run.is_none());
+    let shared = inventory.volume(0x22690e22, 0x01d861d36906cd20).unwrap();
+    assert_eq!(2, shared.prefetch_count);
+    let first = shared.first_run.unwrap().filetime();
+    for executable in &shared.executables {
+        assert!(executable.first_run.unwrap().filetime() >= first);
+        assert!(executable.last_run.unwrap().filetime() <= shared.last_run.unwrap().filetime());
+    }
+    // A carved file keeps the name it was parsed with
+    let mut fs = StdVirtualFS::new();
+    let path = "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf";
+    let carved = read_prefetch_file("carved_00042.pf", fs.open(Path::new(path)).unwrap()).unwrap();
+    assert_eq!("carved_00042.pf", carved.file_name);
+    let inventory = VolumeInventory::from_prefetches(std::slice::from_ref(&carved));
+    let files: Vec<_> = inventory
+        .volumes
+        .iter()
+        .flat_map(|v| v.executables.iter())
+        .flat_map(|v| v.prefetch_files.iter())
+        .collect();
+    assert_eq!(vec!["carved_00042.pf"], files);
+}
+
+#[test]
+fn should_detect_files_loaded_from_removable_volumes() {
+    use forensic_rs::{traits::registry::RegValue, utils::testing::TestingRegistry};
+    let mut prefetches = Vec::new();
+    for version in ["17", "23", "26", "30"] {
+        let mut fs = ChRootFileSystem::new(
+            format!("./artifacts/{}", version),
+            Box::new(StdVirtualFS::new()),
+        );
+        prefetches.extend(scan_prefetch_report(&mut fs).unwrap().prefetches);
+    }
+    let mut registry = TestingRegistry::new();
+    registry.add_value(
+        &format!(
+            r"HKLM\{}\_??_USBSTOR#Disk&Ven_SanDisk&Prod_Cruzer&Rev_1.00#4C530001230718116023&0#{{53f56307-b6bf-11d0-94f2-00a0c91efb8b}}CMDER_1727287740",
+            EMDMGMT_KEY
+        ),
+        "LastTestedTime",
+        RegValue::QWord(0),
+    );
+    let history = RemovableDeviceHistory::from_registry(&registry).unwrap();
+    let detector = RemovableMediaDetector::with_history(history);
+    let found = detector.detect_all(&prefetches);
+    assert_eq!(1, found.len());
+    assert_eq!("CMD.EXE", found[0].prefetch.name);
+    let device = found[0].device.as_ref().unwrap();
+    assert_eq!("CMDER", device.volume.label);
+    assert_eq!(Some("SanDisk"), device.vendor.as_deref());
+    // USBSTOR key deleted: the device comes only from EMDMgmt
+    assert!(device.device.is_none());
+}
+
+#[test]
+fn should_detect_files_loaded_from_mounted_images() {
+    let mut prefetches = Vec::new();
+    for version in ["17", "23", "26", "30"] {
+        let mut fs = ChRootFileSystem::new(
+            format!("./artifacts/{}", version),
+            Box::new(StdVirtualFS::new()),
+        );
+        prefetches.extend(scan_prefetch_report(&mut fs).unwrap().prefetches);
+    }
+    let detector = MountedImageDetector::new();
+    assert!(detector.detect_all(&prefetches).is_empty());
+
+    let mut prefetch = prefetches
+        .into_iter()
+        .find(|v| v.hash == 0xD269B812)
+        .unwrap();
+    let position = prefetch
+        .volume
+        .iter()
+        .position(|v| v.serial_number == 0x66f451bc)
+        .unwrap();
+    let original = prefetch.clone();
+    // Volume created two days before the last run, without NTFS references: an ISO mounted on Windows 10
+    let volume = &mut prefetch.volume[position];
+    volume.creation_time = prefetch.last_run_times[0].filetime() - 2 * 864_000_000_000 - 1;
+    volume.file_references.clear();
+    let found = detector.detect(&prefetch);
+    assert_eq!(1, found.len());
+    assert_eq!(MountedImageConfidence::Medium, found[0].confidence);
+    assert!(found[0]
+        .indicators
+        .contains(&MountedImageIndicator::RecentVolume { days_before_run: 2 }));
+    assert!(found[0]
+        .indicators
+        .contains(&MountedImageIndicator::NoFileReferences));
+    assert!(!found[0].executable);
+    assert!(found[0]
+        .loaded
+        .iter()
+        .any(|v| v.file.ends_with(r"\CLINK_DLL_X64.DLL")));
+
+    // The real volume was not created recently: the device path alone passes the default threshold
+    let mut prefetch = original.clone();
+    prefetch.volume[position].device_path = r"\DEVICE\CDROM0".into();
+    let found = detector.detect(&prefetch);
+    assert_eq!(1, found.len());
+    assert_eq!(MountedImageConfidence::High, found[0].confidence);
+    assert!(found[0]
+        .indicators
+        .contains(&MountedImageIndicator::OpticalDevice));
+
+    // With the real creation time, 30 days before the last run, the missing references are enough
+    let mut prefetch = original;
+    let volume = &mut prefetch.volume[position];
+    volume.file_references.clear();
+    volume.directory_strings.truncate(1);
+    let found = detector.detect(&prefetch);
+    assert_eq!(1, found.len());
+    assert_eq!(MountedImageConfidence::Medium, found[0].confidence);
+    assert!(found[0]
+        .indicators
+        .contains(&MountedImageIndicator::RecentVolume {
+            days_before_run: 30
+        }));
+
+    // Not recent for a stricter window: only the low threshold reports it
+    let strict = MountedImageDetector {
+        recent_days: 7,
+        ..Default::default()
+    };
+    assert!(strict.detect(&prefetch).is_empty());
+    let low = MountedImageDetector {
+        min_confidence: MountedImageConfidence::Low,
+        ..strict
+    };
+    let found = low.detect(&prefetch);
+    assert_eq!(1, found.len());
+    assert_eq!(MountedImageConfidence::Low, found[0].confidence);
+}
+
+#[test]
+fn should_summarize_path_categories() {
+    let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+    let prefetches = scan_prefetch_report(&mut fs).unwrap().prefetches;
+    let rust_out = prefetches
+        .iter()
+        .find(|v| v.name == "RUST_OUT.EXE")
+        .unwrap();
+    let summary = rust_out.path_categories();
+    assert_eq!(PathCategory::Temp, summary.executable);
+    assert!(summary.executable.is_user_writable());
+    assert!(summary.files_in(PathCategory::System32) > 0);
+    assert!(summary.contains(PathCategory::AppData));
+    assert_eq!(
+        rust_out.metrics.len(),
+        summary.files.values().sum::<usize>()
+    );
+    assert_eq!(Some("GORN"), rust_out.user());
+
+    let mut fs = ChRootFileSystem::new("./artifacts/26", Box::new(StdVirtualFS::new()));
+    let cmd = &scan_prefetch_report(&mut fs).unwrap().prefetches[0];
+    assert_eq!(PathCategory::System32, cmd.path_categories().executable);
+}
+
+#[test]
+fn should_resolve_ambiguous_and_truncated_executable_paths() {
+    let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+    let prefetches = scan_prefetch_report(&mut fs).unwrap().prefetches;
+    // SYSTEM32 and SYSWOW64 copies of CMD.EXE: the hash selects SYSWOW64
+    let mut cmd = prefetches
+        .iter()
+        .find(|v| v.hash == 0x6D6290C5)
+        .unwrap()
+        .clone();
+    let mut system32 = cmd.metrics[0].clone();
+    system32.file = r"\VOLUME{01d861d36906cd20-22690e22}\WINDOWS\SYSTEM32\CMD.EXE".into();
+    cmd.metrics.insert(0, system32);
+    // The metrics changed after parsing, so the executable is resolved again
+    cmd.executable_index = cmd.resolve_executable().index();
+    let resolved = cmd.resolve_executable();
+    assert_eq!(2, resolved.candidates.len());
+    assert_eq!(ExecutableConfidence::High, resolved.confidence);
+    assert!(resolved.path.ends_with(r"\SYSWOW64\CMD.EXE"));
+    assert_eq!(
+        Some(r"\DEVICE\HARDDISKVOLUME3\WINDOWS\SYSWOW64\CMD.EXE"),
+        resolved.candidates[0].hash_device_path.as_deref()
+    );
+    assert_eq!(resolved.path, cmd.executable_path());
+    // Without the hash both copies are equally likely
+    cmd.hash = 0;
+    cmd.file_information.hash_string = None;
+    assert_eq!(
+        ExecutableConfidence::Low,
+        cmd.resolve_executable().confidence
+    );
+
+    // Names longer than 29 characters are truncated in the header
+    let mut rust_out = prefetches
+        .iter()
+        .find(|v| v.name == "RUST_OUT.EXE")
+        .unwrap()
+        .clone();
+    let position = rust_out
+        .metrics
+        .iter()
+        .position(|v| v.file.ends_with(r"\RUST_OUT.EXE"))
+        .unwrap();
+    rust_out.metrics[position].file = rust_out.metrics[position]
+        .file
+        .replace("RUST_OUT.EXE", "RUST_OUT_WITH_A_VERY_LONG_NAME.EXE");
+    rust_out.name = "RUST_OUT_WITH_A_VERY_LONG_NAM".into();
+    let resolved = rust_out.resolve_executable();
+    assert!(resolved.is_truncated());
+    assert_eq!(
+        ExecutableNameMatch::Truncated,
+        resolved.candidates[0].name_match
+    );
+    assert_eq!(ExecutableConfidence::Medium, resolved.confidence);
+    assert!(rust_out
+        .executable_path()
+        .ends_with(r"\RUST_OUT_WITH_A_VERY_LONG_NAME.EXE"));
+}
+
+#[test]
+fn should_attribute_activity_to_the_best_user() {
+    use forensic_rs::{traits::registry::RegValue, utils::testing::TestingRegistry};
+    let mut fs = StdVirtualFS::new();
+    let file = fs
+        .open(Path::new(
+            "./artifacts/30/C/Windows/Prefetch/RUST_OUT.EXE-5D2C8541.pf",
+        ))
+        .unwrap();
+    let prefetch = read_prefetch_file("RUST_OUT.EXE-5D2C8541.pf", file).unwrap();
+    let activity = prefetch.activity().next().unwrap();
+    assert_eq!("GORN", activity.user);
+    assert_eq!(Some("GORN"), prefetch.user());
+
+    let mut registry = TestingRegistry::new();
+    registry.add_value(
+        &format!(
+            r"HKLM\{}\S-1-5-21-1366093794-4292800403-1155380978-1001",
+            PROFILE_LIST_KEY
+        ),
+        "ProfileImagePath",
+        RegValue::ExpandSZ(r"C:\Users\gorn".into()),
+    );
+    let profiles = ProfileList::from_registry(&registry).unwrap();
+    let mut activities = PrefetchActivityIterator::with_profiles(&prefetch, Some(&profiles));
+    let best = activities.attribution().best().unwrap();
+    assert_eq!(1.0, best.confidence);
+    assert!(activities.attribution().others().is_empty());
+    assert_eq!(
+        "S-1-5-21-1366093794-4292800403-1155380978-1001",
+        activities.next().unwrap().user
+    );
+}
+
+#[test]
+fn should_add_first_run_estimate_to_the_timeline() {
+    use forensic_rs::traits::forensic::TimeContext;
+    for version in ["17", "23", "26", "30"] {
+        let mut fs = ChRootFileSystem::new(
+            format!("./artifacts/{}", version),
+            Box::new(StdVirtualFS::new()),
+        );
+        for prefetch in scan_prefetch_report(&mut fs).unwrap().prefetches {
+            assert!(prefetch.file_times.is_some());
+            let oldest = prefetch
+                .last_run_times
+                .iter()
+                .map(|v| v.filetime())
+                .filter(|v| *v != 0)
+                .min()
+                .unwrap();
+            let estimate = prefetch.first_run_estimate();
+            if let Some(estimate) = estimate {
+                // The first execution can not be later than the oldest run
+                assert!(estimate.time.filetime() <= oldest);
+            }
+            if prefetch.run_count as usize <= prefetch.last_run_times.len() {
+                assert_eq!(Some(FirstRunSource::RunTimes), estimate.map(|v| v.source));
+            }
+            let timeline = prefetch.timeline();
+            let (size, _) = timeline.size_hint();
+            let events: Vec<_> = timeline.collect();
+            assert_eq!(size, events.len());
+            match estimate {
+                Some(estimate) if estimate.source == FirstRunSource::FileCreation => {
+                    assert_eq!(prefetch.last_run_times.len() + 1, events.len());
+                    let first_run = events.last().unwrap();
+                    assert_eq!(estimate.time.filetime(), first_run.time.filetime());
+                    match &first_run.time_context {
+                        TimeContext::Other(v) => assert_eq!(FIRST_RUN_TIME_CONTEXT, v),
+                        _ => panic!("The first run event must have its own context"),
+                    }
+                }
+                // The oldest run time already has its event
+                _ => assert_eq!(prefetch.last_run_times.len(), events.len()),
+            }
+        }
+    }
+    // Parsed from memory: no filesystem timestamps
+    let mut fs = StdVirtualFS::new();
+    let buffer = fs
+        .read_all(Path::new(
+            "./artifacts/30/C/Windows/Prefetch/RUST_OUT.EXE-5D2C8541.pf",
+        ))
+        .unwrap();
+    let prefetch = read_prefetch_bytes("RUST_OUT.EXE-5D2C8541.pf", &buffer).unwrap();
+    assert!(prefetch.file_times.is_none());
+}
+
+#[test]
+fn should_find_wiped_and_timestomped_prefetch() {
+    use crate::consistency::{FindingSeverity, TimestampFindingKind};
+    for version in ["17", "23", "26", "30"] {
+        let mut fs = ChRootFileSystem::new(
+            format!("./artifacts/{}", version),
+            Box::new(StdVirtualFS::new()),
+        );
+        for prefetch in scan_prefetch_report(&mut fs).unwrap().prefetches {
+            assert!(prefetch.timestamp_findings().is_empty());
+        }
+    }
+    let mut fs = StdVirtualFS::new();
+    let file = fs
+        .open(Path::new(
+            "./artifacts/30/C/Windows/Prefetch/POWERSHELL.EXE-AE8EDC9B.pf",
+        ))
+        .unwrap();
+    let mut prefetch = read_prefetch_file("POWERSHELL.EXE-AE8EDC9B.pf", file).unwrap();
+    let last_run = prefetch.file_information.last_run_time_slots[0];
+    // Wiped run times and the file timestamps copied from an older file
+    prefetch.file_information.last_run_time_slots[2] = 0;
+    prefetch.file_information.last_run_time_slots[5] = 0;
+    prefetch.file_times.as_mut().unwrap().modified = Some(Filetime::new(last_run - 36_000_000_000));
+    let findings = prefetch.timestamp_findings();
+    assert_eq!(2, findings.len());
+    assert_eq!(
+        TimestampFindingKind::ZeroedRunTimeSlots {
+            run_count: 2945,
+            slots: vec![2, 5]
+        },
+        findings[0].kind
+    );
+    assert!(matches!(
+        findings[1].kind,
+        TimestampFindingKind::FileModifiedBeforeLastRun { .. }
+    ));
+    assert_eq!(FindingSeverity::High, findings[1].severity);
+}
+
+#[test]
+fn should_report_prefetcher_configuration_in_scan() {
+    use crate::prefetcher::{MissingPrefetchReason, PREFETCH_PARAMETERS_KEY, SYSMAIN_KEY};
+    use crate::scan::scan_prefetch_report_with_registry;
+    use forensic_rs::{traits::registry::RegValue, utils::testing::TestingRegistry};
+    let mut registry = TestingRegistry::new();
+    registry.add_value(
+        &format!(r"HKLM\{}", PREFETCH_PARAMETERS_KEY),
+        "EnablePrefetcher",
+        RegValue::DWord(3),
+    );
+    registry.add_value(
+        &format!(r"HKLM\{}", SYSMAIN_KEY),
+        "Start",
+        RegValue::DWord(2),
+    );
+    let mut fs = ChRootFileSystem::new("./artifacts/30", Box::new(StdVirtualFS::new()));
+    let report = scan_prefetch_report_with_registry(&mut fs, &registry).unwrap();
+    assert_eq!(4, report.prefetches.len());
+    assert!(report.missing_folder.is_none());
+    assert!(!report.configuration.unwrap().is_disabled());
+    // Prefetching enabled but no folder
+    let mut fs = ChRootFileSystem::new("./artifacts/none", Box::new(StdVirtualFS::new()));
+    let report = scan_prefetch_report_with_registry(&mut fs, &registry).unwrap();
+    assert_eq!(
+        Some(MissingPrefetchReason::LikelyWiped),
+        report.missing_folder
+    );
+    // The folder exists but the prefetch files were deleted
+    let root = std::env::temp_dir().join("frnsc_prefetch_wiped");
+    let _ = std::fs::remove_dir_all(&root);
+    let folder = root.join("C").join("Windows").join("Prefetch");
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("Layout.ini"), b"").unwrap();
+    let mut fs = ChRootFileSystem::new(&root, Box::new(StdVirtualFS::new()));
+    let report = scan_prefetch_report_with_registry(&mut fs, &registry).unwrap();
+    assert_eq!(1, report.skipped.len());
+    // A folder that cannot be read is an error, not a missing folder
+    let unreadable = std::env::temp_dir().join("frnsc_prefetch_unreadable");
+    let _ = std::fs::remove_dir_all(&unreadable);
+    std::fs::create_dir_all(unreadable.join("C").join("Windows")).unwrap();
+    std::fs::write(unreadable.join("C").join("Windows").join("Prefetch"), b"").unwrap();
+    let mut unreadable_fs = ChRootFileSystem::new(&unreadable, Box::new(StdVirtualFS::new()));
+    assert!(scan_prefetch_report_with_registry(&mut unreadable_fs, &registry).is_err());
+    let _ = std::fs::remove_dir_all(&unreadable);
+    assert_eq!(
+        Some(MissingPrefetchReason::LikelyWiped),
+        report.missing_folder
+    );
+    registry.add_value(
+        &format!(r"HKLM\{}", PREFETCH_PARAMETERS_KEY),
+        "EnablePrefetcher",
+        RegValue::DWord(0),
+    );
+    let report = scan_prefetch_report_with_registry(&mut fs, &registry).unwrap();
+    assert_eq!(
+        Some(MissingPrefetchReason::DisabledByConfiguration),
+        report.missing_folder
+    );
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn should_line_up_used_runs_with_the_run_count() {
+    use crate::runs::{metric_used_runs, tracked_runs};
+    let mut fs = StdVirtualFS::new();
+    for (path, expected) in [
+        // Version 17 is not verified: 5 runs marked for a run count of 2
+        (
+            "./artifacts/17/C/Windows/Prefetch/CMD.EXE-087B4001.pf",
+            None,
+        ),
+        (
+            "./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf",
+            Some(0b0000_0011),
+        ),
+        (
+            "./artifacts/26/C/Windows/Prefetch/CMD.EXE-4A81B364.pf",
+            Some(0b0000_0011),
+        ),
+        (
+            "./artifacts/30/C/Windows/Prefetch/CMD.EXE-6D6290C5.pf",
+            Some(0b0000_1111),
+        ),
+    ] {
+        let file = fs.open(Path::new(path)).unwrap();
+        let name = Path::new(path).file_name().unwrap().to_str().unwrap();
+        let prefetch = read_prefetch_file(name, file).unwrap();
+        let ntdll = prefetch
+            .metrics
+            .iter()
+            .find(|v| v.file.ends_with(r"\NTDLL.DLL"))
+            .unwrap();
+        assert_eq!(
+            expected,
+            metric_used_runs(prefetch.version, ntdll),
+            "{}",
+            path
+        );
+        let runs = match prefetch.run_dependencies() {
+            Some(v) => v,
+            None => {
+                assert!(expected.is_none());
+                continue;
+            }
+        };
+        assert_eq!(tracked_runs(&prefetch), runs.len());
+        assert!(runs
+            .iter()
+            .all(|run| run.metrics.iter().any(|v| v.file == ntdll.file)));
+    }
+}
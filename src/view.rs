@@ -0,0 +1,714 @@
+use forensic_rs::{
+    err::{ForensicError, ForensicResult},
+    notifications::NotificationType,
+    notify_low,
+    utils::time::Filetime,
+};
+
+use crate::{
+    common::{
+        u16_at_pos, u32_at_pos, u64_at_pos, utf16_at_offset, Metric, NtfsFile,
+        PrefetchFileInformation, PrefetchFlag, Trace, UnknownField, VolumeInformation,
+    },
+    error::{InStage, ParseStage, PrefetchParseError, PrefetchParseResult},
+    trace::{TraceChain, TraceChainEntry},
+};
+
+/// Sizes and positions of the structures that change between format versions
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct VersionLayout {
+    pub version: u32,
+    pub file_information_size: usize,
+    pub metric_size: usize,
+    pub metric_filename_offset: usize,
+    pub trace_size: usize,
+    pub volume_size: usize,
+    pub file_references_header: usize,
+    pub last_run_time_offset: usize,
+    pub run_time_slots: usize,
+    pub run_count_offset: usize,
+    /// Fields of the file information with unknown meaning, relative to its start
+    pub unknown_ranges: &'static [(usize, usize)],
+    /// The file information stores the offset and size of the hash string at 128 and 132
+    pub hash_string: bool,
+}
+
+impl VersionLayout {
+    /// Selects the layout from the format version and the size of the file information structure
+    pub(crate) fn new(version: u32, file_information_size: usize) -> ForensicResult<Self> {
+        let trace_size = if version >= 30 { 8 } else { 12 };
+        Ok(match (version, file_information_size) {
+            (17, 68) => Self {
+                version,
+                file_information_size,
+                metric_size: 20,
+                metric_filename_offset: 8,
+                trace_size,
+                volume_size: 40,
+                file_references_header: 8,
+                last_run_time_offset: 36,
+                run_time_slots: 1,
+                run_count_offset: 60,
+                unknown_ranges: &[(44, 60), (64, 68)],
+                hash_string: false,
+            },
+            (23, 156) => Self {
+                version,
+                file_information_size,
+                metric_size: 32,
+                metric_filename_offset: 12,
+                trace_size,
+                volume_size: 104,
+                file_references_header: 16,
+                last_run_time_offset: 44,
+                run_time_slots: 1,
+                run_count_offset: 68,
+                unknown_ranges: &[(36, 44), (52, 68), (72, 76), (76, 156)],
+                hash_string: false,
+            },
+            (26 | 30 | 31, 224) => Self {
+                version,
+                file_information_size,
+                metric_size: 32,
+                metric_filename_offset: 12,
+                trace_size,
+                volume_size: if version == 26 { 104 } else { 96 },
+                file_references_header: 16,
+                last_run_time_offset: 44,
+                run_time_slots: 8,
+                run_count_offset: 124,
+                unknown_ranges: &[(36, 44), (108, 124), (128, 132), (132, 136), (136, 224)],
+                hash_string: false,
+            },
+            (30 | 31, 212) => Self {
+                version,
+                file_information_size,
+                metric_size: 32,
+                metric_filename_offset: 12,
+                trace_size,
+                volume_size: 96,
+                file_references_header: 16,
+                last_run_time_offset: 44,
+                run_time_slots: 8,
+                run_count_offset: 116,
+                unknown_ranges: &[(36, 44), (108, 116), (120, 124), (124, 128), (136, 212)],
+                hash_string: true,
+            },
+            _ => {
+                return Err(ForensicError::bad_format_string(format!(
+                    "Unknown layout for prefetch version {} with file information of {} bytes",
+                    version, file_information_size
+                )))
+            }
+        })
+    }
+
+    /// Detects the layout of a decompressed prefetch. Version 30 has two variants told apart by the metrics offset.
+    pub(crate) fn detect(buffer: &[u8]) -> ForensicResult<Self> {
+        if buffer.len() < 88 {
+            return Err(ForensicError::bad_format_str(
+                "The prefetch is smaller than its header",
+            ));
+        }
+        let version = u32_at_pos(buffer, 0);
+        let size = match version {
+            17 => 68,
+            23 => 156,
+            26 => 224,
+            30 | 31 if u32_at_pos(buffer, 84) == 304 => 224,
+            30 | 31 => 212,
+            _ => {
+                notify_low!(
+                    NotificationType::Informational,
+                    "The prefetch version is unknown: {}",
+                    version
+                );
+                return Err(ForensicError::bad_format_string(format!(
+                    "The prefetch version is unknown: {}",
+                    version
+                )));
+            }
+        };
+        Self::new(version, size)
+    }
+}
+
+/// UTF-16LE string borrowed from the prefetch buffer. Decoding stops at the first NUL character.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Utf16Str<'a>(&'a [u8]);
+
+impl<'a> Utf16Str<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        let end = bytes
+            .chunks_exact(2)
+            .position(|v| v == [0, 0])
+            .unwrap_or(bytes.len() / 2);
+        Self(&bytes[..end * 2])
+    }
+    /// Raw UTF-16LE bytes without the NUL terminator
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+    /// Number of UTF-16 code units
+    pub fn len(&self) -> usize {
+        self.0.len() / 2
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Decodes the characters, invalid surrogates are replaced by U+FFFD
+    pub fn chars(&self) -> impl Iterator<Item = char> + 'a {
+        char::decode_utf16(
+            self.0
+                .chunks_exact(2)
+                .map(|v| u16::from_le_bytes([v[0], v[1]])),
+        )
+        .map(|v| v.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+    /// Compares with a text without decoding the string
+    pub fn eq_str(&self, text: &str) -> bool {
+        self.chars().eq(text.chars())
+    }
+}
+
+impl core::fmt::Display for Utf16Str<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.chars()
+            .try_for_each(|v| core::fmt::Write::write_char(f, v))
+    }
+}
+
+impl core::fmt::Debug for Utf16Str<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+/// Read-only view of a decompressed prefetch file. Values are decoded when they are accessed, without allocations.
+///
+/// ```rust
+/// use forensic_rs::prelude::*;
+/// use frnsc_prefetch::view::PrefetchView;
+/// use std::path::Path;
+/// let mut fs = StdVirtualFS::new();
+/// let buffer = fs.read_all(Path::new("./artifacts/23/C/Windows/Prefetch/NOTEPAD.EXE-D8414F97.pf")).unwrap();
+/// let view = PrefetchView::new(&buffer).unwrap();
+/// assert!(view.executable_name().eq_str("NOTEPAD.EXE"));
+/// let _last_run = view.last_run_times().next().unwrap();
+/// let libraries = view.metrics().filter_map(|v| v.ok()).count();
+/// assert_eq!(view.metric_count(), libraries);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct PrefetchView<'a> {
+    buffer: &'a [u8],
+    layout: VersionLayout,
+    metrics: &'a [u8],
+    traces: &'a [u8],
+    strings: &'a [u8],
+    volumes: &'a [u8],
+    volume_count: usize,
+}
+
+impl<'a> PrefetchView<'a> {
+    /// Checks the header and the position of each table of a decompressed prefetch
+    pub fn new(buffer: &'a [u8]) -> ForensicResult<Self> {
+        Ok(Self::new_staged(buffer)?)
+    }
+
+    /// Same as [`PrefetchView::new`] keeping the stage of the table that is out of the buffer
+    pub(crate) fn new_staged(buffer: &'a [u8]) -> PrefetchParseResult<Self> {
+        let layout = VersionLayout::detect(buffer).in_stage(ParseStage::Header)?;
+        if &buffer[4..8] != b"SCCA" {
+            return Err(PrefetchParseError::new(
+                ParseStage::Header,
+                ForensicError::bad_format_str("Invalid prefetch signature"),
+            ));
+        }
+        if buffer.len() < 84 + layout.file_information_size {
+            return Err(PrefetchParseError::new(
+                ParseStage::Header,
+                ForensicError::bad_format_str(
+                    "The file information is greater than the file buffer",
+                ),
+            ));
+        }
+        let info = &buffer[84..];
+        let table = |offset: usize, count: usize, size: usize, error: &'static str| {
+            let start = u32_at_pos(info, offset) as usize;
+            let length = u32_at_pos(info, count) as usize * size;
+            buffer
+                .get(start..start + length)
+                .ok_or_else(|| ForensicError::bad_format_str(error))
+        };
+        let metrics = table(
+            0,
+            4,
+            layout.metric_size,
+            "The metrics array position is greater than the file buffer",
+        )
+        .in_stage(ParseStage::Metrics)?;
+        let traces = table(
+            8,
+            12,
+            layout.trace_size,
+            "The trace array position is greater than the file buffer",
+        )
+        .in_stage(ParseStage::Metrics)?;
+        let strings = table(
+            16,
+            20,
+            1,
+            "The filename strings position is greater than the file buffer",
+        )
+        .in_stage(ParseStage::Metrics)?;
+        let volumes = table(
+            24,
+            32,
+            1,
+            "The volume information position is greater than the file buffer",
+        )
+        .in_stage(ParseStage::Volumes)?;
+        let volume_count = u32_at_pos(info, 28) as usize;
+        if volume_count * layout.volume_size > volumes.len() {
+            return Err(PrefetchParseError::new(
+                ParseStage::Volumes,
+                ForensicError::bad_format_str(
+                    "The volume entries are greater than the volume information",
+                ),
+            ));
+        }
+        Ok(Self {
+            buffer,
+            layout,
+            metrics,
+            traces,
+            strings,
+            volumes,
+            volume_count,
+        })
+    }
+    /// Decompressed prefetch data
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buffer
+    }
+    pub fn version(&self) -> u32 {
+        self.layout.version
+    }
+    pub fn executable_name(&self) -> Utf16Str<'a> {
+        Utf16Str::new(&self.buffer[16..76])
+    }
+    pub fn hash(&self) -> u32 {
+        u32_at_pos(self.buffer, 76)
+    }
+    pub fn file_size(&self) -> u32 {
+        u32_at_pos(self.buffer, 12)
+    }
+    pub fn run_count(&self) -> u32 {
+        u32_at_pos(self.buffer, 84 + self.layout.run_count_offset)
+    }
+    /// Last execution times. Empty slots are skipped in versions with 8 slots.
+    pub fn last_run_times(&self) -> impl Iterator<Item = Filetime> + 'a {
+        let start = 84 + self.layout.last_run_time_offset;
+        let slots = self.layout.run_time_slots;
+        self.buffer[start..start + slots * 8]
+            .chunks_exact(8)
+            .map(|v| u64_at_pos(v, 0))
+            .filter(move |&v| v != 0 || slots == 1)
+            .map(Filetime::new)
+    }
+    pub fn metric_count(&self) -> usize {
+        self.metrics.len() / self.layout.metric_size
+    }
+    pub fn trace_count(&self) -> usize {
+        self.traces.len() / self.layout.trace_size
+    }
+    pub fn volume_count(&self) -> usize {
+        self.volume_count
+    }
+    /// Files loaded by the executable. An entry whose name or traces are outside the file is an error.
+    pub fn metrics(&self) -> impl Iterator<Item = ForensicResult<MetricView<'a>>> + 'a {
+        let view = *self;
+        self.metrics
+            .chunks_exact(self.layout.metric_size)
+            .map(move |entry| view.metric(entry))
+    }
+    /// Volumes accessed by the executable. An entry whose data is outside the volume information is an error.
+    pub fn volumes(&self) -> impl Iterator<Item = ForensicResult<VolumeView<'a>>> + 'a {
+        let view = *self;
+        self.volumes[..self.volume_count * self.layout.volume_size]
+            .chunks_exact(self.layout.volume_size)
+            .map(move |entry| view.volume(entry))
+    }
+    /// Decodes the file information structure, including the empty run time slots and the fields with unknown meaning
+    pub fn to_file_information(&self) -> PrefetchFileInformation {
+        let layout = &self.layout;
+        let info = &self.buffer[84..84 + layout.file_information_size];
+        let last_run_time_slots: Vec<u64> = info
+            [layout.last_run_time_offset..layout.last_run_time_offset + layout.run_time_slots * 8]
+            .chunks_exact(8)
+            .map(|v| u64_at_pos(v, 0))
+            .collect();
+        let (hash_string_offset, hash_string_size) = if layout.hash_string {
+            (u32_at_pos(info, 128), u32_at_pos(info, 132))
+        } else {
+            (0, 0)
+        };
+        let hash_string = if hash_string_offset == 0 {
+            None
+        } else {
+            utf16_at_offset(
+                self.buffer,
+                hash_string_offset as usize,
+                hash_string_size as usize,
+            )
+            .ok()
+        };
+        PrefetchFileInformation {
+            size: layout.file_information_size as u32,
+            metrics_offsets: u32_at_pos(info, 0),
+            metrics_count: u32_at_pos(info, 4),
+            trace_chain_offset: u32_at_pos(info, 8),
+            trace_chain_count: u32_at_pos(info, 12),
+            filename_string_offset: u32_at_pos(info, 16),
+            filename_string_size: u32_at_pos(info, 20),
+            volume_information_offset: u32_at_pos(info, 24),
+            volume_count: u32_at_pos(info, 28),
+            volume_information_size: u32_at_pos(info, 32),
+            last_run_times: self.last_run_times().collect(),
+            last_run_time_slots,
+            run_count: self.run_count(),
+            hash_string_offset,
+            hash_string_size,
+            hash_string,
+            unknown: layout
+                .unknown_ranges
+                .iter()
+                .map(|&(start, end)| UnknownField {
+                    offset: 84 + start,
+                    data: info[start..end].to_vec(),
+                })
+                .collect(),
+        }
+    }
+    /// Every entry of the trace chain array, in array order
+    pub fn trace_chain_entries(&self) -> impl Iterator<Item = TraceChainEntry> + 'a {
+        self.traces
+            .chunks_exact(self.layout.trace_size)
+            .enumerate()
+            .filter_map(|(i, entry)| TraceChainEntry::from_bytes(i as u32, entry).ok())
+    }
+    /// Follows the trace chain that starts at `first_index` and records its anomalies
+    pub fn trace_chain(&self, first_index: u32, count: u32) -> TraceChain {
+        TraceChain::follow_bytes(
+            self.layout.version,
+            self.traces,
+            self.layout.trace_size,
+            first_index,
+            count,
+        )
+    }
+    /// Decodes all the metrics
+    pub fn to_metrics(&self) -> ForensicResult<Vec<Metric>> {
+        self.metrics().map(|v| v.map(|v| v.to_metric())).collect()
+    }
+    /// Decodes all the volumes
+    pub fn to_volumes(&self) -> ForensicResult<Vec<VolumeInformation>> {
+        self.volumes().map(|v| v?.to_volume()).collect()
+    }
+
+    fn metric(&self, entry: &'a [u8]) -> ForensicResult<MetricView<'a>> {
+        let trace_index = u32_at_pos(entry, 0) as usize;
+        let trace_count = u32_at_pos(entry, 4) as usize;
+        let filename_offset = u32_at_pos(entry, self.layout.metric_filename_offset) as usize;
+        let filename_characters =
+            u32_at_pos(entry, self.layout.metric_filename_offset + 4) as usize;
+        let file_name = self
+            .strings
+            .get(filename_offset..filename_offset + filename_characters * 2)
+            .ok_or_else(|| {
+                ForensicError::bad_format_str(
+                    "The metric file name is greater than the filename strings",
+                )
+            })?;
+        // Linked chains (versions 17, 23 and 26) are bounds checked entry by entry when they are followed
+        if self.layout.version >= 30
+            && (trace_index + trace_count) * self.layout.trace_size > self.traces.len()
+        {
+            return Err(ForensicError::bad_format_str(
+                "The trace array position is greater than the file buffer length",
+            ));
+        }
+        Ok(MetricView {
+            entry,
+            file_name: Utf16Str::new(file_name),
+            traces: self.traces,
+            trace_size: self.layout.trace_size,
+            version: self.layout.version,
+        })
+    }
+
+    fn volume(&self, entry: &'a [u8]) -> ForensicResult<VolumeView<'a>> {
+        let area = self.volumes;
+        let device_path_offset = u32_at_pos(entry, 0) as usize;
+        let device_path_characters = u32_at_pos(entry, 4) as usize;
+        let device_path = area
+            .get(device_path_offset..device_path_offset + device_path_characters * 2)
+            .ok_or_else(|| {
+                ForensicError::bad_format_str(
+                    "The device path position is greater than the volume buffer",
+                )
+            })?;
+        let file_references_offset = u32_at_pos(entry, 20) as usize;
+        let file_references_size = u32_at_pos(entry, 24) as usize;
+        let file_references = area
+            .get(file_references_offset..file_references_offset + file_references_size)
+            .ok_or_else(|| {
+                ForensicError::bad_format_str(
+                    "The files reference position is greater than the volume buffer",
+                )
+            })?;
+        let header = self.layout.file_references_header;
+        if file_references.len() < header {
+            return Err(ForensicError::Other(
+                "Invalid size for file references".into(),
+            ));
+        }
+        let reference_count = u32_at_pos(file_references, 4) as usize;
+        if header + reference_count * 8 > file_references.len() {
+            return Err(ForensicError::bad_format_str(
+                "The file reference size is greater than the buffer",
+            ));
+        }
+        let directory_strings_offset = u32_at_pos(entry, 28) as usize;
+        let directory_strings = area.get(directory_strings_offset..).ok_or_else(|| {
+            ForensicError::bad_format_str(
+                "The directory strings position is greater than the volume buffer",
+            )
+        })?;
+        Ok(VolumeView {
+            entry,
+            device_path: Utf16Str::new(device_path),
+            file_references: &file_references[header..header + reference_count * 8],
+            directory_strings,
+            directory_string_count: u32_at_pos(entry, 32) as usize,
+        })
+    }
+}
+
+/// File loaded by the executable, borrowed from the prefetch buffer
+#[derive(Debug, Clone, Copy)]
+pub struct MetricView<'a> {
+    entry: &'a [u8],
+    file_name: Utf16Str<'a>,
+    /// Whole trace chain array of the prefetch
+    traces: &'a [u8],
+    trace_size: usize,
+    version: u32,
+}
+
+impl<'a> MetricView<'a> {
+    /// Full path to the dependency
+    pub fn file_name(&self) -> Utf16Str<'a> {
+        self.file_name
+    }
+    pub fn flags(&self) -> PrefetchFlag {
+        let pos = if self.version == 17 { 16 } else { 20 };
+        u32_at_pos(self.entry, pos).into()
+    }
+    /// Number of blocks to be prefetched. Version 17 does not store it, the number of traces is used.
+    pub fn blocks_to_prefetch(&self) -> u32 {
+        let pos = if self.version == 17 { 4 } else { 8 };
+        u32_at_pos(self.entry, pos)
+    }
+    /// Index of the first trace chain entry of the dependency
+    pub fn trace_index(&self) -> u32 {
+        u32_at_pos(self.entry, 0)
+    }
+    /// Number of trace chain entries declared in the metric entry
+    pub fn trace_count(&self) -> usize {
+        u32_at_pos(self.entry, 4) as usize
+    }
+    /// NTFS reference of the file. Version 17 does not store it.
+    pub fn file_reference(&self) -> Option<NtfsFile> {
+        if self.version == 17 {
+            return None;
+        }
+        NtfsFile::from_reference(u64_at_pos(self.entry, 24))
+    }
+    /// Trace chain entries of the dependency in chain order, decoded when they are reached
+    pub fn trace_entries(&self) -> TraceChainEntries<'a> {
+        TraceChainEntries {
+            traces: self.traces,
+            trace_size: self.trace_size,
+            contiguous: self.version >= 30,
+            next: Some(self.trace_index()),
+            remaining: if self.version >= 30 {
+                self.trace_count()
+            } else {
+                self.traces.len() / self.trace_size
+            },
+        }
+    }
+    /// Follows the whole trace chain and records its anomalies. Allocates the entries, use `trace_entries` to walk it.
+    pub fn trace_chain(&self) -> TraceChain {
+        TraceChain::follow_bytes(
+            self.version,
+            self.traces,
+            self.trace_size,
+            self.trace_index(),
+            self.trace_count() as u32,
+        )
+    }
+    /// Traces for this dependency, in chain order
+    pub fn traces(&self) -> impl Iterator<Item = Trace> + 'a {
+        self.trace_entries().map(|v| v.to_trace())
+    }
+    pub fn to_metric(&self) -> Metric {
+        Metric {
+            file: self.file_name.to_string(),
+            flags: self.flags(),
+            blocks_to_prefetch: self.blocks_to_prefetch(),
+            trace_chain: self.trace_chain(),
+            mft_reference: self.file_reference(),
+        }
+    }
+}
+
+/// Iterator over the trace chain of a dependency. Versions 17, 23 and 26 follow the next index until the end marker,
+/// version 30 takes the declared number of consecutive entries. It stops at an index outside the array, and a chain
+/// longer than the array must contain a cycle, so the walk is limited to that many steps.
+#[derive(Debug, Clone)]
+pub struct TraceChainEntries<'a> {
+    traces: &'a [u8],
+    trace_size: usize,
+    contiguous: bool,
+    next: Option<u32>,
+    remaining: usize,
+}
+
+impl Iterator for TraceChainEntries<'_> {
+    type Item = TraceChainEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let index = self.next.take()?;
+        let pos = (index as usize).checked_mul(self.trace_size)?;
+        let entry = self.traces.get(pos..pos + self.trace_size)?;
+        let entry = TraceChainEntry::from_bytes(index, entry).ok()?;
+        self.next = if self.contiguous {
+            index.checked_add(1)
+        } else {
+            entry.next_index
+        };
+        Some(entry)
+    }
+}
+
+/// Volume information borrowed from the prefetch buffer
+#[derive(Debug, Clone, Copy)]
+pub struct VolumeView<'a> {
+    entry: &'a [u8],
+    device_path: Utf16Str<'a>,
+    file_references: &'a [u8],
+    directory_strings: &'a [u8],
+    directory_string_count: usize,
+}
+
+impl<'a> VolumeView<'a> {
+    pub fn device_path(&self) -> Utf16Str<'a> {
+        self.device_path
+    }
+    pub fn creation_time(&self) -> u64 {
+        u64_at_pos(self.entry, 8)
+    }
+    pub fn serial_number(&self) -> u32 {
+        u32_at_pos(self.entry, 16)
+    }
+    /// NTFS references of the files accessed in the volume. Empty references are skipped.
+    pub fn file_references(&self) -> impl Iterator<Item = NtfsFile> + 'a {
+        self.file_references
+            .chunks_exact(8)
+            .filter_map(|v| NtfsFile::from_reference(u64_at_pos(v, 0)))
+    }
+    /// Directories accessed in the volume
+    pub fn directory_strings(&self) -> DirectoryStrings<'a> {
+        DirectoryStrings {
+            data: self.directory_strings,
+            pos: 0,
+            remaining: self.directory_string_count,
+        }
+    }
+    pub fn to_volume(&self) -> ForensicResult<VolumeInformation> {
+        Ok(VolumeInformation {
+            device_path: self.device_path.to_string(),
+            file_references: self.file_references().collect(),
+            directory_strings: self
+                .directory_strings()
+                .map(|v| v.map(|v| v.to_string()))
+                .collect::<ForensicResult<_>>()?,
+            creation_time: self.creation_time(),
+            serial_number: self.serial_number(),
+        })
+    }
+}
+
+/// Iterator over the directory strings of a volume: length (u16), UTF-16 text and NUL terminator
+pub struct DirectoryStrings<'a> {
+    data: &'a [u8],
+    pos: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for DirectoryStrings<'a> {
+    type Item = ForensicResult<Utf16Str<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        if self.pos + 2 > self.data.len() {
+            self.remaining = 0;
+            return Some(Err(ForensicError::bad_format_str(
+                "The Directory String size is greater than the buffer size",
+            )));
+        }
+        let characters = u16_at_pos(self.data, self.pos) as usize;
+        let end = self.pos + 2 + characters * 2;
+        if end + 2 > self.data.len() {
+            self.remaining = 0;
+            return Some(Err(ForensicError::bad_format_str(
+                "The Directory String size is greater than the buffer size",
+            )));
+        }
+        let text = Utf16Str::new(&self.data[self.pos + 2..end]);
+        self.pos = end + 2;
+        Some(Ok(text))
+    }
+}
+
+#[test]
+fn should_stop_trace_chain_cycles() {
+    // Entries of 12 bytes: 0 -> 1 -> 0
+    let mut traces = vec![0u8; 24];
+    traces[0..4].copy_from_slice(&1u32.to_le_bytes());
+    let entries = TraceChainEntries {
+        traces: &traces,
+        trace_size: 12,
+        contiguous: false,
+        next: Some(0),
+        remaining: 2,
+    };
+    assert_eq!(vec![0, 1], entries.map(|v| v.index).collect::<Vec<_>>());
+    let entries = TraceChainEntries {
+        traces: &traces,
+        trace_size: 12,
+        contiguous: false,
+        next: Some(5),
+        remaining: 2,
+    };
+    assert_eq!(0, entries.count());
+}
@@ -0,0 +1,156 @@
+use forensic_rs::utils::time::Filetime;
+
+use crate::common::VolumeInformation;
+
+/// Volume part of a path stored in a prefetch file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeId<'a> {
+    /// `\VOLUME{01d962d37536cd21-a2691d2c}`: creation time and serial number of the volume (Windows 10 and later)
+    Identifier {
+        creation_time: u64,
+        serial_number: u32,
+    },
+    /// `\DEVICE\HARDDISKVOLUME2`: NT device of the volume (Windows XP to 8.1)
+    Device(&'a str),
+}
+
+/// Path of a loaded file or directory split into its volume and the path inside the volume
+///
+/// ```rust
+/// use frnsc_prefetch::volume_path::{VolumeId, VolumePath};
+/// let path = VolumePath::parse(r"\VOLUME{01d962d37536cd21-a2691d2c}\WINDOWS\SYSTEM32\NTDLL.DLL").unwrap();
+/// assert_eq!(VolumeId::Identifier { creation_time: 0x01d962d37536cd21, serial_number: 0xa2691d2c }, path.volume);
+/// assert_eq!(r"\WINDOWS\SYSTEM32\NTDLL.DLL", path.relative);
+/// let path = VolumePath::parse(r"\DEVICE\HARDDISKVOLUME2\WINDOWS\NOTEPAD.EXE").unwrap();
+/// assert_eq!(VolumeId::Device(r"\DEVICE\HARDDISKVOLUME2"), path.volume);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumePath<'a> {
+    pub volume: VolumeId<'a>,
+    /// Volume part of the path. Ex: `\VOLUME{01d962d37536cd21-a2691d2c}`
+    pub prefix: &'a str,
+    /// Path inside the volume starting with a backslash. Empty for the root of the volume.
+    pub relative: &'a str,
+}
+
+impl<'a> VolumePath<'a> {
+    /// Splits a `\VOLUME{<creation>-<serial>}\...` or `\DEVICE\<device>\...` path. Other paths return None.
+    pub fn parse(path: &'a str) -> Option<Self> {
+        let rest = path.strip_prefix('\\')?;
+        let (first, after_first) = split_component(rest);
+        let is_identifier = first
+            .get(..7)
+            .map(|v| v.eq_ignore_ascii_case("VOLUME{"))
+            .unwrap_or(false);
+        let (volume, prefix_length) = if is_identifier {
+            let (creation, serial) = first.get(7..)?.strip_suffix('}')?.split_once('-')?;
+            let volume = VolumeId::Identifier {
+                creation_time: u64::from_str_radix(creation, 16).ok()?,
+                serial_number: u32::from_str_radix(serial, 16).ok()?,
+            };
+            (volume, 1 + first.len())
+        } else if first.eq_ignore_ascii_case("DEVICE") {
+            let (device, _) = split_component(after_first?);
+            if device.is_empty() {
+                return None;
+            }
+            let prefix_length = 1 + first.len() + 1 + device.len();
+            (VolumeId::Device(&path[..prefix_length]), prefix_length)
+        } else {
+            return None;
+        };
+        let relative = &path[prefix_length..];
+        Some(Self {
+            volume,
+            prefix: &path[..prefix_length],
+            relative: if relative == "\\" { "" } else { relative },
+        })
+    }
+
+    /// Creation time of the volume stored in the path
+    pub fn creation_time(&self) -> Option<Filetime> {
+        match self.volume {
+            VolumeId::Identifier { creation_time, .. } => Some(Filetime::new(creation_time)),
+            VolumeId::Device(_) => None,
+        }
+    }
+
+    /// Serial number of the volume stored in the path
+    pub fn serial_number(&self) -> Option<u32> {
+        match self.volume {
+            VolumeId::Identifier { serial_number, .. } => Some(serial_number),
+            VolumeId::Device(_) => None,
+        }
+    }
+
+    /// The path belongs to the volume: same creation time and serial number or same device path
+    pub fn is_in(&self, volume: &VolumeInformation) -> bool {
+        match self.volume {
+            VolumeId::Identifier {
+                creation_time,
+                serial_number,
+            } => {
+                (volume.creation_time == creation_time && volume.serial_number == serial_number)
+                    || volume.device_path.eq_ignore_ascii_case(self.prefix)
+            }
+            VolumeId::Device(device) => volume.device_path.eq_ignore_ascii_case(device),
+        }
+    }
+
+    /// Position of the volume of the path in a list of volumes
+    pub fn position_in(&self, volumes: &[VolumeInformation]) -> Option<usize> {
+        volumes.iter().position(|v| self.is_in(v))
+    }
+}
+
+/// Path inside the volume of a prefetch path, or of a DOS path (`C:\...`) after `DriveMapper::rewrite_paths`
+pub(crate) fn relative_path(path: &str) -> Option<&str> {
+    VolumePath::parse(path)
+        .map(|v| v.relative)
+        .or_else(|| dos_relative_path(path))
+}
+
+/// Removes the drive letter of a DOS path. Ex: `C:\WINDOWS` -> `\WINDOWS`
+pub(crate) fn dos_relative_path(path: &str) -> Option<&str> {
+    let bytes = path.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        Some(&path[2..])
+    } else {
+        None
+    }
+}
+
+fn split_component(path: &str) -> (&str, Option<&str>) {
+    match path.split_once('\\') {
+        Some((component, rest)) => (component, Some(rest)),
+        None => (path, None),
+    }
+}
+
+#[test]
+fn should_parse_volume_paths() {
+    let path = VolumePath::parse(r"\DEVICE\HARDDISKVOLUME1\").unwrap();
+    assert_eq!(r"\DEVICE\HARDDISKVOLUME1", path.prefix);
+    assert_eq!("", path.relative);
+    let path = VolumePath::parse(r"\VOLUME{01d861d36906cd20-22690e22}").unwrap();
+    assert_eq!(Some(0x22690e22), path.serial_number());
+    assert_eq!("", path.relative);
+    assert!(VolumePath::parse(r"C:\WINDOWS\NOTEPAD.EXE").is_none());
+    assert!(VolumePath::parse(r"\VOLUME{nothex-22690e22}\A").is_none());
+    assert!(VolumePath::parse(r"\DEVICE\").is_none());
+    assert_eq!(Some(r"\WINDOWS"), relative_path(r"C:\WINDOWS"));
+    assert_eq!(
+        Some(r"\WINDOWS"),
+        relative_path(r"\DEVICE\HARDDISKVOLUME1\WINDOWS")
+    );
+    assert_eq!(None, relative_path(r"\\SERVER\SHARE"));
+    let volume = VolumeInformation {
+        device_path: r"\VOLUME{01d861d36906cd20-22690e22}".into(),
+        creation_time: 0x01d861d36906cd20,
+        serial_number: 0x22690e22,
+        ..Default::default()
+    };
+    let path = VolumePath::parse(r"\VOLUME{01D861D36906CD20-22690E22}\WINDOWS").unwrap();
+    assert!(path.is_in(&volume));
+    assert_eq!(Some(0), path.position_in(&[volume]));
+}